@@ -0,0 +1,9 @@
+-- Add migration script here
+CREATE TYPE ping_kind AS ENUM ('start', 'success', 'fail');
+
+ALTER TABLE ping
+    ADD COLUMN kind ping_kind NOT NULL DEFAULT 'success';
+
+-- Time elapsed between the start ping and the ping that finished the run
+ALTER TABLE ping
+    ADD COLUMN duration interval;
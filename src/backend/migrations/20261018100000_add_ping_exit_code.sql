@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE ping
+    ADD COLUMN exit_code integer;
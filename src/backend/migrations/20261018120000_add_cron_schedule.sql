@@ -0,0 +1,21 @@
+-- Add migration script here
+CREATE TYPE schedule_kind AS ENUM ('interval', 'cron');
+
+ALTER TABLE system
+    ADD COLUMN schedule_kind schedule_kind NOT NULL DEFAULT 'interval';
+
+ALTER TABLE system
+    ADD COLUMN cron_expression text;
+
+ALTER TABLE system
+    ADD COLUMN cron_timezone text;
+
+-- Cron systems don't have a fixed frequency
+ALTER TABLE system
+    ALTER COLUMN frequency DROP NOT NULL;
+
+ALTER TABLE system
+    ADD CONSTRAINT system_schedule_check CHECK (
+        (schedule_kind = 'interval' AND frequency IS NOT NULL)
+        OR (schedule_kind = 'cron' AND cron_expression IS NOT NULL AND cron_timezone IS NOT NULL)
+    );
@@ -0,0 +1,9 @@
+-- Add migration script here
+-- How much before an expected timestamp a ping still counts for it
+ALTER TABLE system
+    ADD COLUMN grace_early interval NOT NULL DEFAULT '0 minutes';
+
+-- How much after an expected timestamp a ping still counts for it (NULL means
+-- until the next expected timestamp, pings are never late)
+ALTER TABLE system
+    ADD COLUMN grace_late interval;
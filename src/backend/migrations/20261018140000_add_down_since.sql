@@ -0,0 +1,5 @@
+-- Add migration script here
+-- The time since which the system is down, set when the down email is sent and
+-- used to compute the duration of the outage once it is back up
+ALTER TABLE system
+    ADD COLUMN down_since timestamp;
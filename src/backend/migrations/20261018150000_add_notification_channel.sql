@@ -0,0 +1,12 @@
+-- Create the table for storing the notification channels (such as webhooks)
+CREATE TABLE IF NOT EXISTS notification_channel
+(
+    id         SERIAL PRIMARY KEY             NOT NULL,
+    user_id    integer REFERENCES "user" (id) NOT NULL,
+    -- NULL means that the channel applies to all the systems of the user
+    system_id  uuid REFERENCES system (id),
+    config     jsonb                          NOT NULL,
+    created_at timestamp                      NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS notification_channel_user_id_idx ON notification_channel (user_id);
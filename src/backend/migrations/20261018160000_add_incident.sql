@@ -0,0 +1,29 @@
+-- Create the table for storing the incidents (the outages of the systems)
+CREATE TABLE IF NOT EXISTS incident
+(
+    id         SERIAL PRIMARY KEY          NOT NULL,
+    system_id  uuid REFERENCES system (id) NOT NULL,
+    started_at timestamp                   NOT NULL,
+    -- NULL while the incident is open
+    ended_at   timestamp,
+    duration   interval
+);
+
+CREATE INDEX IF NOT EXISTS incident_system_id_idx ON incident (system_id, started_at DESC);
+
+-- At most one incident per system can be open
+CREATE UNIQUE INDEX IF NOT EXISTS incident_open_idx ON incident (system_id) WHERE ended_at IS NULL;
+
+-- Create the table for storing the notifications sent about the incidents
+CREATE TABLE IF NOT EXISTS incident_notification
+(
+    id          SERIAL PRIMARY KEY               NOT NULL,
+    incident_id integer REFERENCES incident (id) NOT NULL,
+    -- The event the notification was about (down or up)
+    event       text                             NOT NULL,
+    -- The kind of channel it was sent through (email, webhook, slack...)
+    channel     text                             NOT NULL,
+    sent_at     timestamp                        NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS incident_notification_incident_id_idx ON incident_notification (incident_id);
@@ -0,0 +1,17 @@
+-- Create the table for storing the personal API tokens of the users
+CREATE TABLE IF NOT EXISTS api_token
+(
+    id           SERIAL PRIMARY KEY             NOT NULL,
+    user_id      integer REFERENCES "user" (id) NOT NULL,
+    name         text                           NOT NULL,
+    -- The SHA-256 of the token, the token itself is only shown when it is created
+    token_hash   text                           NOT NULL UNIQUE,
+    -- Read-only tokens can only be used with GET requests
+    read_only    boolean                        NOT NULL DEFAULT FALSE,
+    -- The systems the token is restricted to, all the systems of the user if NULL
+    system_ids   uuid[],
+    created_at   timestamp                      NOT NULL DEFAULT NOW(),
+    last_used_at timestamp
+);
+
+CREATE INDEX IF NOT EXISTS api_token_user_id_idx ON api_token (user_id);
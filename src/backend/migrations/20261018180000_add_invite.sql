@@ -0,0 +1,21 @@
+-- The user seeded by the first migration took an ID without advancing the
+-- sequence, so the first sign up on a new instance would fail
+SELECT setval('user_id_seq', GREATEST((SELECT MAX(id) FROM "user"), 1));
+
+-- Create the table for storing the invites to sign up (when registration is
+-- invite-only)
+CREATE TABLE IF NOT EXISTS invite
+(
+    id         SERIAL PRIMARY KEY             NOT NULL,
+    created_by integer REFERENCES "user" (id) NOT NULL,
+    -- The SHA-256 of the invite code, the code itself is only shown when it is
+    -- created
+    code_hash  text                           NOT NULL UNIQUE,
+    created_at timestamp                      NOT NULL DEFAULT NOW(),
+    expires_at timestamp                      NOT NULL,
+    -- Both NULL until the invite is used
+    used_by    integer REFERENCES "user" (id),
+    used_at    timestamp
+);
+
+CREATE INDEX IF NOT EXISTS invite_created_by_idx ON invite (created_by);
@@ -0,0 +1,27 @@
+-- NULL until the user opens the link sent to their email address, the existing
+-- users have been receiving the alerts already, so their addresses are trusted
+ALTER TABLE "user"
+    ADD COLUMN IF NOT EXISTS email_verified_at timestamp;
+
+UPDATE "user"
+SET email_verified_at = NOW()
+WHERE email_verified_at IS NULL;
+
+CREATE TYPE email_token_kind AS ENUM ('verify_email', 'reset_password');
+
+-- Create the table for storing the single-use tokens sent by email
+CREATE TABLE IF NOT EXISTS email_token
+(
+    id         SERIAL PRIMARY KEY             NOT NULL,
+    user_id    integer REFERENCES "user" (id) NOT NULL,
+    kind       email_token_kind               NOT NULL,
+    -- The SHA-256 of the token, the token itself is only sent by email
+    token_hash text                           NOT NULL UNIQUE,
+    -- The address the token was sent to
+    email      text                           NOT NULL,
+    created_at timestamp                      NOT NULL DEFAULT NOW(),
+    expires_at timestamp                      NOT NULL,
+    used_at    timestamp
+);
+
+CREATE INDEX IF NOT EXISTS email_token_user_id_idx ON email_token (user_id);
@@ -0,0 +1,26 @@
+-- Create the table for storing the TOTP secrets of the users with 2FA
+CREATE TABLE IF NOT EXISTS totp
+(
+    user_id        integer PRIMARY KEY REFERENCES "user" (id) NOT NULL,
+    -- The base32 secret shared with the authenticator app
+    secret         text                                       NOT NULL,
+    created_at     timestamp                                  NOT NULL DEFAULT NOW(),
+    -- NULL until the user enters a code from the authenticator app, 2FA is only
+    -- required to log in once confirmed
+    confirmed_at   timestamp,
+    -- The time step of the last code used, so that each code can only be used once
+    last_used_step bigint
+);
+
+-- Create the table for storing the recovery codes, to log in without the
+-- authenticator app
+CREATE TABLE IF NOT EXISTS recovery_code
+(
+    id        SERIAL PRIMARY KEY             NOT NULL,
+    user_id   integer REFERENCES "user" (id) NOT NULL,
+    -- The SHA-256 of the code, the code itself is only shown when 2FA is confirmed
+    code_hash text                           NOT NULL UNIQUE,
+    used_at   timestamp
+);
+
+CREATE INDEX IF NOT EXISTS recovery_code_user_id_idx ON recovery_code (user_id);
@@ -0,0 +1,16 @@
+-- Create the table for storing the passkeys (WebAuthn credentials) of the users
+CREATE TABLE IF NOT EXISTS passkey
+(
+    id            SERIAL PRIMARY KEY             NOT NULL,
+    user_id       integer REFERENCES "user" (id) NOT NULL,
+    name          text                           NOT NULL,
+    -- The ID the authenticator assigned to the credential
+    credential_id bytea                          NOT NULL UNIQUE,
+    -- The whole credential as kept by webauthn-rs (public key, signature
+    -- counter, backup state, ...), as JSON
+    credential    jsonb                          NOT NULL,
+    created_at    timestamp                      NOT NULL DEFAULT NOW(),
+    last_used_at  timestamp
+);
+
+CREATE INDEX IF NOT EXISTS passkey_user_id_idx ON passkey (user_id);
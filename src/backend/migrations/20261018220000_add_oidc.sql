@@ -0,0 +1,8 @@
+-- The users created by single sign-on log in through the identity provider, so
+-- they may have no password
+ALTER TABLE "user"
+    ALTER COLUMN password DROP NOT NULL;
+
+-- The `sub` claim the identity provider identifies the user with
+ALTER TABLE "user"
+    ADD COLUMN IF NOT EXISTS oidc_subject text UNIQUE;
@@ -0,0 +1,48 @@
+CREATE TYPE organization_role AS ENUM ('owner', 'admin', 'editor', 'viewer');
+
+-- Create the table for storing the organizations, which share their systems
+-- with their members
+CREATE TABLE IF NOT EXISTS organization
+(
+    id         SERIAL PRIMARY KEY NOT NULL,
+    name       text               NOT NULL,
+    created_at timestamp          NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS organization_member
+(
+    organization_id integer REFERENCES organization (id) NOT NULL,
+    user_id         integer REFERENCES "user" (id)       NOT NULL,
+    role            organization_role                    NOT NULL,
+    -- Whether the member is alerted when the systems of the organization go down
+    receive_alerts  boolean                              NOT NULL DEFAULT FALSE,
+    joined_at       timestamp                            NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (organization_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS organization_member_user_id_idx ON organization_member (user_id);
+
+-- Create the table for storing the invitations to join an organization
+CREATE TABLE IF NOT EXISTS organization_invitation
+(
+    id              SERIAL PRIMARY KEY                   NOT NULL,
+    organization_id integer REFERENCES organization (id) NOT NULL,
+    -- Only the user with this email can accept the invitation
+    email           text                                 NOT NULL,
+    role            organization_role                    NOT NULL,
+    -- The SHA-256 of the code, the code itself is only shown when it is created
+    code_hash       text                                 NOT NULL UNIQUE,
+    created_by      integer REFERENCES "user" (id)       NOT NULL,
+    created_at      timestamp                            NOT NULL DEFAULT NOW(),
+    expires_at      timestamp                            NOT NULL,
+    accepted_at     timestamp
+);
+
+CREATE INDEX IF NOT EXISTS organization_invitation_organization_id_idx ON organization_invitation (organization_id);
+
+-- The systems of an organization are shared with its members, the others
+-- belong to the user that created them
+ALTER TABLE system
+    ADD COLUMN IF NOT EXISTS organization_id integer REFERENCES organization (id);
+
+CREATE INDEX IF NOT EXISTS system_organization_id_idx ON system (organization_id);
@@ -0,0 +1,16 @@
+-- Create the table for tracking the sessions of the users (which are stored in
+-- Redis), so that they can be listed and revoked
+CREATE TABLE IF NOT EXISTS user_session
+(
+    id           SERIAL PRIMARY KEY             NOT NULL,
+    user_id      integer REFERENCES "user" (id) NOT NULL,
+    -- A random key stored in the session, the session is logged out once its row
+    -- is deleted
+    session_key  text                           NOT NULL UNIQUE,
+    created_at   timestamp                      NOT NULL DEFAULT NOW(),
+    last_seen_at timestamp                      NOT NULL DEFAULT NOW(),
+    ip_address   text,
+    user_agent   text
+);
+
+CREATE INDEX IF NOT EXISTS user_session_user_id_idx ON user_session (user_id);
@@ -0,0 +1,94 @@
+CREATE TYPE audit_action AS ENUM (
+    'login',
+    'logout',
+    'signup',
+    'email_verified',
+    'password_reset_requested',
+    'password_reset',
+    'password_changed',
+    'timezone_changed',
+    'language_changed',
+    'api_token_created',
+    'api_token_revoked',
+    'invite_created',
+    'invite_deleted',
+    'verification_email_sent',
+    'totp_enrolled',
+    'totp_enabled',
+    'totp_disabled',
+    'passkey_added',
+    'passkey_deleted',
+    'session_revoked',
+    'other_sessions_revoked',
+    'system_created',
+    'system_renamed',
+    'system_visibility_changed',
+    'system_deleted',
+    'system_moved',
+    'channel_added',
+    'channel_tested',
+    'channel_deleted',
+    'organization_created',
+    'organization_deleted',
+    'member_invited',
+    'invitation_revoked',
+    'invitation_accepted',
+    'member_role_changed',
+    'member_alerts_changed',
+    'member_removed'
+    );
+
+CREATE TYPE audit_target AS ENUM (
+    'user',
+    'system',
+    'notification_channel',
+    'api_token',
+    'invite',
+    'passkey',
+    'session',
+    'organization',
+    'organization_invitation',
+    'organization_member'
+    );
+
+-- Create the table recording who changed what, the ids are not foreign keys so
+-- that the events outlive what they refer to
+CREATE TABLE IF NOT EXISTS audit_event
+(
+    id              BIGSERIAL PRIMARY KEY NOT NULL,
+    -- The user that made the change, missing if it was not logged in
+    actor_id        integer,
+    -- The organization the target belongs to, whose admins can read the event
+    organization_id integer,
+    action          audit_action          NOT NULL,
+    target_type     audit_target          NOT NULL,
+    target_id       text,
+    -- The values changed by the action, before and after it
+    before          jsonb,
+    after           jsonb,
+    ip_address      text,
+    created_at      timestamp             NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS audit_event_actor_id_idx ON audit_event (actor_id, created_at);
+CREATE INDEX IF NOT EXISTS audit_event_organization_id_idx ON audit_event (organization_id, created_at);
+
+-- The events can only be appended
+CREATE OR REPLACE FUNCTION audit_event_append_only() RETURNS trigger AS
+$$
+BEGIN
+    RAISE EXCEPTION 'audit_event is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_event_append_only
+    BEFORE UPDATE OR DELETE
+    ON audit_event
+    FOR EACH ROW
+EXECUTE FUNCTION audit_event_append_only();
+
+CREATE TRIGGER audit_event_no_truncate
+    BEFORE TRUNCATE
+    ON audit_event
+    FOR EACH STATEMENT
+EXECUTE FUNCTION audit_event_append_only();
@@ -0,0 +1,12 @@
+-- The instance admins manage the users and can see every system, the users in
+-- ADMIN_EMAILS are made admins at startup
+ALTER TABLE "user"
+    ADD COLUMN IF NOT EXISTS is_admin    boolean NOT NULL DEFAULT FALSE,
+    -- Disabled users cannot log in, and their sessions and API tokens stop working
+    ADD COLUMN IF NOT EXISTS disabled_at timestamp;
+
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'user_disabled';
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'user_enabled';
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'password_reset_forced';
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'admin_granted';
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'admin_revoked';
@@ -0,0 +1,6 @@
+-- The ID of the session in Redis, so that the sessions of a deleted account can
+-- be destroyed (missing until the first request of the session)
+ALTER TABLE user_session
+    ADD COLUMN IF NOT EXISTS session_id text;
+
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'account_deleted';
@@ -0,0 +1,12 @@
+-- The token of an email change is sent to (and stores) the new address
+ALTER TYPE email_token_kind ADD VALUE IF NOT EXISTS 'change_email';
+
+-- The sessions are bound to this hash, so that changing the password or the
+-- email address logs out the existing sessions
+ALTER TABLE "user"
+    ADD COLUMN IF NOT EXISTS session_auth_hash text NOT NULL GENERATED ALWAYS AS (
+        encode(sha256((COALESCE(password, '') || ':' || email)::bytea), 'hex')
+        ) STORED;
+
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'email_change_requested';
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'email_changed';
@@ -0,0 +1,24 @@
+-- The schedules the systems followed before they were edited, the current one
+-- is the one of the system itself
+CREATE TABLE IF NOT EXISTS schedule_version
+(
+    id              SERIAL PRIMARY KEY          NOT NULL,
+    system_id       uuid REFERENCES system (id) NOT NULL,
+    schedule_kind   schedule_kind               NOT NULL,
+    frequency       interval,
+    cron_expression text,
+    cron_timezone   text,
+    starts_at       timestamp                   NOT NULL,
+    grace_early     interval                    NOT NULL,
+    grace_late      interval,
+    -- The time at which the schedule was replaced
+    valid_until     timestamp                   NOT NULL DEFAULT NOW(),
+    CONSTRAINT schedule_version_schedule_check CHECK (
+        (schedule_kind = 'interval' AND frequency IS NOT NULL)
+        OR (schedule_kind = 'cron' AND cron_expression IS NOT NULL AND cron_timezone IS NOT NULL)
+    )
+);
+
+CREATE INDEX IF NOT EXISTS schedule_version_system_id_idx ON schedule_version (system_id, valid_until DESC);
+
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'system_edited';
@@ -0,0 +1,65 @@
+-- The events can only be appended, except when the events of a deleted
+-- account are anonymized by anonymize_audit_events, which may only clear the
+-- actor, the address and the emails of an event
+CREATE OR REPLACE FUNCTION audit_event_append_only() RETURNS trigger AS
+$$
+BEGIN
+    IF TG_OP = 'UPDATE'
+        AND current_setting('monitor.anonymizing_audit_events', TRUE) = 'on'
+        AND NEW.id = OLD.id
+        AND NEW.organization_id IS NOT DISTINCT FROM OLD.organization_id
+        AND NEW.action = OLD.action
+        AND NEW.target_type = OLD.target_type
+        AND NEW.target_id IS NOT DISTINCT FROM OLD.target_id
+        AND NEW.created_at = OLD.created_at
+        AND (NEW.actor_id IS NULL OR NEW.actor_id = OLD.actor_id)
+        AND (NEW.ip_address IS NULL OR NEW.ip_address = OLD.ip_address) THEN
+        RETURN NEW;
+    END IF;
+
+    RAISE EXCEPTION 'audit_event is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+-- Removes the personal data of a deleted user from the audit trail: the events
+-- they made lose their actor and address, and their email addresses (the
+-- current one and the ones recorded by their events) are removed from every
+-- event. Returns the number of events that were anonymized
+CREATE OR REPLACE FUNCTION anonymize_audit_events(deleted_user_id integer, deleted_user_email text)
+    RETURNS bigint AS
+$$
+DECLARE
+    emails     text[];
+    anonymized bigint;
+BEGIN
+    SELECT ARRAY_AGG(DISTINCT email)
+    INTO emails
+    FROM (
+        SELECT deleted_user_email AS email
+        UNION
+        SELECT value ->> 'email'
+        FROM audit_event,
+             LATERAL (VALUES (before), (after)) AS changed(value)
+        WHERE (actor_id = deleted_user_id
+            OR (target_type = 'user' AND target_id = deleted_user_id::text))
+          AND value ->> 'email' IS NOT NULL
+    ) AS known_emails;
+
+    PERFORM set_config('monitor.anonymizing_audit_events', 'on', TRUE);
+
+    UPDATE audit_event
+    SET actor_id   = CASE WHEN actor_id = deleted_user_id THEN NULL ELSE actor_id END,
+        ip_address = CASE WHEN actor_id = deleted_user_id THEN NULL ELSE ip_address END,
+        before     = CASE WHEN before ->> 'email' = ANY (emails) THEN before - 'email' ELSE before END,
+        after      = CASE WHEN after ->> 'email' = ANY (emails) THEN after - 'email' ELSE after END
+    WHERE actor_id = deleted_user_id
+       OR before ->> 'email' = ANY (emails)
+       OR after ->> 'email' = ANY (emails);
+
+    GET DIAGNOSTICS anonymized = ROW_COUNT;
+
+    PERFORM set_config('monitor.anonymizing_audit_events', 'off', TRUE);
+
+    RETURN anonymized;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,23 @@
+-- The init migration seeded ferris@example.com with a password anyone can look
+-- up, the first admin is now set with ADMIN_EMAILS. The seeded account is
+-- removed if it was never used to add a system and still has the seeded
+-- password, or disabled (without a password) if other rows refer to it
+DO
+$$
+BEGIN
+    DELETE
+    FROM "user" u
+    WHERE u.email = 'ferris@example.com'
+      AND u.password = '$argon2id$v=19$m=19456,t=2,p=1$VE0e3g7DalWHgDwou3nuRA$uC6TER156UQpk0lNQ5+jHM0l5poVjPA1he/Tyn9J4Zw'
+      AND NOT EXISTS (SELECT 1 FROM system s WHERE s.user_id = u.id);
+EXCEPTION
+    WHEN foreign_key_violation THEN
+        UPDATE "user" u
+        SET password    = NULL,
+            is_admin    = FALSE,
+            disabled_at = COALESCE(u.disabled_at, NOW())
+        WHERE u.email = 'ferris@example.com'
+          AND u.password = '$argon2id$v=19$m=19456,t=2,p=1$VE0e3g7DalWHgDwou3nuRA$uC6TER156UQpk0lNQ5+jHM0l5poVjPA1he/Tyn9J4Zw'
+          AND NOT EXISTS (SELECT 1 FROM system s WHERE s.user_id = u.id);
+END
+$$;
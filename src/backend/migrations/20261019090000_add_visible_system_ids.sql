@@ -0,0 +1,12 @@
+-- The systems (not deleted) the user can see: their personal systems and the
+-- systems of the organizations they are a member of. Shared by the queries
+-- listing or reading systems, incidents and pings for the user
+CREATE OR REPLACE FUNCTION visible_system_ids(viewer_id integer)
+    RETURNS SETOF uuid AS
+$$
+SELECT s.id
+FROM system s
+WHERE s.deleted = FALSE
+  AND (s.organization_id IS NULL AND s.user_id = viewer_id
+    OR s.organization_id IN (SELECT m.organization_id FROM organization_member m WHERE m.user_id = viewer_id))
+$$ LANGUAGE sql STABLE;
@@ -1,7 +1,7 @@
 use sqlx::{PgPool, postgres::PgPoolOptions};
 use tracing::info;
 
-use crate::app::App;
+use crate::{ADMIN_EMAILS, app::App, web::protected::admin::promote_admins};
 
 impl App {
     pub(super) async fn setup_db() -> color_eyre::Result<PgPool> {
@@ -29,6 +29,11 @@ impl App {
 
         info!("SQLx: Migrations run");
 
+        let promoted = promote_admins(&pool, &ADMIN_EMAILS).await?;
+        if promoted > 0 {
+            info!("SQLx: Made {} users admins from ADMIN_EMAILS", promoted);
+        }
+
         Ok(pool)
     }
 }
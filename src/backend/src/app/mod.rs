@@ -1,14 +1,19 @@
 mod db;
 pub mod openapi;
 mod redis;
+#[cfg(test)]
+pub mod test_app;
 mod workers;
 
-use std::str::FromStr;
+use std::{net::SocketAddr, str::FromStr};
 
 use axum::{middleware, routing::get};
 use axum_login::{
     AuthManagerLayerBuilder,
-    tower_sessions::{Expiry, SessionManagerLayer, cookie::Key},
+    tower_sessions::{
+        Expiry, SessionManagerLayer,
+        cookie::{Key, SameSite},
+    },
 };
 use http::StatusCode;
 use sqlx::PgPool;
@@ -27,10 +32,20 @@ use utoipa_scalar::{Scalar, Servable};
 use crate::{
     app::{openapi::ApiDoc, redis::RedisLibPool},
     custom_login_required,
-    middleware::{set_cache_control::set_cache_control, set_user_info::set_user_info},
+    middleware::{
+        api_token::authenticate_api_token,
+        rate_limit::{API_RATE_LIMIT, rate_limit},
+        set_cache_control::set_cache_control,
+        set_user_info::set_user_info,
+        track_session::{SESSION_INACTIVITY_DAYS, track_session},
+    },
+    notifiers::email::{SmtpClient, init_smtp_client},
     users::LoginBackend,
-    web::{auth, protected, public},
-    workers::email_worker::{SmtpClient, init_smtp_client},
+    web::{
+        auth::{self, oidc::OidcClient},
+        protected, public,
+    },
+    workers::deadlines::run_deadline_scheduler,
 };
 
 pub struct App {
@@ -38,6 +53,7 @@ pub struct App {
     redis_lib: RedisLibPool,
     redis_fred: FredPool,
     smtp_client: SmtpClient,
+    oidc: Option<OidcClient>,
 }
 
 impl App {
@@ -49,24 +65,37 @@ impl App {
             init_smtp_client()
         )?;
 
+        let oidc = OidcClient::from_env()?;
+        if let Some(oidc) = &oidc {
+            info!("Single sign-on is enabled with {}", oidc.issuer_url);
+        }
+
         Ok(Self {
             db,
             redis_lib,
             redis_fred,
             smtp_client,
+            oidc,
         })
     }
 
     pub async fn serve(self) -> color_eyre::Result<()> {
         // Worker task.
         let worker_task_handle = {
-            let processor =
-                Self::init_workers(self.redis_lib.clone(), self.db.clone(), self.smtp_client)
-                    .await?;
+            let processor = Self::init_workers(
+                self.redis_lib.clone(),
+                self.db.clone(),
+                self.smtp_client.clone(),
+            )
+            .await?;
 
             tokio::task::spawn(Self::start_workers(processor))
         };
 
+        // Deadline scheduler task.
+        let deadline_task_handle =
+            tokio::task::spawn(run_deadline_scheduler(self.redis_lib.clone()));
+
         // Session layer.
         //
         // This uses `tower-sessions` to establish a layer that will provide the session
@@ -81,8 +110,13 @@ impl App {
             SessionManagerLayer::new(session_store)
                 .with_name("monitor_id")
                 .with_secure(true)
+                // Lax (instead of Strict) so that the cookie is sent when the identity
+                // provider redirects back to the single sign-on callback
+                .with_same_site(SameSite::Lax)
                 .with_expiry(Expiry::OnInactivity(
-                    axum_login::tower_sessions::cookie::time::Duration::days(7),
+                    axum_login::tower_sessions::cookie::time::Duration::days(
+                        SESSION_INACTIVITY_DAYS,
+                    ),
                 ))
                 .with_signed(key)
         };
@@ -92,19 +126,12 @@ impl App {
         // This combines the session layer with our backendOld to establish the auth
         // service which will provide the auth session as a request extension.
         let auth_layer = {
-            let backend = LoginBackend::new(self.db);
+            let backend =
+                LoginBackend::new(self.db, self.redis_lib, self.smtp_client).with_oidc(self.oidc);
             AuthManagerLayerBuilder::new(backend, session_layer).build()
         };
 
-        let (router, api) = OpenApiRouter::with_openapi(ApiDoc::openapi())
-            .merge(protected::router())
-            .route_layer(custom_login_required!(
-                LoginBackend,
-                (StatusCode::UNAUTHORIZED, "You are not logged in.")
-            ))
-            .merge(auth::router())
-            .merge(public::router())
-            .layer(middleware::from_fn(set_user_info))
+        let (router, api) = Self::router()
             .layer(auth_layer)
             .layer(middleware::from_fn(set_cache_control))
             .split_for_parts();
@@ -131,11 +158,14 @@ impl App {
         info!("Axum: Listening on {}", listener.local_addr()?);
 
         // Ensure we use a shutdown signal to abort the deletion task.
-        axum::serve(listener, router.into_make_service())
-            .with_graceful_shutdown(shutdown_signal())
-            .await?;
+        axum::serve(
+            listener,
+            router.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(shutdown_signal())
+        .await?;
 
-        let handles = vec![worker_task_handle];
+        let handles = vec![worker_task_handle, deadline_task_handle];
 
         // Abort each worker
         for handle in handles.iter() {
@@ -147,6 +177,25 @@ impl App {
 
         Ok(())
     }
+
+    /// The routes of the API, they expect the auth layer to be added on top
+    fn router() -> OpenApiRouter {
+        OpenApiRouter::with_openapi(ApiDoc::openapi())
+            .merge(protected::router())
+            .route_layer(middleware::from_fn_with_state(
+                API_RATE_LIMIT.clone(),
+                rate_limit,
+            ))
+            .route_layer(custom_login_required!(
+                LoginBackend,
+                (StatusCode::UNAUTHORIZED, "You are not logged in.")
+            ))
+            .route_layer(middleware::from_fn(authenticate_api_token))
+            .merge(auth::router())
+            .merge(public::router())
+            .layer(middleware::from_fn(set_user_info))
+            .layer(middleware::from_fn(track_session))
+    }
 }
 
 async fn shutdown_signal() {
@@ -1,6 +1,6 @@
 use utoipa::{
     Modify, OpenApi,
-    openapi::security::{ApiKey, ApiKeyValue, SecurityScheme},
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
 };
 
 pub const AUTH_TAG: &str = "Auth";
@@ -9,6 +9,11 @@ pub const USER_TAG: &str = "User";
 pub const SYSTEM_TAG: &str = "System";
 pub const DATA_TAG: &str = "Data";
 pub const PUBLIC_SYSTEM_TAG: &str = "Public systems";
+pub const NOTIFICATION_TAG: &str = "Notifications";
+pub const INCIDENT_TAG: &str = "Incidents";
+pub const ORGANIZATION_TAG: &str = "Organizations";
+pub const AUDIT_TAG: &str = "Audit";
+pub const ADMIN_TAG: &str = "Admin";
 
 #[derive(OpenApi)]
 #[openapi(
@@ -19,7 +24,12 @@ pub const PUBLIC_SYSTEM_TAG: &str = "Public systems";
         (name = USER_TAG, description = "Endpoints related to users and their accounts"),
         (name = SYSTEM_TAG, description = "Endpoints related to monitored systems"),
         (name = DATA_TAG, description = "Endpoints that must be connected to by the monitored systems"),
-        (name = PUBLIC_SYSTEM_TAG, description = "Endpoints related to monitored systems that are public")
+        (name = PUBLIC_SYSTEM_TAG, description = "Endpoints related to monitored systems that are public"),
+        (name = NOTIFICATION_TAG, description = "Endpoints related to the channels users get notified through"),
+        (name = INCIDENT_TAG, description = "Endpoints related to the periods in which the systems were down"),
+        (name = ORGANIZATION_TAG, description = "Endpoints related to the organizations sharing systems between their members"),
+        (name = AUDIT_TAG, description = "Endpoints related to the audit trail of the changes to the accounts and systems"),
+        (name = ADMIN_TAG, description = "Endpoints to administer the instance, only available to its admins")
     )
 )]
 pub(super) struct ApiDoc;
@@ -32,7 +42,18 @@ impl Modify for ApiDocSecurityAddon {
             components.add_security_scheme(
                 "session",
                 SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new("monitor_id"))),
-            )
+            );
+            components.add_security_scheme(
+                "api_token",
+                SecurityScheme::Http(
+                    HttpBuilder::new()
+                        .scheme(HttpAuthScheme::Bearer)
+                        .description(Some(
+                            "A personal API token, created under /user/create_api_token",
+                        ))
+                        .build(),
+                ),
+            );
         }
     }
 }
@@ -0,0 +1,143 @@
+use std::{net::SocketAddr, time::Duration};
+
+use axum::{extract::Path, routing::post};
+use axum_login::{AuthManagerLayerBuilder, AuthnBackend};
+use http::{StatusCode, header};
+use sidekiq::RedisConnectionManager;
+use sqlx::PgPool;
+use tower_sessions::{MemoryStore, SessionManagerLayer};
+use uuid::Uuid;
+
+use crate::{
+    app::{App, redis::RedisLibPool},
+    notifiers::email::SmtpClient,
+    users::{AuthSession, LoginBackend},
+    web::auth::oidc::OidcClient,
+};
+
+/// The app served on a local port for the tests that need a database, the
+/// sessions are kept in memory (Redis is never connected to)
+pub struct TestApp {
+    pub db: PgPool,
+    pub client: reqwest::Client,
+    address: String,
+}
+
+impl TestApp {
+    pub async fn spawn(db: PgPool) -> Self {
+        Self::spawn_with_oidc(db, None).await
+    }
+
+    /// Spawns the app with single sign-on through the identity provider
+    pub async fn spawn_with_oidc(db: PgPool, oidc: Option<OidcClient>) -> Self {
+        // Nothing listens on Redis either, so the requests that need it fail right
+        // away (and the rate limits let them through)
+        let redis = RedisLibPool::builder()
+            .connection_timeout(Duration::from_millis(100))
+            .retry_connection(false)
+            .build_unchecked(
+                RedisConnectionManager::new("redis://127.0.0.1:6379").expect("Valid Redis URL"),
+            );
+
+        // Nothing listens on the discard port, so the emails fail to be sent
+        let smtp_client = SmtpClient::builder_dangerous("127.0.0.1").port(9).build();
+
+        let session_layer =
+            SessionManagerLayer::new(MemoryStore::default()).with_name("monitor_id");
+        let auth_layer = AuthManagerLayerBuilder::new(
+            LoginBackend::new(db.clone(), redis, smtp_client).with_oidc(oidc),
+            session_layer,
+        )
+        .build();
+
+        let (router, _) = App::router().split_for_parts();
+        let router = router
+            .route("/test_login/{id}", post(login_as))
+            .layer(auth_layer);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                router.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+        });
+
+        Self {
+            db,
+            client: reqwest::Client::new(),
+            address,
+        }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.address, path)
+    }
+
+    pub async fn create_user(&self, email: &str) -> i32 {
+        sqlx::query_scalar!(
+            r#"
+            INSERT INTO "user" (email, password) VALUES ($1, '') RETURNING id
+            "#,
+            email
+        )
+        .fetch_one(&self.db)
+        .await
+        .unwrap()
+    }
+
+    pub async fn create_system(&self, user_id: i32, name: &str) -> Uuid {
+        let id = Uuid::new_v4();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO system (id, name, user_id, frequency, starts_at, down_after)
+            VALUES ($1, $2, $3, '1 hour', NOW(), '1 hour')
+            "#,
+            id,
+            name,
+            user_id
+        )
+        .execute(&self.db)
+        .await
+        .unwrap();
+
+        id
+    }
+
+    /// Logs in as the user, returning the session cookie to send along with the
+    /// requests
+    pub async fn login(&self, user_id: i32) -> String {
+        let response = self
+            .client
+            .post(self.url(&format!("/test_login/{user_id}")))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        response
+            .headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .filter_map(|cookie| cookie.to_str().ok())
+            .find(|cookie| cookie.starts_with("monitor_id="))
+            .and_then(|cookie| cookie.split(';').next())
+            .expect("The session cookie is set")
+            .to_string()
+    }
+}
+
+async fn login_as(mut auth_session: AuthSession, Path(id): Path<i32>) -> StatusCode {
+    let Ok(Some(user)) = auth_session.backend.get_user(&id).await else {
+        return StatusCode::NOT_FOUND;
+    };
+
+    match auth_session.login(&user).await {
+        Ok(()) => StatusCode::OK,
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
@@ -5,7 +5,8 @@ use tracing::info;
 use crate::{
     PRODUCTION,
     app::{App, redis::RedisLibPool},
-    workers::{email_worker::SmtpClient, register_workers},
+    notifiers::email::SmtpClient,
+    workers::{deadlines::schedule_all_deadlines, register_workers},
 };
 
 impl App {
@@ -33,11 +34,19 @@ impl App {
         // Clear out all periodic jobs and their schedules
         periodic::destroy_all(redis.clone()).await?;
 
+        // Make sure that every system has its deadline scheduled
+        schedule_all_deadlines(&redis, &db)
+            .await
+            .map_err(|e| color_eyre::eyre::eyre!(e))?;
+
         // Sidekiq server
-        let mut p = Processor::new(redis, vec!["down_emails".to_string()]);
+        let mut p = Processor::new(
+            redis.clone(),
+            vec!["down_checks".to_string(), "notifications".to_string()],
+        );
 
         // Add known workers
-        register_workers(&mut p, db, smtp_client).await?;
+        register_workers(&mut p, redis, db, smtp_client).await?;
 
         Ok(p)
     }
@@ -0,0 +1,208 @@
+use axum::{
+    extract::FromRequestParts,
+    response::{IntoResponse, Response},
+};
+use http::request::Parts;
+use serde::{Deserialize, Serialize};
+use sonic_rs::Value;
+use sqlx::{PgPool, types::Json};
+use tracing::error;
+use utoipa::ToSchema;
+
+use crate::{middleware::client_ip::client_ip, users::AuthSession};
+
+/// What was done, recorded in the audit trail
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, sqlx::Type)]
+#[sqlx(type_name = "audit_action", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Login,
+    Logout,
+    Signup,
+    EmailVerified,
+    PasswordResetRequested,
+    PasswordReset,
+    PasswordChanged,
+    TimezoneChanged,
+    LanguageChanged,
+    ApiTokenCreated,
+    ApiTokenRevoked,
+    InviteCreated,
+    InviteDeleted,
+    VerificationEmailSent,
+    TotpEnrolled,
+    TotpEnabled,
+    TotpDisabled,
+    PasskeyAdded,
+    PasskeyDeleted,
+    SessionRevoked,
+    OtherSessionsRevoked,
+    SystemCreated,
+    SystemRenamed,
+    SystemVisibilityChanged,
+    SystemDeleted,
+    SystemMoved,
+    SystemEdited,
+    ChannelAdded,
+    ChannelTested,
+    ChannelDeleted,
+    OrganizationCreated,
+    OrganizationDeleted,
+    MemberInvited,
+    InvitationRevoked,
+    InvitationAccepted,
+    MemberRoleChanged,
+    MemberAlertsChanged,
+    MemberRemoved,
+    UserDisabled,
+    UserEnabled,
+    PasswordResetForced,
+    AdminGranted,
+    AdminRevoked,
+    AccountDeleted,
+    EmailChangeRequested,
+    EmailChanged,
+}
+
+/// The kind of resource an action was done on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, sqlx::Type)]
+#[sqlx(type_name = "audit_target", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum AuditTarget {
+    User,
+    System,
+    NotificationChannel,
+    ApiToken,
+    Invite,
+    Passkey,
+    Session,
+    Organization,
+    OrganizationInvitation,
+    OrganizationMember,
+}
+
+/// Records the actions of the request in the audit trail, on behalf of the
+/// logged-in user (or the user of the API token) and from the address of the
+/// client
+#[derive(Debug, Clone)]
+pub struct Audit {
+    db: PgPool,
+    actor_id: Option<i32>,
+    ip_address: Option<String>,
+}
+
+impl<S> FromRequestParts<S> for Audit
+where
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let auth_session = AuthSession::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        Ok(Self {
+            db: auth_session.backend.db,
+            actor_id: auth_session.user.map(|user| user.id),
+            ip_address: client_ip(&parts.headers, &parts.extensions).map(|ip| ip.to_string()),
+        })
+    }
+}
+
+impl Audit {
+    /// An event about the target, recorded with [`AuditEvent::record`]
+    pub fn event(
+        &self,
+        action: AuditAction,
+        target_type: AuditTarget,
+        target_id: impl ToString,
+    ) -> AuditEvent<'_> {
+        AuditEvent {
+            audit: self,
+            actor_id: self.actor_id,
+            organization_id: None,
+            action,
+            target_type,
+            target_id: target_id.to_string(),
+            before: None,
+            after: None,
+            anonymous: false,
+        }
+    }
+}
+
+/// An event of the audit trail, which can only be appended
+#[derive(Debug)]
+#[must_use = "the event is only recorded with `record`"]
+pub struct AuditEvent<'a> {
+    audit: &'a Audit,
+    actor_id: Option<i32>,
+    organization_id: Option<i32>,
+    action: AuditAction,
+    target_type: AuditTarget,
+    target_id: String,
+    before: Option<Value>,
+    after: Option<Value>,
+    anonymous: bool,
+}
+
+impl AuditEvent<'_> {
+    /// The user that made the change, for the requests that log the user in
+    pub fn actor(mut self, actor_id: i32) -> Self {
+        self.actor_id = Some(actor_id);
+        self
+    }
+
+    /// Records the event without the actor and the address of the client, for
+    /// the events about a deleted account
+    pub fn anonymous(mut self) -> Self {
+        self.actor_id = None;
+        self.anonymous = true;
+        self
+    }
+
+    /// The organization the target belongs to, so that its admins can read the
+    /// event
+    pub fn organization(mut self, organization_id: Option<i32>) -> Self {
+        self.organization_id = organization_id;
+        self
+    }
+
+    /// The values changed by the action, before it
+    pub fn before(mut self, before: Value) -> Self {
+        self.before = Some(before);
+        self
+    }
+
+    /// The values changed by the action, after it
+    pub fn after(mut self, after: Value) -> Self {
+        self.after = Some(after);
+        self
+    }
+
+    /// Appends the event to the audit trail, once the change was made. The
+    /// failures are only logged, as the change cannot be undone anymore
+    pub async fn record(self) {
+        if let Err(e) = sqlx::query!(
+            r#"
+            INSERT INTO audit_event (actor_id, organization_id, action, target_type, target_id,
+                                     before, after, ip_address)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            self.actor_id,
+            self.organization_id,
+            self.action as _,
+            self.target_type as _,
+            self.target_id,
+            self.before.map(Json) as _,
+            self.after.map(Json) as _,
+            self.audit.ip_address.as_ref().filter(|_| !self.anonymous)
+        )
+        .execute(&self.audit.db)
+        .await
+        {
+            error!("Error recording the audit event {:?}: {}", self.action, e);
+        }
+    }
+}
@@ -11,10 +11,13 @@ use rust_i18n::i18n;
 use rustls::crypto::aws_lc_rs;
 use tracing::info;
 use tracing_subscriber::{EnvFilter, layer::SubscriberExt, util::SubscriberInitExt};
-use web::App;
+use web::{App, auth::signup::RegistrationMode};
 
 pub mod app;
+pub mod audit;
 pub mod middleware;
+pub mod models;
+pub mod notifiers;
 pub mod users;
 pub mod web;
 pub mod workers;
@@ -22,6 +25,41 @@ pub mod workers;
 pub static PRODUCTION: Lazy<bool> = Lazy::new(|| std::env::var("PRODUCTION").is_ok());
 pub static SITE_URL: Lazy<String> =
     Lazy::new(|| std::env::var("SITE_URL").unwrap_or_else(|_| "http://localhost:5173".into()));
+/// Whether the app is behind a reverse proxy, whose `X-Forwarded-For` header
+/// carries the address of the clients
+pub static TRUST_PROXY: Lazy<bool> = Lazy::new(|| std::env::var("TRUST_PROXY").is_ok());
+/// Whether the notification channels can send requests to private addresses
+/// (loopback, private networks, link-local), for services on the network of
+/// the instance
+pub static ALLOW_PRIVATE_TARGETS: Lazy<bool> =
+    Lazy::new(|| std::env::var("ALLOW_PRIVATE_TARGETS").is_ok());
+/// Maximum size in bytes of the job output stored along with a ping
+pub static PING_BODY_LIMIT: Lazy<usize> = Lazy::new(|| {
+    std::env::var("PING_BODY_LIMIT")
+        .ok()
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(10 * 1024)
+});
+/// Who can sign up, set with REGISTRATION (open, invite_only or disabled)
+pub static REGISTRATION_MODE: Lazy<RegistrationMode> = Lazy::new(|| {
+    std::env::var("REGISTRATION")
+        .map(|mode| mode.parse().expect("Invalid REGISTRATION"))
+        .unwrap_or_default()
+});
+/// The users made admins at startup, set with ADMIN_EMAILS (a comma-separated
+/// list of emails)
+pub static ADMIN_EMAILS: Lazy<Vec<String>> = Lazy::new(|| {
+    std::env::var("ADMIN_EMAILS")
+        .map(|emails| {
+            emails
+                .split(',')
+                .map(str::trim)
+                .filter(|email| !email.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+});
 
 i18n!("./i18n/", fallback = ["en", "it"], minify_key = true);
 
@@ -51,5 +89,7 @@ async fn main() -> Result<()> {
         info!("System: Development mode");
     }
 
+    info!("System: Registration is {}", *REGISTRATION_MODE);
+
     App::new().await?.serve().await
 }
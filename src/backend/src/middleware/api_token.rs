@@ -0,0 +1,348 @@
+use axum::{
+    extract::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_login::AuthnBackend;
+use http::{Method, StatusCode, header};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    users::AuthSession,
+    web::utils::token::{generate_token, hash_token},
+};
+
+/// The prefix of the API tokens, to recognize them (such as in secret scanners)
+pub const API_TOKEN_PREFIX: &str = "mon_";
+
+/// What the API token the request was authenticated with is allowed to do,
+/// stored in the request extensions
+#[derive(Debug, Clone, Default)]
+pub struct TokenScope {
+    /// The token can only be used to read
+    pub read_only: bool,
+    /// The systems the token is restricted to, all the systems of the user if
+    /// missing
+    pub system_ids: Option<Vec<Uuid>>,
+}
+
+impl TokenScope {
+    /// Whether the token can be used for the endpoint, the account of the user
+    /// can only be managed with a session (so that a token cannot create new
+    /// tokens), and tokens restricted to some systems cannot act on the other
+    /// resources of the user
+    pub fn allows_route(&self, method: &Method, path: &str) -> bool {
+        if path.starts_with("/user/") || path.starts_with("/admin/") {
+            return false;
+        }
+
+        if self.read_only && method != Method::GET && method != Method::HEAD {
+            return false;
+        }
+
+        if self.system_ids.is_some()
+            && (path == "/add_system"
+                || path.starts_with("/notification_channels/")
+                || path.starts_with("/organizations/")
+                || path == "/list_audit_events")
+        {
+            return false;
+        }
+
+        true
+    }
+
+    pub fn allows_system(&self, system_id: Uuid) -> bool {
+        self.system_ids
+            .as_ref()
+            .is_none_or(|system_ids| system_ids.contains(&system_id))
+    }
+}
+
+/// The systems a request can act on (all the systems of the user if missing),
+/// used to filter the queries
+pub fn scoped_system_ids(scope: Option<&TokenScope>) -> Option<&[Uuid]> {
+    scope.and_then(|scope| scope.system_ids.as_deref())
+}
+
+/// Generates a new API token, returning it along with its hash
+pub fn generate_api_token() -> (String, String) {
+    generate_token(API_TOKEN_PREFIX)
+}
+
+/// Authenticates the requests carrying an `Authorization: Bearer` header with
+/// an API token, as if the user was logged in (the session is left untouched)
+pub async fn authenticate_api_token(mut request: Request, next: Next) -> Response {
+    let Some(authorization) = request.headers().get(header::AUTHORIZATION) else {
+        return next.run(request).await;
+    };
+
+    let Some(token) = authorization
+        .to_str()
+        .ok()
+        .and_then(|authorization| authorization.strip_prefix("Bearer "))
+    else {
+        return (StatusCode::UNAUTHORIZED, "Invalid authorization header").into_response();
+    };
+
+    let token_hash = hash_token(token.trim());
+
+    let Some(backend) = request
+        .extensions()
+        .get::<AuthSession>()
+        .map(|auth_session| auth_session.backend.clone())
+    else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    let api_token = match sqlx::query!(
+        r#"
+        UPDATE api_token
+        SET last_used_at = NOW()
+        WHERE token_hash = $1
+        RETURNING user_id, read_only, system_ids
+        "#,
+        token_hash
+    )
+    .fetch_optional(&backend.db)
+    .await
+    {
+        Ok(Some(api_token)) => api_token,
+        Ok(None) => return (StatusCode::UNAUTHORIZED, "Invalid API token").into_response(),
+        Err(e) => {
+            error!("Error authenticating the API token: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let user = match backend.get_user(&api_token.user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return (StatusCode::UNAUTHORIZED, "Invalid API token").into_response(),
+        Err(e) => {
+            error!("Error retrieving the user of the API token: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let scope = TokenScope {
+        read_only: api_token.read_only,
+        system_ids: api_token.system_ids,
+    };
+
+    if !scope.allows_route(request.method(), request.uri().path()) {
+        return (
+            StatusCode::FORBIDDEN,
+            "The API token is not allowed to use this endpoint",
+        )
+            .into_response();
+    }
+
+    if let Some(auth_session) = request.extensions_mut().get_mut::<AuthSession>() {
+        auth_session.user = Some(user);
+    }
+    request.extensions_mut().insert(scope);
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod test {
+    use sonic_rs::{JsonContainerTrait, JsonValueTrait, Value, json};
+    use sqlx::PgPool;
+
+    use crate::app::test_app::TestApp;
+
+    use super::*;
+
+    #[test]
+    fn test_allows_route() {
+        let full = TokenScope::default();
+        assert!(full.allows_route(&Method::GET, "/list_systems"));
+        assert!(full.allows_route(&Method::DELETE, "/delete_system"));
+        assert!(full.allows_route(&Method::POST, "/notification_channels/add_channel"));
+        assert!(!full.allows_route(&Method::GET, "/user/list_api_tokens"));
+        assert!(!full.allows_route(&Method::GET, "/admin/list_users"));
+        assert!(full.allows_system(Uuid::new_v4()));
+
+        let read_only = TokenScope {
+            read_only: true,
+            system_ids: None,
+        };
+        assert!(read_only.allows_route(&Method::GET, "/list_systems"));
+        assert!(!read_only.allows_route(&Method::PATCH, "/edit_system_name"));
+
+        let system_id = Uuid::new_v4();
+        let scoped = TokenScope {
+            read_only: false,
+            system_ids: Some(vec![system_id]),
+        };
+        assert!(scoped.allows_route(&Method::PATCH, "/edit_system_name"));
+        assert!(!scoped.allows_route(&Method::POST, "/add_system"));
+        assert!(!scoped.allows_route(&Method::GET, "/notification_channels/list_channels"));
+        assert!(!scoped.allows_route(&Method::GET, "/organizations/list_organizations"));
+        assert!(scoped.allows_system(system_id));
+        assert!(!scoped.allows_system(Uuid::new_v4()));
+    }
+
+    async fn create_token(app: &TestApp, cookie: &str, request: Value) -> String {
+        let response = app
+            .client
+            .post(app.url("/user/create_api_token"))
+            .header(header::COOKIE, cookie)
+            .json(&request)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response: Value = sonic_rs::from_str(&response.text().await.unwrap()).unwrap();
+        let token = response["token"].as_str().unwrap();
+        assert!(token.starts_with(API_TOKEN_PREFIX));
+
+        format!("Bearer {token}")
+    }
+
+    async fn listed_systems(app: &TestApp, authorization: &str) -> Vec<String> {
+        let response = app
+            .client
+            .get(app.url("/list_systems?page=0&list_size=1"))
+            .header(header::AUTHORIZATION, authorization)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response: Value = sonic_rs::from_str(&response.text().await.unwrap()).unwrap();
+        response["systems"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|system| system["name"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a PostgreSQL database (set DATABASE_URL)"]
+    async fn test_api_token_lifecycle(db: PgPool) {
+        let app = TestApp::spawn(db).await;
+        let user_id = app.create_user("owner@example.com").await;
+        app.create_system(user_id, "Backups").await;
+        let cookie = app.login(user_id).await;
+
+        let authorization = create_token(&app, &cookie, json!({ "name": "CI" })).await;
+        assert_eq!(listed_systems(&app, &authorization).await, ["Backups"]);
+
+        // The account cannot be managed with a token
+        let response = app
+            .client
+            .get(app.url("/user/list_api_tokens"))
+            .header(header::AUTHORIZATION, &authorization)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = app
+            .client
+            .get(app.url("/user/list_api_tokens"))
+            .header(header::COOKIE, &cookie)
+            .send()
+            .await
+            .unwrap();
+        let response: Value = sonic_rs::from_str(&response.text().await.unwrap()).unwrap();
+        let token = &response["tokens"][0];
+        assert_eq!(token["name"].as_str(), Some("CI"));
+        assert!(token["last_used_at"].is_str());
+
+        let response = app
+            .client
+            .delete(app.url("/user/revoke_api_token"))
+            .header(header::COOKIE, &cookie)
+            .json(&json!({ "id": token["id"] }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        for authorization in [authorization.as_str(), "Bearer mon_invalid"] {
+            let response = app
+                .client
+                .get(app.url("/list_systems?page=0&list_size=1"))
+                .header(header::AUTHORIZATION, authorization)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a PostgreSQL database (set DATABASE_URL)"]
+    async fn test_read_only_api_token(db: PgPool) {
+        let app = TestApp::spawn(db).await;
+        let user_id = app.create_user("owner@example.com").await;
+        let system_id = app.create_system(user_id, "Backups").await;
+        let cookie = app.login(user_id).await;
+
+        let authorization = create_token(
+            &app,
+            &cookie,
+            json!({ "name": "Dashboard", "read_only": true }),
+        )
+        .await;
+        assert_eq!(listed_systems(&app, &authorization).await, ["Backups"]);
+
+        let response = app
+            .client
+            .delete(app.url("/delete_system"))
+            .header(header::AUTHORIZATION, &authorization)
+            .json(&json!({ "id": system_id }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a PostgreSQL database (set DATABASE_URL)"]
+    async fn test_system_scoped_api_token(db: PgPool) {
+        let app = TestApp::spawn(db).await;
+        let user_id = app.create_user("owner@example.com").await;
+        let backups_id = app.create_system(user_id, "Backups").await;
+        let reports_id = app.create_system(user_id, "Reports").await;
+        let cookie = app.login(user_id).await;
+
+        let authorization = create_token(
+            &app,
+            &cookie,
+            json!({ "name": "Backups job", "system_ids": [backups_id] }),
+        )
+        .await;
+        assert_eq!(listed_systems(&app, &authorization).await, ["Backups"]);
+
+        for (id, status) in [
+            (backups_id, StatusCode::OK),
+            (reports_id, StatusCode::NOT_FOUND),
+        ] {
+            let response = app
+                .client
+                .patch(app.url("/edit_system_name"))
+                .header(header::AUTHORIZATION, &authorization)
+                .json(&json!({ "id": id, "name": "Renamed" }))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), status);
+        }
+
+        let response = app
+            .client
+            .get(app.url("/notification_channels/list_channels"))
+            .header(header::AUTHORIZATION, &authorization)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+}
@@ -1,2 +1,6 @@
+pub mod api_token;
+pub mod client_ip;
+pub mod rate_limit;
 pub mod set_cache_control;
 pub mod set_user_info;
+pub mod track_session;
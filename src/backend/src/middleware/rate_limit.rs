@@ -0,0 +1,449 @@
+use std::{
+    net::{IpAddr, Ipv6Addr},
+    time::Duration,
+};
+
+use axum::{
+    RequestPartsExt,
+    extract::{RawPathParams, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http::{StatusCode, header};
+use once_cell::sync::Lazy;
+use sidekiq::{
+    RedisPool,
+    redis_rs::{self, Script},
+};
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::{middleware::client_ip::client_ip, users::AuthSession, workers::GenericResult};
+
+/// What the requests are counted by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitKey {
+    /// The address of the client (its /64 network for IPv6)
+    Ip,
+    /// The logged-in user (or the user of the API token)
+    User,
+    /// The system in the path of the request (its `id`, in its canonical form)
+    System,
+}
+
+/// The maximum number of requests in a window of time, counted in Redis (so
+/// that the count is shared by all the instances of the app)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimit {
+    /// The name of the limit, used for the Redis keys and for the environment
+    /// variable that overrides it
+    pub name: &'static str,
+    pub key: RateLimitKey,
+    pub requests: u64,
+    pub window: Duration,
+}
+
+impl RateLimit {
+    /// The limit, which can be overridden by `RATE_LIMIT_<NAME>` as
+    /// `<requests>/<seconds>` (or disabled with `off`)
+    pub fn from_env(
+        name: &'static str,
+        key: RateLimitKey,
+        requests: u64,
+        window: Duration,
+    ) -> Option<Self> {
+        let variable = format!("RATE_LIMIT_{}", name.to_uppercase());
+
+        let (requests, window) = match std::env::var(&variable) {
+            Ok(value) => Self::parse(&value).unwrap_or_else(|| panic!("Invalid {variable}"))?,
+            Err(_) => (requests, window),
+        };
+
+        Some(Self {
+            name,
+            key,
+            requests,
+            window,
+        })
+    }
+
+    fn parse(value: &str) -> Option<Option<(u64, Duration)>> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("off") {
+            return Some(None);
+        }
+
+        let (requests, seconds) = value.split_once('/')?;
+        let requests = requests.trim().parse().ok()?;
+        let seconds: u64 = seconds.trim().parse().ok().filter(|&seconds| seconds > 0)?;
+
+        Some(Some((requests, Duration::from_secs(seconds))))
+    }
+}
+
+/// The endpoints used to log in and sign up, counted by IP address
+pub static AUTH_RATE_LIMIT: Lazy<Option<RateLimit>> =
+    Lazy::new(|| RateLimit::from_env("auth", RateLimitKey::Ip, 30, Duration::from_secs(60)));
+
+/// The pings sent by the systems, counted by system
+pub static PING_RATE_LIMIT: Lazy<Option<RateLimit>> =
+    Lazy::new(|| RateLimit::from_env("ping", RateLimitKey::System, 60, Duration::from_secs(60)));
+
+/// The endpoints of the logged-in users, counted by user
+pub static API_RATE_LIMIT: Lazy<Option<RateLimit>> =
+    Lazy::new(|| RateLimit::from_env("api", RateLimitKey::User, 600, Duration::from_secs(60)));
+
+/// Counts a hit in the window of the key (which starts with its first hit),
+/// returning the hits so far and the time left in the window
+static HIT_SCRIPT: Lazy<Script> = Lazy::new(|| {
+    Script::new(
+        r#"
+        local hits = redis.call('INCR', KEYS[1])
+        if hits == 1 then
+            redis.call('PEXPIRE', KEYS[1], ARGV[1])
+        end
+        return {hits, redis.call('PTTL', KEYS[1])}
+        "#,
+    )
+});
+
+/// Rejects the requests over the limit with `429 Too Many Requests`, to be
+/// added as a route layer with the limit as its state. The requests are let
+/// through if Redis cannot be reached, or if there is nothing to count them by
+pub async fn rate_limit(
+    State(limit): State<Option<RateLimit>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(limit) = limit else {
+        return next.run(request).await;
+    };
+
+    let (mut parts, body) = request.into_parts();
+
+    let subject = match limit.key {
+        RateLimitKey::Ip => client_ip(&parts.headers, &parts.extensions).map(ip_bucket),
+        RateLimitKey::User => parts
+            .extensions
+            .get::<AuthSession>()
+            .and_then(|auth_session| auth_session.user.as_ref())
+            .map(|user| user.id.to_string()),
+        RateLimitKey::System => parts
+            .extract::<RawPathParams>()
+            .await
+            .ok()
+            .and_then(|params| {
+                params
+                    .iter()
+                    .find(|(name, _)| *name == "id")
+                    .and_then(|(_, id)| Uuid::parse_str(id).ok())
+                    .map(|id| id.to_string())
+            }),
+    };
+
+    let redis = parts
+        .extensions
+        .get::<AuthSession>()
+        .map(|auth_session| auth_session.backend.redis.clone());
+
+    let request = Request::from_parts(parts, body);
+
+    let (Some(subject), Some(redis)) = (subject, redis) else {
+        return next.run(request).await;
+    };
+
+    let key = format!("rate_limit:{}:{}", limit.name, subject);
+
+    match hit(&redis, &key, limit.window).await {
+        Ok((hits, retry_after)) if hits > limit.requests => {
+            warn!("Rate limit {} exceeded by {}", limit.name, subject);
+            too_many_requests(retry_after)
+        }
+        Ok(_) => next.run(request).await,
+        Err(e) => {
+            error!("Error counting the request for the rate limit: {}", e);
+            next.run(request).await
+        }
+    }
+}
+
+/// What an address is counted by: IPv6 clients usually get a whole /64
+/// network, so its addresses share their count
+fn ip_bucket(ip: IpAddr) -> String {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => {
+            let network = Ipv6Addr::from_bits(ip.to_bits() & !(u64::MAX as u128));
+            format!("{network}/64")
+        }
+    }
+}
+
+async fn hit(redis: &RedisPool, key: &str, window: Duration) -> GenericResult<(u64, Duration)> {
+    let mut conn = redis.get().await?;
+
+    let (hits, ttl): (u64, i64) = HIT_SCRIPT
+        .key(key)
+        .arg(window.as_millis() as u64)
+        .invoke_async(conn.unnamespaced_borrow_mut())
+        .await?;
+
+    Ok((hits, Duration::from_millis(ttl.max(0) as u64)))
+}
+
+/// A `429 Too Many Requests` response, telling when to retry (in seconds,
+/// rounded up)
+pub fn too_many_requests(retry_after: Duration) -> Response {
+    let seconds = retry_after.as_millis().div_ceil(1000).max(1);
+
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, seconds.to_string())],
+        "Too many requests",
+    )
+        .into_response()
+}
+
+/// The wrong passwords (or second factors) allowed from an address before the
+/// account is locked for it
+const LOCKOUT_THRESHOLD: u32 = 5;
+
+/// The wrong passwords (or second factors) allowed from all the addresses
+/// together before the account is locked for every address, so that spreading
+/// the guesses over many addresses does not get around the lockout
+const ACCOUNT_LOCKOUT_THRESHOLD: u32 = 50;
+
+/// How long the account is locked for the address after reaching the
+/// threshold, doubled at every further failure
+const LOCKOUT_BASE: Duration = Duration::from_secs(30);
+
+const LOCKOUT_MAX: Duration = Duration::from_secs(60 * 60);
+
+/// How long the failures are remembered after the latest one
+const LOGIN_FAILURES_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long the account is locked after the failures, once they reached the
+/// threshold
+fn lockout_duration(failures: u32, threshold: u32) -> Option<Duration> {
+    let exponent = failures.checked_sub(threshold)?;
+
+    Some(
+        LOCKOUT_BASE
+            .checked_mul(2u32.saturating_pow(exponent))
+            .map_or(LOCKOUT_MAX, |duration| duration.min(LOCKOUT_MAX)),
+    )
+}
+
+/// The failures are counted by account and address, so that nobody can lock
+/// out the owner of an account by failing its logins on purpose (until the
+/// much higher ceiling of the account, counted by [`account_login_key`])
+fn login_key(kind: &str, email: &str, ip: Option<IpAddr>) -> String {
+    let ip = ip.map_or_else(|| "unknown".to_string(), ip_bucket);
+
+    format!("login_{}:{}:{}", kind, email.trim().to_lowercase(), ip)
+}
+
+/// The failures of the account from all the addresses
+fn account_login_key(kind: &str, email: &str) -> String {
+    format!("login_{}:{}", kind, email.trim().to_lowercase())
+}
+
+/// The time left before the account can be logged in again from the address,
+/// if it is locked (for the address or for every address)
+pub async fn login_lockout(
+    redis: &RedisPool,
+    email: &str,
+    ip: Option<IpAddr>,
+) -> GenericResult<Option<Duration>> {
+    let mut conn = redis.get().await?;
+
+    let (ttl, account_ttl): (i64, i64) = redis_rs::pipe()
+        .cmd("PTTL")
+        .arg(login_key("lockout", email, ip))
+        .cmd("PTTL")
+        .arg(account_login_key("lockout", email))
+        .query_async(conn.unnamespaced_borrow_mut())
+        .await?;
+
+    let ttl = ttl.max(account_ttl);
+
+    Ok((ttl > 0).then(|| Duration::from_millis(ttl as u64)))
+}
+
+/// Records a failed login of the account from the address, locking it for the
+/// address (for longer at every further failure) once it failed too many times
+/// from there, and for every address once it failed too many times in total
+pub async fn record_login_failure(
+    redis: &RedisPool,
+    email: &str,
+    ip: Option<IpAddr>,
+) -> GenericResult<()> {
+    let failures_key = login_key("failures", email, ip);
+    let account_failures_key = account_login_key("failures", email);
+    let mut conn = redis.get().await?;
+
+    let (failures, account_failures): (u32, u32) = redis_rs::pipe()
+        .atomic()
+        .cmd("INCR")
+        .arg(&failures_key)
+        .cmd("PEXPIRE")
+        .arg(&failures_key)
+        .arg(LOGIN_FAILURES_TTL.as_millis() as u64)
+        .ignore()
+        .cmd("INCR")
+        .arg(&account_failures_key)
+        .cmd("PEXPIRE")
+        .arg(&account_failures_key)
+        .arg(LOGIN_FAILURES_TTL.as_millis() as u64)
+        .ignore()
+        .query_async(conn.unnamespaced_borrow_mut())
+        .await?;
+
+    if let Some(lockout) = lockout_duration(failures, LOCKOUT_THRESHOLD) {
+        warn!(
+            "Locking the login of {} from {:?} for {:?}",
+            email, ip, lockout
+        );
+
+        let _: () = redis_rs::cmd("SET")
+            .arg(login_key("lockout", email, ip))
+            .arg(failures)
+            .arg("PX")
+            .arg(lockout.as_millis() as u64)
+            .query_async(conn.unnamespaced_borrow_mut())
+            .await?;
+    }
+
+    if let Some(lockout) = lockout_duration(account_failures, ACCOUNT_LOCKOUT_THRESHOLD) {
+        warn!(
+            "Locking the login of {} from every address for {:?}",
+            email, lockout
+        );
+
+        let _: () = redis_rs::cmd("SET")
+            .arg(account_login_key("lockout", email))
+            .arg(account_failures)
+            .arg("PX")
+            .arg(lockout.as_millis() as u64)
+            .query_async(conn.unnamespaced_borrow_mut())
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Forgets the failed logins of the account from the address, once it logged
+/// in successfully. The failures of the account from all the addresses are
+/// kept until they expire, as a login from one address says nothing about the
+/// guesses made from the others
+pub async fn clear_login_failures(
+    redis: &RedisPool,
+    email: &str,
+    ip: Option<IpAddr>,
+) -> GenericResult<()> {
+    let mut conn = redis.get().await?;
+
+    conn.del(login_key("failures", email, ip)).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_rate_limit() {
+        assert_eq!(
+            RateLimit::parse("10/60"),
+            Some(Some((10, Duration::from_secs(60))))
+        );
+        assert_eq!(RateLimit::parse(" Off "), Some(None));
+        assert_eq!(RateLimit::parse("10/0"), None);
+        assert_eq!(RateLimit::parse("10"), None);
+        assert_eq!(RateLimit::parse("ten/60"), None);
+    }
+
+    #[test]
+    fn test_lockout_duration() {
+        assert_eq!(
+            lockout_duration(LOCKOUT_THRESHOLD - 1, LOCKOUT_THRESHOLD),
+            None
+        );
+        assert_eq!(
+            lockout_duration(LOCKOUT_THRESHOLD, LOCKOUT_THRESHOLD),
+            Some(LOCKOUT_BASE)
+        );
+        assert_eq!(
+            lockout_duration(LOCKOUT_THRESHOLD + 2, LOCKOUT_THRESHOLD),
+            Some(LOCKOUT_BASE * 4)
+        );
+        assert_eq!(
+            lockout_duration(LOCKOUT_THRESHOLD + 100, LOCKOUT_THRESHOLD),
+            Some(LOCKOUT_MAX)
+        );
+        assert_eq!(
+            lockout_duration(ACCOUNT_LOCKOUT_THRESHOLD - 1, ACCOUNT_LOCKOUT_THRESHOLD),
+            None
+        );
+    }
+
+    #[test]
+    fn test_login_key() {
+        let ip = Some("203.0.113.7".parse().unwrap());
+
+        assert_eq!(
+            login_key("failures", " Ferris@Example.com ", ip),
+            "login_failures:ferris@example.com:203.0.113.7"
+        );
+        assert_ne!(
+            login_key("lockout", "ferris@example.com", ip),
+            login_key(
+                "lockout",
+                "ferris@example.com",
+                Some("198.51.100.1".parse().unwrap())
+            )
+        );
+        assert_eq!(
+            login_key("lockout", "ferris@example.com", None),
+            "login_lockout:ferris@example.com:unknown"
+        );
+        assert_eq!(
+            login_key(
+                "lockout",
+                "ferris@example.com",
+                Some("2001:db8:1:2:3:4:5:6".parse().unwrap())
+            ),
+            "login_lockout:ferris@example.com:2001:db8:1:2::/64"
+        );
+        assert_eq!(
+            account_login_key("failures", " Ferris@Example.com "),
+            "login_failures:ferris@example.com"
+        );
+    }
+
+    #[test]
+    fn test_ip_bucket() {
+        assert_eq!(ip_bucket("203.0.113.7".parse().unwrap()), "203.0.113.7");
+        assert_eq!(
+            ip_bucket("::ffff:203.0.113.7".parse().unwrap()),
+            "203.0.113.7"
+        );
+        assert_eq!(
+            ip_bucket("2001:db8:1:2:3:4:5:6".parse().unwrap()),
+            ip_bucket("2001:db8:1:2:ffff::1".parse().unwrap())
+        );
+        assert_ne!(
+            ip_bucket("2001:db8:1:2::1".parse().unwrap()),
+            ip_bucket("2001:db8:1:3::1".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_too_many_requests() {
+        let response = too_many_requests(Duration::from_millis(1500));
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "2");
+    }
+}
@@ -0,0 +1,279 @@
+use axum::{
+    extract::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http::{HeaderMap, StatusCode, header};
+use sidekiq::{RedisPool, redis_rs};
+use sqlx::PgExecutor;
+use tracing::error;
+
+use crate::{
+    middleware::client_ip::client_ip, users::AuthSession,
+    web::utils::token::generate_url_safe_token, workers::GenericResult,
+};
+
+/// The key, in the session data, of the random key identifying the session in
+/// the `user_session` table
+pub const SESSION_KEY: &str = "session_key";
+
+/// How long a session lasts without being used
+pub const SESSION_INACTIVITY_DAYS: i64 = 7;
+
+/// The longest user agent that is stored
+const USER_AGENT_LIMIT: usize = 512;
+
+/// Keeps track of the sessions of the logged-in users: each session is given a
+/// key (on its first request after the login) and its last activity is
+/// recorded, the sessions whose key was revoked are logged out
+pub async fn track_session(mut request: Request, next: Next) -> Response {
+    let ip_address = client_ip(request.headers(), request.extensions()).map(|ip| ip.to_string());
+    let user_agent = user_agent(request.headers());
+
+    let Some(auth_session) = request.extensions_mut().get_mut::<AuthSession>() else {
+        return next.run(request).await;
+    };
+
+    let Some(user_id) = auth_session.user.as_ref().map(|user| user.id) else {
+        return next.run(request).await;
+    };
+
+    match check_session(auth_session, user_id, ip_address, user_agent).await {
+        Ok(true) => {}
+        // The session was revoked, the request goes on as if the user was not
+        // logged in
+        Ok(false) => {
+            if let Err(e) = auth_session.logout().await {
+                error!("Error logging out a revoked session: {}", e);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        }
+        Err(e) => {
+            error!("Error tracking the session of user {}: {}", user_id, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
+    next.run(request).await
+}
+
+/// Records the activity of the session (registering it if it has no key yet),
+/// returning whether it is still valid
+async fn check_session(
+    auth_session: &AuthSession,
+    user_id: i32,
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+) -> GenericResult<bool> {
+    let db = &auth_session.backend.db;
+    // The ID changes at the login, and is missing until the session is saved
+    let session_id = auth_session.session.id().map(|id| id.to_string());
+
+    if let Some(session_key) = auth_session.session.get::<String>(SESSION_KEY).await? {
+        let session_user_id = sqlx::query_scalar!(
+            r#"
+            UPDATE user_session
+            SET last_seen_at = NOW(),
+                ip_address = COALESCE($2, ip_address),
+                user_agent = COALESCE($3, user_agent),
+                session_id = COALESCE($4, session_id)
+            WHERE session_key = $1
+            RETURNING user_id
+            "#,
+            session_key,
+            ip_address,
+            user_agent,
+            session_id
+        )
+        .fetch_optional(db)
+        .await?;
+
+        match session_user_id {
+            Some(session_user_id) if session_user_id == user_id => return Ok(true),
+            None => return Ok(false),
+            // Another user logged in with the session (the login keeps the data of
+            // the session), the session of the previous one is over
+            Some(_) => {
+                sqlx::query!(
+                    "DELETE FROM user_session WHERE session_key = $1",
+                    session_key
+                )
+                .execute(db)
+                .await?;
+            }
+        }
+    }
+
+    let session_key = generate_url_safe_token();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO user_session (user_id, session_key, ip_address, user_agent, session_id)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        user_id,
+        session_key,
+        ip_address,
+        user_agent,
+        session_id
+    )
+    .execute(db)
+    .await?;
+
+    auth_session
+        .session
+        .insert(SESSION_KEY, session_key)
+        .await?;
+
+    Ok(true)
+}
+
+/// Forgets the session of the request (such as when logging out), its key is
+/// removed along with the rest of the session data
+pub async fn forget_session(auth_session: &AuthSession) -> Result<(), sqlx::Error> {
+    let Ok(Some(session_key)) = auth_session.session.get::<String>(SESSION_KEY).await else {
+        return Ok(());
+    };
+
+    sqlx::query!(
+        "DELETE FROM user_session WHERE session_key = $1",
+        session_key
+    )
+    .execute(&auth_session.backend.db)
+    .await?;
+
+    Ok(())
+}
+
+/// Forgets all the sessions of the user, for when they are invalidated (such as
+/// by a new password)
+pub async fn forget_user_sessions(
+    executor: impl PgExecutor<'_>,
+    user_id: i32,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM user_session WHERE user_id = $1", user_id)
+        .execute(executor)
+        .await?;
+
+    Ok(())
+}
+
+/// Destroys the sessions (stored in Redis) with the IDs, so that their data is
+/// gone along with the account
+pub async fn destroy_sessions(redis: &RedisPool, session_ids: Vec<String>) -> GenericResult<()> {
+    if session_ids.is_empty() {
+        return Ok(());
+    }
+
+    let mut conn = redis.get().await?;
+
+    let mut command = redis_rs::cmd("DEL");
+    command.arg(session_ids);
+    let _: u64 = command.query_async(conn.unnamespaced_borrow_mut()).await?;
+
+    Ok(())
+}
+
+fn user_agent(headers: &HeaderMap) -> Option<String> {
+    let user_agent = headers.get(header::USER_AGENT)?.to_str().ok()?;
+
+    Some(user_agent.chars().take(USER_AGENT_LIMIT).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use sonic_rs::{JsonContainerTrait, JsonValueTrait, Value, json};
+    use sqlx::PgPool;
+
+    use crate::app::test_app::TestApp;
+
+    use super::*;
+
+    async fn list_sessions(app: &TestApp, cookie: &str) -> Result<Vec<Value>, StatusCode> {
+        let response = app
+            .client
+            .get(app.url("/user/list_sessions"))
+            .header(header::COOKIE, cookie)
+            .header(header::USER_AGENT, "Tests")
+            .send()
+            .await
+            .unwrap();
+
+        if response.status() != StatusCode::OK {
+            return Err(response.status());
+        }
+
+        let response: Value = sonic_rs::from_str(&response.text().await.unwrap()).unwrap();
+        Ok(response["sessions"].as_array().unwrap().to_vec())
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a PostgreSQL database (set DATABASE_URL)"]
+    async fn test_list_and_revoke_sessions(db: PgPool) {
+        let app = TestApp::spawn(db).await;
+        let user_id = app.create_user("owner@example.com").await;
+        let laptop = app.login(user_id).await;
+        let phone = app.login(user_id).await;
+        let tablet = app.login(user_id).await;
+
+        // The sessions are tracked from their first request
+        assert_eq!(list_sessions(&app, &phone).await.unwrap().len(), 1);
+        assert_eq!(list_sessions(&app, &tablet).await.unwrap().len(), 2);
+
+        let sessions = list_sessions(&app, &laptop).await.unwrap();
+        assert_eq!(sessions.len(), 3);
+        let current = sessions
+            .iter()
+            .find(|session| session["current"].as_bool() == Some(true))
+            .unwrap();
+        assert_eq!(current["ip_address"].as_str(), Some("127.0.0.1"));
+        assert_eq!(current["user_agent"].as_str(), Some("Tests"));
+
+        let other_id = sessions
+            .iter()
+            .find(|session| session["current"].as_bool() == Some(false))
+            .map(|session| session["id"].clone())
+            .unwrap();
+        let response = app
+            .client
+            .delete(app.url("/user/revoke_session"))
+            .header(header::COOKIE, &laptop)
+            .json(&json!({ "id": other_id }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .client
+            .delete(app.url("/user/revoke_other_sessions"))
+            .header(header::COOKIE, &laptop)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        for cookie in [&phone, &tablet] {
+            assert_eq!(
+                list_sessions(&app, cookie).await,
+                Err(StatusCode::UNAUTHORIZED)
+            );
+        }
+        assert_eq!(list_sessions(&app, &laptop).await.unwrap().len(), 1);
+
+        let response = app
+            .client
+            .get(app.url("/logout"))
+            .header(header::COOKIE, &laptop)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let sessions = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM user_session"#)
+            .fetch_one(&app.db)
+            .await
+            .unwrap();
+        assert_eq!(sessions, 0);
+    }
+}
@@ -0,0 +1,33 @@
+//! The types of the enums of the database, shared by the endpoints, the
+//! workers and the notifiers
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Serialize, Deserialize, Clone, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "visibility", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum Visibility {
+    Public,
+    Private,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "ping_kind", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum PingKind {
+    Start,
+    Success,
+    Fail,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "schedule_kind", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleKind {
+    /// Pings are expected every fixed frequency, starting from starts_at
+    #[default]
+    Interval,
+    /// Pings are expected at every occurrence of a cron expression
+    Cron,
+}
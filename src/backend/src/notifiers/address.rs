@@ -0,0 +1,126 @@
+use std::net::{IpAddr, SocketAddr};
+
+use reqwest::{
+    Url,
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect::{Attempt, Policy},
+};
+use url::Host;
+
+/// The maximum number of redirects followed by a notification request
+const MAX_REDIRECTS: usize = 10;
+
+/// Whether the address can be reached from the internet, requests to the other
+/// ones (loopback, private networks, link-local, ...) would let the users
+/// probe the network of the instance
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                // 0.0.0.0/8 ("this network") and 100.64.0.0/10 (shared address space)
+                || first == 0
+                || (first == 100 && (64..128).contains(&second)))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(IpAddr::V4(ip)),
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local()
+                    || ip.is_multicast())
+            }
+        },
+    }
+}
+
+/// Whether the host of the URL is known to be private without resolving it
+/// (`localhost` or a private IP address)
+pub fn is_private_host(url: &Url) -> bool {
+    match url.host() {
+        Some(Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            domain == "localhost" || domain.ends_with(".localhost")
+        }
+        Some(Host::Ipv4(ip)) => !is_public_ip(IpAddr::V4(ip)),
+        Some(Host::Ipv6(ip)) => !is_public_ip(IpAddr::V6(ip)),
+        None => true,
+    }
+}
+
+/// Resolves the hosts of the notification requests, leaving out the private
+/// addresses (so that a host cannot be pointed at them after the channel was
+/// added)
+pub struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public_ip(addr.ip()))
+                .collect();
+
+            if addrs.is_empty() {
+                return Err(format!("{} resolves to a private address", name.as_str()).into());
+            }
+
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Follows the redirects, unless they point to a private IP address (the
+/// private hosts are left out by `PublicResolver`)
+pub fn public_redirect_policy() -> Policy {
+    Policy::custom(|attempt: Attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            attempt.error("Too many redirects")
+        } else if is_private_host(attempt.url()) {
+            attempt.error("Redirected to a private address")
+        } else {
+            attempt.follow()
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_private_host() {
+        for url in [
+            "http://localhost:8080/hook",
+            "http://api.localhost/hook",
+            "http://127.0.0.1/hook",
+            "http://10.0.0.5/hook",
+            "http://192.168.1.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://100.64.0.1/hook",
+            "http://0.0.0.0/hook",
+            "http://[::1]/hook",
+            "http://[fd00::1]/hook",
+            "http://[fe80::1]/hook",
+            "http://[::ffff:127.0.0.1]/hook",
+        ] {
+            assert!(is_private_host(&Url::parse(url).unwrap()), "{url}");
+        }
+
+        for url in [
+            "https://example.org/hook",
+            "https://93.184.215.14/hook",
+            "https://[2606:4700::1111]/hook",
+        ] {
+            assert!(!is_private_host(&Url::parse(url).unwrap()), "{url}");
+        }
+    }
+}
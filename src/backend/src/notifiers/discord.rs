@@ -0,0 +1,45 @@
+use async_trait::async_trait;
+use sonic_rs::json;
+
+use crate::{
+    notifiers::{
+        HTTP_CLIENT, NotificationEvent, Notifier,
+        message::{MessageLocale, PlainMessage},
+    },
+    workers::GenericResult,
+};
+
+/// The maximum length of the content of a Discord message
+const MAX_CONTENT_LENGTH: usize = 2000;
+
+/// Posts the messages to a Discord incoming webhook
+pub struct DiscordNotifier {
+    pub webhook_url: String,
+    pub locale: MessageLocale,
+}
+
+#[async_trait]
+impl Notifier for DiscordNotifier {
+    async fn notify(&self, event: &NotificationEvent) -> GenericResult<()> {
+        let message = PlainMessage::new(event, &self.locale);
+
+        let content: String = format!("**{}**\n{}", message.title, message.body)
+            .chars()
+            .take(MAX_CONTENT_LENGTH)
+            .collect();
+
+        HTTP_CLIENT
+            .post(&self.webhook_url)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(sonic_rs::to_vec(&json!({ "content": content }))?)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    fn kind(&self) -> &'static str {
+        "discord"
+    }
+}
@@ -0,0 +1,237 @@
+use async_trait::async_trait;
+use chrono::Duration;
+use chrono_tz::Tz;
+use color_eyre::Result;
+use humanize_duration::{Truncate, prelude::*};
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor, message::header::ContentType,
+    transport::smtp::authentication::Credentials,
+};
+use rust_i18n::t;
+use tracing::info;
+
+use crate::{
+    SITE_URL,
+    notifiers::{NotificationEvent, Notifier, message::last_run},
+    workers::GenericResult,
+};
+
+pub type SmtpClient = AsyncSmtpTransport<Tokio1Executor>;
+
+/// Sends the notifications to the email address of the user, in their
+/// language and timezone
+pub struct EmailNotifier {
+    pub smtp_client: SmtpClient,
+    pub user_email: String,
+    pub timezone: Tz,
+    pub language: String,
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    async fn notify(&self, event: &NotificationEvent) -> GenericResult<()> {
+        let email = match event {
+            NotificationEvent::Down { .. } => self.compose_down_email(event)?,
+            NotificationEvent::Up { .. } => self.compose_recovery_email(event)?,
+            NotificationEvent::Test => self.compose_test_email()?,
+        };
+
+        self.smtp_client.send(email).await?;
+
+        Ok(())
+    }
+
+    fn kind(&self) -> &'static str {
+        "email"
+    }
+}
+
+impl EmailNotifier {
+    //noinspection HtmlUnknownTarget
+    fn compose_down_email(&self, event: &NotificationEvent) -> GenericResult<Message> {
+        let NotificationEvent::Down {
+            system,
+            down_since,
+            down_after,
+            last_ping_kind,
+            last_exit_code,
+            last_output,
+        } = event
+        else {
+            return Err("Not a down event".into());
+        };
+
+        info!(
+            "Scheduled task: Composing email for the system {} (id {}, user email {}, down since {})",
+            system.name, system.id, self.user_email, down_since
+        );
+
+        let local_timestamp = down_since.with_timezone(&self.timezone);
+        let down_after = Duration::seconds(*down_after).human(Truncate::Minute);
+
+        let user_locale = self.language.as_str();
+
+        let last_run = last_run(*last_ping_kind, *last_exit_code, user_locale);
+
+        let last_output = match last_output.as_deref() {
+            Some(body) => format!(
+                // language=HTML
+                r#"
+                    <p>{}</p>
+                    <pre>{}</pre>
+                "#,
+                t!("email.output_of_the_last_run", locale = user_locale),
+                escape_html(body)
+            ),
+            None => String::new(),
+        };
+
+        let message = Message::builder()
+            .from("Monitor Mailer <monitor@polp.online>".parse()?)
+            .to(format!("User <{}>", self.user_email).as_str().parse()?)
+            .subject(t!(
+                "email.subject",
+                locale = user_locale,
+                service_name = system.name
+            ))
+            .header(ContentType::TEXT_HTML)
+            .body(format!(
+                // language=HTML
+                r#"
+                    <p>
+                      {}
+                      <time datetime="{}">
+                      {}
+                      </time>.
+                      <br />
+                      {}
+                      <br />
+                      {}
+                      <br />
+                      {}
+                      <a href="{}">{}</a>.
+                    </p>
+                    {}
+                "#,
+                t!(
+                    "email.service_is_down_since",
+                    locale = user_locale,
+                    service_name = system.name
+                ),
+                down_since.to_rfc3339(),
+                local_timestamp,
+                t!(
+                    "email.it_was_supposed_to_be_up_after",
+                    locale = user_locale,
+                    down_after = down_after
+                ),
+                last_run,
+                t!("email.check_its_status_now_at", locale = user_locale),
+                SITE_URL.as_str(),
+                SITE_URL.as_str(),
+                last_output
+            ))?;
+
+        Ok(message)
+    }
+
+    //noinspection HtmlUnknownTarget
+    fn compose_recovery_email(&self, event: &NotificationEvent) -> GenericResult<Message> {
+        let NotificationEvent::Up {
+            system,
+            down_since,
+            up_since,
+        } = event
+        else {
+            return Err("Not an up event".into());
+        };
+
+        info!(
+            "Scheduled task: Composing recovery email for the system {} (id {}, user email {})",
+            system.name, system.id, self.user_email
+        );
+
+        let local_down_since = down_since.with_timezone(&self.timezone);
+        let outage_duration = (*up_since - *down_since).human(Truncate::Minute);
+
+        let user_locale = self.language.as_str();
+
+        let message = Message::builder()
+            .from("Monitor Mailer <monitor@polp.online>".parse()?)
+            .to(format!("User <{}>", self.user_email).as_str().parse()?)
+            .subject(t!(
+                "email.recovery_subject",
+                locale = user_locale,
+                service_name = system.name
+            ))
+            .header(ContentType::TEXT_HTML)
+            .body(format!(
+                // language=HTML
+                r#"
+                    <p>
+                      {}
+                      <br />
+                      {}
+                      <time datetime="{}">
+                      {}
+                      </time>.
+                      <br />
+                      {}
+                      <a href="{}">{}</a>.
+                    </p>
+                "#,
+                t!(
+                    "email.service_is_back_up_after",
+                    locale = user_locale,
+                    service_name = system.name,
+                    outage_duration = outage_duration
+                ),
+                t!("email.it_was_down_since", locale = user_locale),
+                down_since.to_rfc3339(),
+                local_down_since,
+                t!("email.check_its_status_now_at", locale = user_locale),
+                SITE_URL.as_str(),
+                SITE_URL.as_str(),
+            ))?;
+
+        Ok(message)
+    }
+
+    fn compose_test_email(&self) -> GenericResult<Message> {
+        let user_locale = self.language.as_str();
+
+        let message = Message::builder()
+            .from("Monitor Mailer <monitor@polp.online>".parse()?)
+            .to(format!("User <{}>", self.user_email).as_str().parse()?)
+            .subject(t!("notification.test_subject", locale = user_locale))
+            .header(ContentType::TEXT_HTML)
+            .body(format!(
+                // language=HTML
+                r#"
+                    <p>{}</p>
+                "#,
+                t!("notification.test_body", locale = user_locale)
+            ))?;
+
+        Ok(message)
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub async fn init_smtp_client() -> Result<SmtpClient> {
+    let host = std::env::var("EMAIL_HOST")?;
+    let username = std::env::var("EMAIL_USERNAME")?;
+    let password = std::env::var("EMAIL_PASSWORD")?;
+
+    let creds = Credentials::new(username, password);
+
+    let client = SmtpClient::relay(&host)?.credentials(creds).build();
+
+    Ok(client)
+}
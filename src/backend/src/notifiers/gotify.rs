@@ -0,0 +1,51 @@
+use async_trait::async_trait;
+use sonic_rs::json;
+
+use crate::{
+    notifiers::{
+        HTTP_CLIENT, NotificationEvent, Notifier, endpoint,
+        message::{MessageLocale, PlainMessage},
+    },
+    workers::GenericResult,
+};
+
+/// Pushes the messages to a Gotify server
+pub struct GotifyNotifier {
+    pub server_url: String,
+    /// The token of the Gotify application the messages are sent as
+    pub app_token: String,
+    pub locale: MessageLocale,
+}
+
+#[async_trait]
+impl Notifier for GotifyNotifier {
+    async fn notify(&self, event: &NotificationEvent) -> GenericResult<()> {
+        let message = PlainMessage::new(event, &self.locale);
+
+        let priority = match event {
+            NotificationEvent::Down { .. } => 8,
+            NotificationEvent::Up { .. } | NotificationEvent::Test => 5,
+        };
+
+        let body = json!({
+            "title": message.title,
+            "message": message.body,
+            "priority": priority,
+        });
+
+        HTTP_CLIENT
+            .post(endpoint(&self.server_url, &["message"])?)
+            .header("X-Gotify-Key", &self.app_token)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(sonic_rs::to_vec(&body)?)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    fn kind(&self) -> &'static str {
+        "gotify"
+    }
+}
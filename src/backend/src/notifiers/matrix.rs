@@ -0,0 +1,64 @@
+use async_trait::async_trait;
+use sonic_rs::json;
+use uuid::Uuid;
+
+use crate::{
+    notifiers::{
+        HTTP_CLIENT, NotificationEvent, Notifier, endpoint,
+        message::{MessageLocale, PlainMessage},
+    },
+    workers::GenericResult,
+};
+
+/// Sends the messages to a Matrix room through the client-server API
+pub struct MatrixNotifier {
+    pub homeserver_url: String,
+    pub access_token: String,
+    pub room_id: String,
+    pub locale: MessageLocale,
+}
+
+#[async_trait]
+impl Notifier for MatrixNotifier {
+    async fn notify(&self, event: &NotificationEvent) -> GenericResult<()> {
+        let message = PlainMessage::new(event, &self.locale);
+
+        // Every message needs its own transaction ID, or the homeserver
+        // deduplicates it
+        let transaction_id = Uuid::new_v4().to_string();
+
+        let url = endpoint(
+            &self.homeserver_url,
+            &[
+                "_matrix",
+                "client",
+                "v3",
+                "rooms",
+                &self.room_id,
+                "send",
+                "m.room.message",
+                &transaction_id,
+            ],
+        )?;
+
+        let body = json!({
+            "msgtype": "m.text",
+            "body": message.text(),
+        });
+
+        HTTP_CLIENT
+            .put(url)
+            .bearer_auth(&self.access_token)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(sonic_rs::to_vec(&body)?)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    fn kind(&self) -> &'static str {
+        "matrix"
+    }
+}
@@ -0,0 +1,124 @@
+use std::borrow::Cow;
+
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use humanize_duration::{Truncate, prelude::*};
+use rust_i18n::t;
+
+use crate::{models::PingKind, notifiers::NotificationEvent};
+
+/// The language and the timezone in which the messages are written
+#[derive(Debug, Clone)]
+pub struct MessageLocale {
+    pub language: String,
+    pub timezone: Tz,
+}
+
+impl MessageLocale {
+    fn local_time(&self, timestamp: &DateTime<Utc>) -> String {
+        timestamp
+            .with_timezone(&self.timezone)
+            .format("%Y-%m-%d %H:%M %Z")
+            .to_string()
+    }
+}
+
+/// A localized plain text message describing an event, used by the chat
+/// channels
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlainMessage {
+    pub title: String,
+    pub body: String,
+}
+
+impl PlainMessage {
+    pub fn new(event: &NotificationEvent, locale: &MessageLocale) -> Self {
+        let language = locale.language.as_str();
+
+        match event {
+            NotificationEvent::Down {
+                system,
+                down_since,
+                down_after,
+                last_ping_kind,
+                last_exit_code,
+                ..
+            } => PlainMessage {
+                title: t!(
+                    "email.subject",
+                    locale = language,
+                    service_name = system.name
+                )
+                .trim()
+                .to_string(),
+                body: format!(
+                    "{} {}.\n{}\n{}",
+                    t!(
+                        "email.service_is_down_since",
+                        locale = language,
+                        service_name = system.name
+                    ),
+                    locale.local_time(down_since),
+                    t!(
+                        "email.it_was_supposed_to_be_up_after",
+                        locale = language,
+                        down_after = Duration::seconds(*down_after).human(Truncate::Minute)
+                    ),
+                    last_run(*last_ping_kind, *last_exit_code, language)
+                ),
+            },
+            NotificationEvent::Up {
+                system,
+                down_since,
+                up_since,
+            } => PlainMessage {
+                title: t!(
+                    "email.recovery_subject",
+                    locale = language,
+                    service_name = system.name
+                )
+                .to_string(),
+                body: format!(
+                    "{}\n{} {}.",
+                    t!(
+                        "email.service_is_back_up_after",
+                        locale = language,
+                        service_name = system.name,
+                        outage_duration = (*up_since - *down_since).human(Truncate::Minute)
+                    ),
+                    t!("email.it_was_down_since", locale = language),
+                    locale.local_time(down_since)
+                ),
+            },
+            NotificationEvent::Test => PlainMessage {
+                title: t!("notification.test_subject", locale = language).to_string(),
+                body: t!("notification.test_body", locale = language).to_string(),
+            },
+        }
+    }
+
+    /// The title followed by the body, for the services that have no title
+    pub fn text(&self) -> String {
+        format!("{}\n\n{}", self.title, self.body)
+    }
+}
+
+/// Describes the latest run of a system that is down
+pub fn last_run(
+    last_ping_kind: Option<PingKind>,
+    last_exit_code: Option<i32>,
+    language: &str,
+) -> Cow<'static, str> {
+    match (last_ping_kind, last_exit_code) {
+        (Some(PingKind::Fail), Some(exit_code)) => t!(
+            "email.last_run_exited_with_code",
+            locale = language,
+            exit_code = exit_code
+        ),
+        (Some(PingKind::Fail), None) => t!("email.last_run_failed", locale = language),
+        (Some(PingKind::Start), _) => t!("email.last_run_did_not_finish", locale = language),
+        (Some(PingKind::Success) | None, _) => {
+            t!("email.last_run_never_started", locale = language)
+        }
+    }
+}
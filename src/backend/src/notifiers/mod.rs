@@ -0,0 +1,791 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sidekiq::{RedisPool, Worker};
+use sqlx::PgPool;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    ALLOW_PRIVATE_TARGETS,
+    models::PingKind,
+    notifiers::{
+        address::{PublicResolver, is_private_host, public_redirect_policy},
+        discord::DiscordNotifier,
+        gotify::GotifyNotifier,
+        matrix::MatrixNotifier,
+        message::MessageLocale,
+        ntfy::{DEFAULT_NTFY_SERVER_URL, NtfyNotifier},
+        slack::SlackNotifier,
+        telegram::{DEFAULT_TELEGRAM_API_URL, TelegramNotifier},
+        webhook::WebhookNotifier,
+    },
+    workers::{
+        GenericResult,
+        notify_worker::{NotifyArgs, NotifyWorker},
+    },
+};
+
+pub mod address;
+pub mod discord;
+pub mod email;
+pub mod gotify;
+pub mod matrix;
+pub mod message;
+pub mod ntfy;
+pub mod slack;
+pub mod telegram;
+pub mod webhook;
+
+/// Shown in place of the secrets of the channels when they are listed
+pub const REDACTED: &str = "********";
+
+/// The time after which an outgoing notification request is considered failed
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The client used by the channels that send HTTP requests, it does not
+/// connect to private addresses unless ALLOW_PRIVATE_TARGETS is set
+pub static HTTP_CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
+    let builder = reqwest::Client::builder().timeout(HTTP_TIMEOUT);
+
+    let builder = if *ALLOW_PRIVATE_TARGETS {
+        builder
+    } else {
+        builder
+            .dns_resolver(PublicResolver)
+            .redirect(public_redirect_policy())
+    };
+
+    builder.build().expect("Failed to build the HTTP client")
+});
+
+/// A way of notifying a user about the events of their systems
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(&self, event: &NotificationEvent) -> GenericResult<()>;
+
+    /// The kind of channel (such as `email` or `slack`), recorded in the
+    /// notifications sent about the incidents
+    fn kind(&self) -> &'static str;
+}
+
+/// An event of a system that users get notified about (this is also the
+/// payload of the webhooks)
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum NotificationEvent {
+    /// The system did not ping in time, or it reported a failure
+    Down {
+        system: EventSystem,
+        /// The time since which the system is down
+        down_since: DateTime<Utc>,
+        /// The time in seconds after which the system is considered down
+        down_after: i64,
+        /// The kind of the latest ping received after the last successful one
+        last_ping_kind: Option<PingKind>,
+        /// The exit code reported by the latest ping, if any
+        last_exit_code: Option<i32>,
+        /// The output sent along with the failing (or latest) ping, if any
+        last_output: Option<String>,
+    },
+    /// The system pinged successfully after being down
+    Up {
+        system: EventSystem,
+        /// The time since which the system was down
+        down_since: DateTime<Utc>,
+        /// The time of the ping that brought the system back up
+        up_since: DateTime<Utc>,
+    },
+    /// Sent on request to check that a channel is configured correctly
+    Test,
+}
+
+impl NotificationEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            NotificationEvent::Down { .. } => "down",
+            NotificationEvent::Up { .. } => "up",
+            NotificationEvent::Test => "test",
+        }
+    }
+
+    /// The system the event is about
+    pub fn system(&self) -> Option<&EventSystem> {
+        match self {
+            NotificationEvent::Down { system, .. } | NotificationEvent::Up { system, .. } => {
+                Some(system)
+            }
+            NotificationEvent::Test => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EventSystem {
+    /// The ID of the system
+    pub id: Uuid,
+    /// The name of the system
+    pub name: String,
+}
+
+/// Where a notification is sent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NotificationTarget {
+    /// The email address of the user
+    Email { user_id: i32 },
+    /// A notification channel configured by the user
+    Channel { channel_id: i32 },
+}
+
+/// The configuration of a notification channel, stored as JSON
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ChannelConfig {
+    /// A JSON payload describing the event is POSTed to `url`, signed with
+    /// HMAC-SHA256 using `secret` (see the `X-Monitor-Signature` header), a
+    /// secret is generated if it is not given
+    Webhook { url: String, secret: Option<String> },
+    /// A Slack incoming webhook
+    Slack { webhook_url: String },
+    /// A Discord incoming webhook
+    Discord { webhook_url: String },
+    /// A Telegram chat, the messages are sent by the bot with the given token
+    /// (`api_url` defaults to the official Bot API server)
+    Telegram {
+        bot_token: String,
+        chat_id: String,
+        api_url: Option<String>,
+    },
+    /// A Matrix room, the messages are sent by the user of the access token
+    Matrix {
+        homeserver_url: String,
+        access_token: String,
+        room_id: String,
+    },
+    /// An ntfy topic (`server_url` defaults to ntfy.sh, `token` is only needed
+    /// for protected topics)
+    Ntfy {
+        topic: String,
+        server_url: Option<String>,
+        token: Option<String>,
+    },
+    /// A Gotify server, the messages are sent as the application of the token
+    Gotify {
+        server_url: String,
+        app_token: String,
+    },
+}
+
+impl ChannelConfig {
+    /// Validates the configuration given by the user, filling in the missing
+    /// secrets, the URLs cannot point to private addresses unless
+    /// `allow_private` is set
+    pub fn prepare(self, allow_private: bool) -> Result<Self, &'static str> {
+        self.check_targets(allow_private)?;
+
+        match self {
+            ChannelConfig::Webhook { url, secret } => {
+                if !is_http_url(&url) {
+                    return Err("Webhook URL is not valid");
+                }
+
+                let secret = secret
+                    .filter(|secret| !secret.is_empty())
+                    .unwrap_or_else(generate_secret);
+
+                Ok(ChannelConfig::Webhook {
+                    url,
+                    secret: Some(secret),
+                })
+            }
+            ChannelConfig::Slack { ref webhook_url }
+            | ChannelConfig::Discord { ref webhook_url } => {
+                if !is_http_url(webhook_url) {
+                    return Err("Webhook URL is not valid");
+                }
+
+                Ok(self)
+            }
+            ChannelConfig::Telegram {
+                ref bot_token,
+                ref chat_id,
+                ref api_url,
+            } => {
+                if bot_token.is_empty() || chat_id.is_empty() {
+                    return Err("Bot token and chat ID are required");
+                }
+
+                if api_url.as_deref().is_some_and(|url| !is_http_url(url)) {
+                    return Err("API URL is not valid");
+                }
+
+                Ok(self)
+            }
+            ChannelConfig::Matrix {
+                ref homeserver_url,
+                ref access_token,
+                ref room_id,
+            } => {
+                if !is_http_url(homeserver_url) {
+                    return Err("Homeserver URL is not valid");
+                }
+
+                if access_token.is_empty() || room_id.is_empty() {
+                    return Err("Access token and room ID are required");
+                }
+
+                Ok(self)
+            }
+            ChannelConfig::Ntfy {
+                ref topic,
+                ref server_url,
+                ..
+            } => {
+                if topic.is_empty() {
+                    return Err("Topic is required");
+                }
+
+                if server_url.as_deref().is_some_and(|url| !is_http_url(url)) {
+                    return Err("Server URL is not valid");
+                }
+
+                Ok(self)
+            }
+            ChannelConfig::Gotify {
+                ref server_url,
+                ref app_token,
+            } => {
+                if !is_http_url(server_url) {
+                    return Err("Server URL is not valid");
+                }
+
+                if app_token.is_empty() {
+                    return Err("Application token is required");
+                }
+
+                Ok(self)
+            }
+        }
+    }
+
+    /// Checks that none of the URLs of the channel points to a private address
+    /// (unless `allow_private` is set), the hosts that resolve to one are
+    /// rejected by the HTTP client when the request is sent
+    pub fn check_targets(&self, allow_private: bool) -> Result<(), &'static str> {
+        if allow_private {
+            return Ok(());
+        }
+
+        let urls = match self {
+            ChannelConfig::Webhook { url, .. } => Some(url),
+            ChannelConfig::Slack { webhook_url } | ChannelConfig::Discord { webhook_url } => {
+                Some(webhook_url)
+            }
+            ChannelConfig::Telegram { api_url, .. } => api_url.as_ref(),
+            ChannelConfig::Matrix { homeserver_url, .. } => Some(homeserver_url),
+            ChannelConfig::Ntfy { server_url, .. } => server_url.as_ref(),
+            ChannelConfig::Gotify { server_url, .. } => Some(server_url),
+        };
+
+        if urls
+            .and_then(|url| reqwest::Url::parse(url).ok())
+            .is_some_and(|url| is_private_host(&url))
+        {
+            return Err("URL points to a private address");
+        }
+
+        Ok(())
+    }
+
+    /// The configuration with its secrets (tokens, signing secrets and the
+    /// paths of the chat webhooks, which carry their token) replaced by
+    /// `REDACTED`, to be shown back to the user
+    pub fn redacted(self) -> Self {
+        let redact = |secret: String| {
+            if secret.is_empty() {
+                secret
+            } else {
+                REDACTED.to_string()
+            }
+        };
+
+        match self {
+            ChannelConfig::Webhook { url, secret } => ChannelConfig::Webhook {
+                url,
+                secret: secret.map(redact),
+            },
+            ChannelConfig::Slack { webhook_url } => ChannelConfig::Slack {
+                webhook_url: redact_path(&webhook_url),
+            },
+            ChannelConfig::Discord { webhook_url } => ChannelConfig::Discord {
+                webhook_url: redact_path(&webhook_url),
+            },
+            ChannelConfig::Telegram {
+                bot_token,
+                chat_id,
+                api_url,
+            } => ChannelConfig::Telegram {
+                bot_token: redact(bot_token),
+                chat_id,
+                api_url,
+            },
+            ChannelConfig::Matrix {
+                homeserver_url,
+                access_token,
+                room_id,
+            } => ChannelConfig::Matrix {
+                homeserver_url,
+                access_token: redact(access_token),
+                room_id,
+            },
+            ChannelConfig::Ntfy {
+                topic,
+                server_url,
+                token,
+            } => ChannelConfig::Ntfy {
+                topic,
+                server_url,
+                token: token.map(redact),
+            },
+            ChannelConfig::Gotify {
+                server_url,
+                app_token,
+            } => ChannelConfig::Gotify {
+                server_url,
+                app_token: redact(app_token),
+            },
+        }
+    }
+
+    /// Builds the notifier of the channel, the messages are written in the
+    /// given locale
+    pub fn into_notifier(self, locale: MessageLocale) -> Box<dyn Notifier> {
+        match self {
+            ChannelConfig::Webhook { url, secret } => Box::new(WebhookNotifier {
+                url,
+                secret: secret.unwrap_or_default(),
+            }),
+            ChannelConfig::Slack { webhook_url } => Box::new(SlackNotifier {
+                webhook_url,
+                locale,
+            }),
+            ChannelConfig::Discord { webhook_url } => Box::new(DiscordNotifier {
+                webhook_url,
+                locale,
+            }),
+            ChannelConfig::Telegram {
+                bot_token,
+                chat_id,
+                api_url,
+            } => Box::new(TelegramNotifier {
+                api_url: api_url.unwrap_or_else(|| DEFAULT_TELEGRAM_API_URL.to_string()),
+                bot_token,
+                chat_id,
+                locale,
+            }),
+            ChannelConfig::Matrix {
+                homeserver_url,
+                access_token,
+                room_id,
+            } => Box::new(MatrixNotifier {
+                homeserver_url,
+                access_token,
+                room_id,
+                locale,
+            }),
+            ChannelConfig::Ntfy {
+                topic,
+                server_url,
+                token,
+            } => Box::new(NtfyNotifier {
+                server_url: server_url.unwrap_or_else(|| DEFAULT_NTFY_SERVER_URL.to_string()),
+                topic,
+                token,
+                locale,
+            }),
+            ChannelConfig::Gotify {
+                server_url,
+                app_token,
+            } => Box::new(GotifyNotifier {
+                server_url,
+                app_token,
+                locale,
+            }),
+        }
+    }
+}
+
+/// Appends the path segments (percent-encoding them) to the base URL
+fn endpoint(base: &str, segments: &[&str]) -> GenericResult<reqwest::Url> {
+    let mut url = reqwest::Url::parse(base)?;
+
+    url.path_segments_mut()
+        .map_err(|_| "URL cannot be a base")?
+        .pop_if_empty()
+        .extend(segments);
+
+    Ok(url)
+}
+
+/// Keeps only the origin of the URL (such as `https://hooks.slack.com`)
+fn redact_path(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(url) => format!("{}/{REDACTED}", url.origin().ascii_serialization()),
+        Err(_) => REDACTED.to_string(),
+    }
+}
+
+fn is_http_url(url: &str) -> bool {
+    reqwest::Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+fn generate_secret() -> String {
+    hex::encode(rand::random::<[u8; 32]>())
+}
+
+/// Sends the event to the users alerted about the system (its owner for a
+/// personal system, the members who opted in for the system of an
+/// organization): by email (if their address is verified) and to every
+/// notification channel of theirs that applies to it (the ones of the user and
+/// the ones of the system), each notification is sent (and retried) by its own
+/// job and recorded in the incident, if any
+pub async fn dispatch(
+    redis: &RedisPool,
+    db: &PgPool,
+    incident_id: Option<i32>,
+    event: &NotificationEvent,
+) -> GenericResult<()> {
+    let Some(system) = event.system() else {
+        return Ok(());
+    };
+
+    let recipients = sqlx::query!(
+        r#"
+        WITH recipient AS (
+            SELECT user_id
+            FROM system
+            WHERE id = $1
+              AND organization_id IS NULL
+            UNION
+            SELECT m.user_id
+            FROM system s
+                JOIN organization_member m ON m.organization_id = s.organization_id
+            WHERE s.id = $1
+              AND m.receive_alerts = TRUE
+        )
+        SELECT u.id,
+               u.email_verified_at IS NOT NULL AS "email_verified!",
+               ARRAY(
+                   SELECT c.id
+                   FROM notification_channel c
+                   WHERE c.user_id = u.id
+                     AND (c.system_id IS NULL OR c.system_id = $1)
+                   ORDER BY c.id
+               ) AS "channel_ids!"
+        FROM "user" u
+            JOIN recipient r ON r.user_id = u.id
+        ORDER BY u.id
+        "#,
+        system.id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let targets = recipients.into_iter().flat_map(|recipient| {
+        recipient
+            .email_verified
+            .then_some(NotificationTarget::Email {
+                user_id: recipient.id,
+            })
+            .into_iter()
+            .chain(
+                recipient
+                    .channel_ids
+                    .into_iter()
+                    .map(|channel_id| NotificationTarget::Channel { channel_id }),
+            )
+    });
+
+    for target in targets {
+        NotifyWorker::opts()
+            .queue("notifications")
+            .perform_async(
+                redis,
+                NotifyArgs {
+                    target,
+                    incident_id,
+                    event: event.clone(),
+                },
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use axum::{Router, body::Bytes, extract::State};
+    use http::{HeaderMap, Method, Uri};
+    use sonic_rs::{JsonContainerTrait, JsonValueTrait, Value};
+
+    use super::*;
+
+    #[derive(Debug, Clone)]
+    struct RecordedRequest {
+        method: Method,
+        path: String,
+        headers: HeaderMap,
+        body: Bytes,
+    }
+
+    impl RecordedRequest {
+        fn json(&self) -> Value {
+            sonic_rs::from_slice(&self.body).unwrap()
+        }
+    }
+
+    type Recorded = Arc<Mutex<Vec<RecordedRequest>>>;
+
+    // Starts a local HTTP server standing in for the services, it records every
+    // request and answers with 200 OK
+    async fn stand_in() -> (String, Recorded) {
+        let recorded = Recorded::default();
+
+        let app = Router::new()
+            .fallback(
+                |State(recorded): State<Recorded>,
+                 method: Method,
+                 uri: Uri,
+                 headers: HeaderMap,
+                 body: Bytes| async move {
+                    recorded.lock().unwrap().push(RecordedRequest {
+                        method,
+                        path: uri.path().to_string(),
+                        headers,
+                        body,
+                    });
+                    "{}"
+                },
+            )
+            .with_state(recorded.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        (base_url, recorded)
+    }
+
+    fn down_event() -> NotificationEvent {
+        NotificationEvent::Down {
+            system: EventSystem {
+                id: Uuid::nil(),
+                name: "Backups".to_string(),
+            },
+            down_since: DateTime::from_timestamp(1_726_016_400, 0).unwrap(),
+            down_after: 3600,
+            last_ping_kind: Some(PingKind::Fail),
+            last_exit_code: Some(2),
+            last_output: None,
+        }
+    }
+
+    fn locale() -> MessageLocale {
+        MessageLocale {
+            language: "en".to_string(),
+            timezone: chrono_tz::Europe::Rome,
+        }
+    }
+
+    async fn send(config: ChannelConfig, recorded: &Recorded) -> RecordedRequest {
+        config
+            .prepare(true)
+            .unwrap()
+            .into_notifier(locale())
+            .notify(&down_event())
+            .await
+            .unwrap();
+
+        recorded.lock().unwrap().pop().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_chat_channels() {
+        let (base_url, recorded) = stand_in().await;
+
+        let request = send(
+            ChannelConfig::Slack {
+                webhook_url: format!("{base_url}/services/slack"),
+            },
+            &recorded,
+        )
+        .await;
+        assert_eq!(request.path, "/services/slack");
+        let text = request.json()["text"].as_str().unwrap().to_string();
+        assert!(text.starts_with("*Service Backups is down*\n"));
+        assert!(text.contains("2024-09-11 03:00 CEST"));
+        assert!(text.contains("exited with code 2"));
+
+        let request = send(
+            ChannelConfig::Discord {
+                webhook_url: format!("{base_url}/api/webhooks/1/token"),
+            },
+            &recorded,
+        )
+        .await;
+        assert_eq!(request.path, "/api/webhooks/1/token");
+        assert!(
+            request.json()["content"]
+                .as_str()
+                .unwrap()
+                .starts_with("**Service Backups is down**\n")
+        );
+
+        let request = send(
+            ChannelConfig::Telegram {
+                bot_token: "123:secret".to_string(),
+                chat_id: "-42".to_string(),
+                api_url: Some(base_url.clone()),
+            },
+            &recorded,
+        )
+        .await;
+        assert_eq!(request.path, "/bot123:secret/sendMessage");
+        assert_eq!(request.json()["chat_id"].as_str(), Some("-42"));
+
+        let request = send(
+            ChannelConfig::Matrix {
+                homeserver_url: base_url.clone(),
+                access_token: "token".to_string(),
+                room_id: "!room:example.org".to_string(),
+            },
+            &recorded,
+        )
+        .await;
+        assert_eq!(request.method, Method::PUT);
+        assert!(
+            request
+                .path
+                .starts_with("/_matrix/client/v3/rooms/!room:example.org/send/m.room.message/")
+        );
+        assert_eq!(request.headers["authorization"], "Bearer token");
+        assert_eq!(request.json()["msgtype"].as_str(), Some("m.text"));
+
+        let request = send(
+            ChannelConfig::Ntfy {
+                topic: "alerts".to_string(),
+                server_url: Some(base_url.clone()),
+                token: None,
+            },
+            &recorded,
+        )
+        .await;
+        assert_eq!(request.path, "/");
+        let json = request.json();
+        assert_eq!(json["topic"].as_str(), Some("alerts"));
+        assert_eq!(json["title"].as_str(), Some("Service Backups is down"));
+        assert_eq!(json["tags"].as_array().unwrap().len(), 1);
+
+        let request = send(
+            ChannelConfig::Gotify {
+                server_url: base_url.clone(),
+                app_token: "app".to_string(),
+            },
+            &recorded,
+        )
+        .await;
+        assert_eq!(request.path, "/message");
+        assert_eq!(request.headers["x-gotify-key"], "app");
+        assert_eq!(request.json()["priority"].as_i64(), Some(8));
+    }
+
+    #[tokio::test]
+    async fn test_webhook_channel() {
+        let (base_url, recorded) = stand_in().await;
+
+        let request = send(
+            ChannelConfig::Webhook {
+                url: format!("{base_url}/hook"),
+                secret: Some("secret".to_string()),
+            },
+            &recorded,
+        )
+        .await;
+
+        assert_eq!(request.path, "/hook");
+        assert_eq!(request.headers[webhook::EVENT_HEADER], "down");
+        assert_eq!(
+            request.headers[webhook::SIGNATURE_HEADER],
+            webhook::sign(b"secret", &request.body).as_str()
+        );
+        assert_eq!(request.json()["event"].as_str(), Some("down"));
+        assert_eq!(request.json()["system"]["name"].as_str(), Some("Backups"));
+    }
+
+    #[test]
+    fn test_prepare() {
+        assert!(
+            ChannelConfig::Slack {
+                webhook_url: "ftp://example.org".to_string()
+            }
+            .prepare(true)
+            .is_err()
+        );
+
+        // The private addresses are only allowed when asked for
+        let local = ChannelConfig::Gotify {
+            server_url: "http://169.254.169.254".to_string(),
+            app_token: "app".to_string(),
+        };
+        assert!(local.clone().prepare(false).is_err());
+        assert!(local.prepare(true).is_ok());
+
+        // A secret is generated for the webhooks that do not have one
+        let ChannelConfig::Webhook {
+            secret: Some(secret),
+            ..
+        } = ChannelConfig::Webhook {
+            url: "https://example.org/hook".to_string(),
+            secret: None,
+        }
+        .prepare(false)
+        .unwrap()
+        else {
+            panic!("Expected a webhook with a secret");
+        };
+        assert_eq!(secret.len(), 64);
+    }
+
+    #[test]
+    fn test_redacted() {
+        let ChannelConfig::Slack { webhook_url } = ChannelConfig::Slack {
+            webhook_url: "https://hooks.slack.com/services/T0/B0/secret".to_string(),
+        }
+        .redacted() else {
+            panic!("Expected a Slack channel");
+        };
+        assert_eq!(webhook_url, "https://hooks.slack.com/********");
+
+        let ChannelConfig::Telegram {
+            bot_token, chat_id, ..
+        } = ChannelConfig::Telegram {
+            bot_token: "123:secret".to_string(),
+            chat_id: "-42".to_string(),
+            api_url: None,
+        }
+        .redacted()
+        else {
+            panic!("Expected a Telegram channel");
+        };
+        assert_eq!(bot_token, REDACTED);
+        assert_eq!(chat_id, "-42");
+    }
+}
@@ -0,0 +1,60 @@
+use async_trait::async_trait;
+use sonic_rs::json;
+
+use crate::{
+    notifiers::{
+        HTTP_CLIENT, NotificationEvent, Notifier, endpoint,
+        message::{MessageLocale, PlainMessage},
+    },
+    workers::GenericResult,
+};
+
+/// The URL of the public ntfy server
+pub const DEFAULT_NTFY_SERVER_URL: &str = "https://ntfy.sh";
+
+/// Publishes the messages to an ntfy topic
+pub struct NtfyNotifier {
+    pub server_url: String,
+    pub topic: String,
+    /// The access token, for protected topics
+    pub token: Option<String>,
+    pub locale: MessageLocale,
+}
+
+#[async_trait]
+impl Notifier for NtfyNotifier {
+    async fn notify(&self, event: &NotificationEvent) -> GenericResult<()> {
+        let message = PlainMessage::new(event, &self.locale);
+
+        let (priority, tag) = match event {
+            NotificationEvent::Down { .. } => (4, "rotating_light"),
+            NotificationEvent::Up { .. } => (3, "white_check_mark"),
+            NotificationEvent::Test => (3, "bell"),
+        };
+
+        let body = json!({
+            "topic": self.topic,
+            "title": message.title,
+            "message": message.body,
+            "priority": priority,
+            "tags": [tag],
+        });
+
+        let mut request = HTTP_CLIENT
+            .post(endpoint(&self.server_url, &[])?)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(sonic_rs::to_vec(&body)?);
+
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+
+        request.send().await?.error_for_status()?;
+
+        Ok(())
+    }
+
+    fn kind(&self) -> &'static str {
+        "ntfy"
+    }
+}
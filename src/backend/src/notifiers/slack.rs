@@ -0,0 +1,41 @@
+use async_trait::async_trait;
+use sonic_rs::json;
+
+use crate::{
+    notifiers::{
+        HTTP_CLIENT, NotificationEvent, Notifier,
+        message::{MessageLocale, PlainMessage},
+    },
+    workers::GenericResult,
+};
+
+/// Posts the messages to a Slack incoming webhook
+pub struct SlackNotifier {
+    pub webhook_url: String,
+    pub locale: MessageLocale,
+}
+
+#[async_trait]
+impl Notifier for SlackNotifier {
+    async fn notify(&self, event: &NotificationEvent) -> GenericResult<()> {
+        let message = PlainMessage::new(event, &self.locale);
+
+        let body = json!({
+            "text": format!("*{}*\n{}", message.title, message.body),
+        });
+
+        HTTP_CLIENT
+            .post(&self.webhook_url)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(sonic_rs::to_vec(&body)?)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    fn kind(&self) -> &'static str {
+        "slack"
+    }
+}
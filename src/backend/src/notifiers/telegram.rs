@@ -0,0 +1,52 @@
+use async_trait::async_trait;
+use sonic_rs::json;
+
+use crate::{
+    notifiers::{
+        HTTP_CLIENT, NotificationEvent, Notifier, endpoint,
+        message::{MessageLocale, PlainMessage},
+    },
+    workers::GenericResult,
+};
+
+/// The URL of the official Telegram Bot API server
+pub const DEFAULT_TELEGRAM_API_URL: &str = "https://api.telegram.org";
+
+/// Sends the messages to a Telegram chat through the Bot API
+pub struct TelegramNotifier {
+    pub api_url: String,
+    pub bot_token: String,
+    pub chat_id: String,
+    pub locale: MessageLocale,
+}
+
+#[async_trait]
+impl Notifier for TelegramNotifier {
+    async fn notify(&self, event: &NotificationEvent) -> GenericResult<()> {
+        let message = PlainMessage::new(event, &self.locale);
+
+        let url = endpoint(
+            &self.api_url,
+            &[&format!("bot{}", self.bot_token), "sendMessage"],
+        )?;
+
+        let body = json!({
+            "chat_id": self.chat_id,
+            "text": message.text(),
+        });
+
+        HTTP_CLIENT
+            .post(url)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(sonic_rs::to_vec(&body)?)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    fn kind(&self) -> &'static str {
+        "telegram"
+    }
+}
@@ -0,0 +1,66 @@
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::{
+    notifiers::{HTTP_CLIENT, NotificationEvent, Notifier},
+    workers::GenericResult,
+};
+
+/// The header containing the HMAC-SHA256 of the body, as `sha256=<hex digest>`
+pub const SIGNATURE_HEADER: &str = "X-Monitor-Signature";
+/// The header containing the name of the event (`down` or `up`)
+pub const EVENT_HEADER: &str = "X-Monitor-Event";
+
+/// POSTs the event as JSON to a URL, signing the body with a secret so that
+/// the receiver can verify that it was sent by us
+pub struct WebhookNotifier {
+    pub url: String,
+    pub secret: String,
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, event: &NotificationEvent) -> GenericResult<()> {
+        let body = sonic_rs::to_vec(event)?;
+
+        HTTP_CLIENT
+            .post(&self.url)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, event.name())
+            .header(SIGNATURE_HEADER, sign(self.secret.as_bytes(), &body))
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    fn kind(&self) -> &'static str {
+        "webhook"
+    }
+}
+
+/// Signs the body with HMAC-SHA256, formatted as the value of the signature
+/// header
+pub fn sign(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC can take a key of any size");
+    mac.update(body);
+
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            sign(b"Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
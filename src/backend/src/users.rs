@@ -1,17 +1,42 @@
+use std::sync::Arc;
+
 use axum_login::{AuthUser, AuthnBackend, UserId};
+use chrono::NaiveDateTime;
 use password_auth::verify_password;
 use serde::{Deserialize, Serialize};
+use sidekiq::RedisPool;
 use sqlx::{FromRow, PgPool};
 use tokio::task;
+use tracing::error;
+
+use crate::{
+    notifiers::email::SmtpClient,
+    web::auth::{
+        email_token::{
+            EmailTokenKind, send_email_change_notice, send_email_token, send_email_token_to,
+        },
+        oidc::OidcClient,
+        totp::{confirmed_totp_secret, verify_second_factor},
+    },
+};
 use utoipa::ToSchema;
 
 #[derive(Clone, Serialize, Deserialize, FromRow)]
 pub struct User {
     pub id: i32,
     pub email: String,
-    pub password: String,
+    /// Missing for the users created by single sign-on
+    pub password: Option<String>,
     pub timezone: String,
     pub language: String,
+    pub email_verified_at: Option<NaiveDateTime>,
+    pub oidc_subject: Option<String>,
+    /// Whether the user administers the instance
+    pub is_admin: bool,
+    /// Disabled users cannot log in
+    pub disabled_at: Option<NaiveDateTime>,
+    /// A hash of the password and the email address, computed by the database
+    pub session_auth_hash: String,
 }
 
 // Here we've implemented `Debug` manually to avoid accidentally logging the
@@ -34,10 +59,10 @@ impl AuthUser for User {
     }
 
     fn session_auth_hash(&self) -> &[u8] {
-        self.password.as_bytes() // We use the password hash as the auth
-        // hash--what this means
-        // is when the user changes their password the
+        // We use a hash of the password hash and the email as the auth hash--what
+        // this means is when the user changes their password or their email the
         // auth session becomes invalid.
+        self.session_auth_hash.as_bytes()
     }
 }
 
@@ -49,16 +74,125 @@ pub struct Credentials {
     pub email: String,
     /// The password of the user
     pub password: String,
+    /// A code from the authenticator app or a recovery code, required when the
+    /// user has 2FA enabled
+    #[serde(default)]
+    pub totp_code: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct LoginBackend {
     pub db: PgPool,
+    pub redis: RedisPool,
+    pub smtp_client: SmtpClient,
+    /// The identity provider for single sign-on, if configured
+    pub oidc: Option<Arc<OidcClient>>,
+}
+
+impl std::fmt::Debug for LoginBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoginBackend")
+            .field("db", &self.db)
+            .field("redis", &self.redis)
+            .field("oidc", &self.oidc)
+            .finish_non_exhaustive()
+    }
 }
 
 impl LoginBackend {
-    pub fn new(db: PgPool) -> Self {
-        Self { db }
+    pub fn new(db: PgPool, redis: RedisPool, smtp_client: SmtpClient) -> Self {
+        Self {
+            db,
+            redis,
+            smtp_client,
+            oidc: None,
+        }
+    }
+
+    pub fn with_oidc(mut self, oidc: Option<OidcClient>) -> Self {
+        self.oidc = oidc.map(Arc::new);
+        self
+    }
+
+    /// Checks the password of the user, without the second factor (to confirm
+    /// sensitive actions of a user that is already logged in)
+    pub async fn check_password(
+        &self,
+        email: &str,
+        password: String,
+    ) -> Result<Option<User>, Error> {
+        let user: Option<User> = sqlx::query_as!(
+            User,
+            r#"
+            SELECT * FROM "user" WHERE email = $1
+            "#,
+            email
+        )
+        .fetch_optional(&self.db)
+        .await?;
+
+        // Verifying the password is blocking and potentially slow, so we'll do so via
+        // `spawn_blocking`.
+        task::spawn_blocking(|| {
+            // We're using password-based authentication--this works by comparing our form
+            // input with an argon2 password hash.
+            Ok(user.filter(|user| {
+                user.password
+                    .as_deref()
+                    .is_some_and(|hash| verify_password(password, hash).is_ok())
+            }))
+        })
+        .await?
+    }
+
+    /// Sends a single-use link of the kind to the email address of the user in
+    /// the background, so that the request does not wait for the SMTP server
+    /// (failures are logged)
+    pub fn send_email_token(&self, user: &User, kind: EmailTokenKind) {
+        let backend = self.clone();
+        let user = user.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = send_email_token(&backend.db, &backend.smtp_client, &user, kind).await {
+                error!(
+                    "Error sending the {:?} email to user {}: {}",
+                    kind, user.email, e
+                );
+            }
+        });
+    }
+
+    /// Sends the link confirming the email change to the new address, and a
+    /// notice to the current one, in the background (failures are logged)
+    pub fn send_email_change(&self, user: &User, new_email: &str) {
+        let backend = self.clone();
+        let user = user.clone();
+        let new_email = new_email.to_string();
+
+        tokio::spawn(async move {
+            if let Err(e) = send_email_token_to(
+                &backend.db,
+                &backend.smtp_client,
+                &user,
+                &new_email,
+                EmailTokenKind::ChangeEmail,
+            )
+            .await
+            {
+                error!(
+                    "Error sending the email change link to {} for user {}: {}",
+                    new_email, user.email, e
+                );
+            }
+
+            if let Err(e) = send_email_change_notice(&backend.smtp_client, &user, &new_email).await
+            {
+                error!(
+                    "Error sending the email change notice to user {}: {}",
+                    user.email, e
+                );
+            }
+        });
     }
 }
 
@@ -69,6 +203,17 @@ pub enum Error {
 
     #[error(transparent)]
     TaskJoin(#[from] task::JoinError),
+
+    /// The password is right, but the user has 2FA enabled and no code was given
+    #[error("Second factor required")]
+    SecondFactorRequired,
+
+    #[error("Wrong second factor")]
+    WrongSecondFactor,
+
+    /// The password is right, but the account was disabled by an admin
+    #[error("Account is disabled")]
+    AccountDisabled,
 }
 
 impl AuthnBackend for LoginBackend {
@@ -80,31 +225,36 @@ impl AuthnBackend for LoginBackend {
         &self,
         creds: Self::Credentials,
     ) -> Result<Option<Self::User>, Self::Error> {
-        let user: Option<Self::User> = sqlx::query_as!(
-            User,
-            r#"
-            SELECT * FROM "user" WHERE email = $1
-            "#,
-            creds.email
-        )
-        .fetch_optional(&self.db)
-        .await?;
+        let Some(user) = self.check_password(&creds.email, creds.password).await? else {
+            return Ok(None);
+        };
 
-        // Verifying the password is blocking and potentially slow, so we'll do so via
-        // `spawn_blocking`.
-        task::spawn_blocking(|| {
-            // We're using password-based authentication--this works by comparing our form
-            // input with an argon2 password hash.
-            Ok(user.filter(|user| verify_password(creds.password, &user.password).is_ok()))
-        })
-        .await?
+        if user.disabled_at.is_some() {
+            return Err(Error::AccountDisabled);
+        }
+
+        let Some(secret) = confirmed_totp_secret(&self.db, user.id).await? else {
+            return Ok(Some(user));
+        };
+
+        let Some(code) = creds.totp_code else {
+            return Err(Error::SecondFactorRequired);
+        };
+
+        if !verify_second_factor(&self.db, user.id, &secret, &code).await? {
+            return Err(Error::WrongSecondFactor);
+        }
+
+        Ok(Some(user))
     }
 
+    /// The disabled users are not found, so that their sessions are logged out
+    /// (and their API tokens rejected)
     async fn get_user(&self, user_id: &UserId<Self>) -> Result<Option<Self::User>, Self::Error> {
         let user = sqlx::query_as!(
             User,
             r#"
-            SELECT * FROM "user" WHERE id = $1
+            SELECT * FROM "user" WHERE id = $1 AND disabled_at IS NULL
             "#,
             user_id
         )
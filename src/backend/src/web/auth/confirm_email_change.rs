@@ -0,0 +1,240 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::Deserialize;
+use sonic_rs::json;
+use utoipa::ToSchema;
+
+use crate::{
+    app::openapi::AUTH_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    middleware::track_session::forget_user_sessions,
+    users::AuthSession,
+    web::auth::{
+        email_token::{EmailTokenKind, consume_email_token},
+        login::AuthError,
+    },
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ConfirmEmailChangeRequest {
+    /// The token contained in the link sent to the new email address
+    token: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/confirm_email_change",
+    summary = "Confirm Email Change",
+    description = "Replace the email address of a user with the new one the token was sent to, the existing sessions of the user are logged out",
+    request_body = ConfirmEmailChangeRequest,
+    responses(
+        (status = OK, description = "Email was changed successfully"),
+        (status = BAD_REQUEST, description = "Token is not valid or expired"),
+        (status = CONFLICT, description = "Email is already in use"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    tag = AUTH_TAG
+)]
+pub async fn confirm_email_change(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<ConfirmEmailChangeRequest>,
+) -> impl IntoResponse {
+    let Ok(mut tx) = auth_session.backend.db.begin().await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    let token =
+        match consume_email_token(&mut tx, &request.token, EmailTokenKind::ChangeEmail).await {
+            Ok(Some(token)) => token,
+            Ok(None) => return AuthError::InvalidToken.into_response(),
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        };
+
+    // The new address is verified by the token, the change of the address (and
+    // so of the session auth hash) logs out the sessions
+    let old_email = match sqlx::query_scalar!(
+        r#"
+        UPDATE "user" u
+        SET email = $2,
+            email_verified_at = NOW()
+        FROM (SELECT id, email FROM "user" WHERE id = $1 FOR UPDATE) old
+        WHERE u.id = old.id
+        RETURNING old.email
+        "#,
+        token.user_id,
+        token.email
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    {
+        Ok(Some(old_email)) => old_email,
+        Ok(None) => return AuthError::InvalidToken.into_response(),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            return AuthError::EmailAlreadyInUse.into_response();
+        }
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    // The links sent to the previous address (such as to reset the password)
+    // cannot be used anymore
+    let revoked = sqlx::query!(
+        r#"
+        UPDATE email_token
+        SET used_at = NOW()
+        WHERE user_id = $1
+          AND used_at IS NULL
+        "#,
+        token.user_id
+    )
+    .execute(&mut *tx)
+    .await;
+
+    if revoked.is_err()
+        || forget_user_sessions(&mut *tx, token.user_id).await.is_err()
+        || tx.commit().await.is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    audit
+        .event(AuditAction::EmailChanged, AuditTarget::User, token.user_id)
+        .actor(token.user_id)
+        .before(json!({ "email": old_email }))
+        .after(json!({ "email": token.email }))
+        .record()
+        .await;
+
+    StatusCode::OK.into_response()
+}
+
+#[cfg(test)]
+mod test {
+    use http::header;
+    use password_auth::generate_hash;
+    use sqlx::PgPool;
+
+    use crate::{
+        app::test_app::TestApp,
+        users::User,
+        web::auth::email_token::{create_email_token, create_email_token_to},
+    };
+
+    use super::*;
+
+    #[sqlx::test]
+    #[ignore = "needs a PostgreSQL database (set DATABASE_URL)"]
+    async fn test_change_email(db: PgPool) {
+        let app = TestApp::spawn(db).await;
+        let user_id = app.create_user("owner@example.com").await;
+        app.create_user("taken@example.com").await;
+
+        let user = sqlx::query_as!(
+            User,
+            r#"UPDATE "user" SET password = $2 WHERE id = $1 RETURNING *"#,
+            user_id,
+            generate_hash("hunter42")
+        )
+        .fetch_one(&app.db)
+        .await
+        .unwrap();
+        let cookie = app.login(user_id).await;
+
+        let change_email = |new_email: &str, password: &str| {
+            app.client
+                .post(app.url("/user/change_email"))
+                .header(header::COOKIE, &cookie)
+                .json(&json!({ "new_email": new_email, "password": password }))
+                .send()
+        };
+        let login = |email: &str| {
+            app.client
+                .post(app.url("/login"))
+                .json(&json!({ "email": email, "password": "hunter42" }))
+                .send()
+        };
+
+        assert_eq!(
+            change_email("new@example.com", "hunter22")
+                .await
+                .unwrap()
+                .status(),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            change_email("taken@example.com", "hunter42")
+                .await
+                .unwrap()
+                .status(),
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            change_email("new@example.com", "hunter42")
+                .await
+                .unwrap()
+                .status(),
+            StatusCode::OK
+        );
+
+        // The address is only changed once the link is opened
+        assert_eq!(
+            login("owner@example.com").await.unwrap().status(),
+            StatusCode::OK
+        );
+
+        let token = create_email_token_to(
+            &app.db,
+            &user,
+            "new@example.com",
+            EmailTokenKind::ChangeEmail,
+        )
+        .await
+        .unwrap();
+        let reset_token = create_email_token(&app.db, &user, EmailTokenKind::ResetPassword)
+            .await
+            .unwrap();
+
+        let confirm = |token: &str| {
+            app.client
+                .post(app.url("/confirm_email_change"))
+                .json(&json!({ "token": token }))
+                .send()
+        };
+
+        assert_eq!(confirm(&token).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(
+            confirm(&token).await.unwrap().status(),
+            StatusCode::BAD_REQUEST
+        );
+
+        // The sessions are logged out by the new session auth hash
+        let response = app
+            .client
+            .get(app.url("/user/get_current_settings"))
+            .header(header::COOKIE, &cookie)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        assert_eq!(
+            login("owner@example.com").await.unwrap().status(),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            login("new@example.com").await.unwrap().status(),
+            StatusCode::OK
+        );
+
+        // The links sent to the previous address stopped working
+        let response = app
+            .client
+            .post(app.url("/reset_password"))
+            .json(&json!({ "token": reset_token, "new_password": "hunter23" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
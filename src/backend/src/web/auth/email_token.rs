@@ -0,0 +1,334 @@
+use chrono::{Duration, Utc};
+use lettre::{AsyncTransport, Message, message::header::ContentType};
+use rust_i18n::t;
+use sqlx::{PgConnection, PgPool};
+
+use crate::{
+    SITE_URL,
+    notifiers::email::SmtpClient,
+    users::User,
+    web::utils::token::{generate_token, hash_token},
+    workers::GenericResult,
+};
+
+/// What a single-use token sent by email allows to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "email_token_kind", rename_all = "snake_case")]
+pub enum EmailTokenKind {
+    /// Proves that the user can read the emails sent to their address
+    VerifyEmail,
+    /// Allows to choose a new password without knowing the old one
+    ResetPassword,
+    /// Proves that the user can read the emails sent to the new address they
+    /// asked to change to (the token is sent to, and stores, the new address)
+    ChangeEmail,
+}
+
+impl EmailTokenKind {
+    /// How long the token can be used for
+    fn validity(self) -> Duration {
+        match self {
+            EmailTokenKind::VerifyEmail => Duration::days(2),
+            EmailTokenKind::ResetPassword => Duration::hours(1),
+            EmailTokenKind::ChangeEmail => Duration::hours(24),
+        }
+    }
+
+    /// The page of the frontend the link in the email opens
+    fn page(self) -> &'static str {
+        match self {
+            EmailTokenKind::VerifyEmail => "verify_email",
+            EmailTokenKind::ResetPassword => "reset_password",
+            EmailTokenKind::ChangeEmail => "confirm_email_change",
+        }
+    }
+}
+
+/// A token that was consumed, along with the address it was sent to
+#[derive(Debug)]
+pub struct ConsumedEmailToken {
+    pub user_id: i32,
+    pub email: String,
+}
+
+/// Creates a token of the kind for the current email address of the user,
+/// returning the token (only its hash is stored)
+pub async fn create_email_token(
+    db: &PgPool,
+    user: &User,
+    kind: EmailTokenKind,
+) -> Result<String, sqlx::Error> {
+    create_email_token_to(db, user, &user.email, kind).await
+}
+
+/// Creates a token of the kind for the email address, returning the token
+pub async fn create_email_token_to(
+    db: &PgPool,
+    user: &User,
+    email: &str,
+    kind: EmailTokenKind,
+) -> Result<String, sqlx::Error> {
+    let (token, token_hash) = generate_token("");
+
+    sqlx::query!(
+        r#"
+        INSERT INTO email_token (user_id, kind, token_hash, email, expires_at)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        user.id,
+        kind as EmailTokenKind,
+        token_hash,
+        email,
+        (Utc::now() + kind.validity()).naive_utc()
+    )
+    .execute(db)
+    .await?;
+
+    Ok(token)
+}
+
+/// Marks the token as used, if it is of the kind, unused and not expired
+pub async fn consume_email_token(
+    conn: &mut PgConnection,
+    token: &str,
+    kind: EmailTokenKind,
+) -> Result<Option<ConsumedEmailToken>, sqlx::Error> {
+    sqlx::query_as!(
+        ConsumedEmailToken,
+        r#"
+        UPDATE email_token
+        SET used_at = NOW()
+        WHERE token_hash = $1
+          AND kind = $2
+          AND used_at IS NULL
+          AND expires_at > NOW()
+        RETURNING user_id, email
+        "#,
+        hash_token(token.trim()),
+        kind as EmailTokenKind
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// Creates a token of the kind and sends the link containing it to the user
+pub async fn send_email_token(
+    db: &PgPool,
+    smtp_client: &SmtpClient,
+    user: &User,
+    kind: EmailTokenKind,
+) -> GenericResult<()> {
+    send_email_token_to(db, smtp_client, user, &user.email, kind).await
+}
+
+/// Creates a token of the kind and sends the link containing it to the email
+/// address (another one than the user's for the email changes)
+pub async fn send_email_token_to(
+    db: &PgPool,
+    smtp_client: &SmtpClient,
+    user: &User,
+    email: &str,
+    kind: EmailTokenKind,
+) -> GenericResult<()> {
+    let token = create_email_token_to(db, user, email, kind).await?;
+
+    smtp_client
+        .send(compose_email_token_email(user, email, kind, &token)?)
+        .await?;
+
+    Ok(())
+}
+
+/// Tells the user, at their current address, that the email address of the
+/// account is being changed, in case they did not ask for it
+pub async fn send_email_change_notice(
+    smtp_client: &SmtpClient,
+    user: &User,
+    new_email: &str,
+) -> GenericResult<()> {
+    let user_locale = user.language.as_str();
+
+    let message = Message::builder()
+        .from("Monitor Mailer <monitor@polp.online>".parse()?)
+        .to(format!("User <{}>", user.email).as_str().parse()?)
+        .subject(t!(
+            "email.email_change_notice_subject",
+            locale = user_locale
+        ))
+        .header(ContentType::TEXT_HTML)
+        .body(format!(
+            // language=HTML
+            r#"
+                <p>
+                  {}
+                </p>
+            "#,
+            t!(
+                "email.email_change_notice_body",
+                locale = user_locale,
+                new_email = new_email
+            ),
+        ))?;
+
+    smtp_client.send(message).await?;
+
+    Ok(())
+}
+
+//noinspection HtmlUnknownTarget
+fn compose_email_token_email(
+    user: &User,
+    email: &str,
+    kind: EmailTokenKind,
+    token: &str,
+) -> GenericResult<Message> {
+    let user_locale = user.language.as_str();
+
+    let (subject, body) = match kind {
+        EmailTokenKind::VerifyEmail => (
+            t!("email.verify_email_subject", locale = user_locale),
+            t!("email.verify_email_body", locale = user_locale),
+        ),
+        EmailTokenKind::ResetPassword => (
+            t!("email.reset_password_subject", locale = user_locale),
+            t!("email.reset_password_body", locale = user_locale),
+        ),
+        EmailTokenKind::ChangeEmail => (
+            t!("email.change_email_subject", locale = user_locale),
+            t!("email.change_email_body", locale = user_locale),
+        ),
+    };
+
+    let link = format!("{}/{}?token={}", SITE_URL.as_str(), kind.page(), token);
+
+    let message = Message::builder()
+        .from("Monitor Mailer <monitor@polp.online>".parse()?)
+        .to(format!("User <{}>", email).as_str().parse()?)
+        .subject(subject)
+        .header(ContentType::TEXT_HTML)
+        .body(format!(
+            // language=HTML
+            r#"
+                <p>
+                  {}
+                  <br />
+                  <a href="{}">{}</a>
+                  <br />
+                  {}
+                </p>
+            "#,
+            body,
+            link,
+            link,
+            t!(
+                "email.link_expires_in_hours",
+                locale = user_locale,
+                hours = kind.validity().num_hours()
+            ),
+        ))?;
+
+    Ok(message)
+}
+
+#[cfg(test)]
+mod test {
+    use http::StatusCode;
+    use sonic_rs::json;
+
+    use crate::app::test_app::TestApp;
+
+    use super::*;
+
+    async fn user(app: &TestApp, email: &str) -> User {
+        let user_id = app.create_user(email).await;
+
+        sqlx::query_as!(User, r#"SELECT * FROM "user" WHERE id = $1"#, user_id)
+            .fetch_one(&app.db)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a PostgreSQL database (set DATABASE_URL)"]
+    async fn test_email_token_is_single_use(db: PgPool) {
+        let app = TestApp::spawn(db).await;
+        let user = user(&app, "owner@example.com").await;
+
+        let token = create_email_token(&app.db, &user, EmailTokenKind::VerifyEmail)
+            .await
+            .unwrap();
+
+        let mut conn = app.db.acquire().await.unwrap();
+        // A token cannot be used for another purpose
+        assert!(
+            consume_email_token(&mut conn, &token, EmailTokenKind::ResetPassword)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let consumed = consume_email_token(&mut conn, &token, EmailTokenKind::VerifyEmail)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(consumed.user_id, user.id);
+        assert_eq!(consumed.email, user.email);
+
+        assert!(
+            consume_email_token(&mut conn, &token, EmailTokenKind::VerifyEmail)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let expired = create_email_token(&app.db, &user, EmailTokenKind::VerifyEmail)
+            .await
+            .unwrap();
+        sqlx::query!("UPDATE email_token SET expires_at = NOW() - INTERVAL '1 minute'")
+            .execute(&app.db)
+            .await
+            .unwrap();
+        assert!(
+            consume_email_token(&mut conn, &expired, EmailTokenKind::VerifyEmail)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a PostgreSQL database (set DATABASE_URL)"]
+    async fn test_verify_email(db: PgPool) {
+        let app = TestApp::spawn(db).await;
+        let user = user(&app, "owner@example.com").await;
+        assert!(user.email_verified_at.is_none());
+
+        let token = create_email_token(&app.db, &user, EmailTokenKind::VerifyEmail)
+            .await
+            .unwrap();
+
+        for (token, status) in [
+            ("unknown", StatusCode::BAD_REQUEST),
+            (token.as_str(), StatusCode::OK),
+            (token.as_str(), StatusCode::BAD_REQUEST),
+        ] {
+            let response = app
+                .client
+                .post(app.url("/verify_email"))
+                .json(&json!({ "token": token }))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), status);
+        }
+
+        let verified = sqlx::query_scalar!(
+            r#"SELECT email_verified_at IS NOT NULL AS "verified!" FROM "user" WHERE id = $1"#,
+            user.id
+        )
+        .fetch_one(&app.db)
+        .await
+        .unwrap();
+        assert!(verified);
+    }
+}
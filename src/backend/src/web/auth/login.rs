@@ -1,19 +1,24 @@
 use axum::response::IntoResponse;
 use axum_serde::Sonic;
 use axum_thiserror::ErrorStatus;
-use chrono_tz::Tz;
 use http::StatusCode;
-use password_auth::generate_hash;
 use schemars::JsonSchema;
 use serde::{Serialize, Serializer};
+use sonic_rs::json;
 use thiserror::Error;
-use tokio::task;
-use tracing::{debug, info};
+use tracing::{debug, error, info};
 use utoipa::ToSchema;
 
 use crate::{
     app::openapi::AUTH_TAG,
-    users::{AuthSession, Credentials, User},
+    audit::{Audit, AuditAction, AuditTarget},
+    middleware::{
+        client_ip::ClientIp,
+        rate_limit::{
+            clear_login_failures, login_lockout, record_login_failure, too_many_requests,
+        },
+    },
+    users::{AuthSession, Credentials, Error},
 };
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -35,150 +40,149 @@ pub enum AuthError {
         #[schemars(with = "String")]
         sqlx::Error,
     ),
-    #[error("User doesn't exist after signup")]
-    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
-    UserNotExistingAfterSignUp,
-    #[error("Failed to re-authenticate after signup")]
-    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
-    FailedToReAuthenticateAfterSignUp,
-    #[error("Wrong password")]
+    #[error("Wrong email or password")]
+    #[status(StatusCode::UNAUTHORIZED)]
+    WrongCredentials,
+    #[error("Registration is disabled")]
+    #[status(StatusCode::FORBIDDEN)]
+    RegistrationDisabled,
+    #[error("Invite is not valid")]
+    #[status(StatusCode::FORBIDDEN)]
+    InvalidInvite,
+    #[error("Email is not valid")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidEmail,
+    #[error("Password is not valid")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidPassword,
+    #[error("Email is already in use")]
+    #[status(StatusCode::CONFLICT)]
+    EmailAlreadyInUse,
+    #[error("Token is not valid or expired")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidToken,
+    #[error("Second factor required")]
+    #[status(StatusCode::UNAUTHORIZED)]
+    SecondFactorRequired,
+    #[error("Wrong second factor")]
+    #[status(StatusCode::UNAUTHORIZED)]
+    WrongSecondFactor,
+    #[error("Passkey is not valid")]
     #[status(StatusCode::UNAUTHORIZED)]
-    WrongPassword,
+    InvalidPasskey,
+    #[error("Single sign-on is not configured")]
+    #[status(StatusCode::NOT_FOUND)]
+    OidcNotConfigured,
+    #[error("Single sign-on failed")]
+    #[status(StatusCode::UNAUTHORIZED)]
+    OidcFailed,
+    #[error("Email domain is not allowed")]
+    #[status(StatusCode::FORBIDDEN)]
+    EmailDomainNotAllowed,
+    #[error("Email is not verified by the identity provider")]
+    #[status(StatusCode::FORBIDDEN)]
+    EmailNotVerified,
+    #[error("Account is disabled")]
+    #[status(StatusCode::FORBIDDEN)]
+    AccountDisabled,
 }
 
 #[utoipa::path(
     post,
     path = "/login",
     summary = "Login",
-    description = "Login or sign up a user if it does not exist",
+    description = "Login a user, accounts are created with the signup endpoint. When the user has 2FA enabled, the request fails with `SecondFactorRequired` until it is repeated with the code from the authenticator app (or a recovery code). After too many failed logins from an address the account is locked for that address for a while, longer at every further failure",
     request_body = Credentials,
     responses(
-        (status = CREATED, description = "User was created", body = LoginResponse),
         (status = OK, description = "User was logged in", body = LoginResponse),
         (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = str, content_type = "text/plain"),
-        (status = UNAUTHORIZED, description = "Wrong password")
+        (status = UNAUTHORIZED, description = "Wrong email, password or second factor, or second factor required", body = AuthError),
+        (status = FORBIDDEN, description = "Account is disabled", body = AuthError),
+        (status = TOO_MANY_REQUESTS, description = "Too many failed logins, the account is locked until the time in the Retry-After header", body = str, content_type = "text/plain")
     ),
     tag = AUTH_TAG
 )]
 pub async fn login(
     mut auth_session: AuthSession,
+    audit: Audit,
+    ClientIp(ip): ClientIp,
     Sonic(req): Sonic<Credentials>,
 ) -> impl IntoResponse {
-    let mut status_code = StatusCode::OK;
+    let redis = auth_session.backend.redis.clone();
+
+    // Like the rate limits, the lockout is not enforced when Redis cannot be reached
+    match login_lockout(&redis, &req.email, ip).await {
+        Ok(Some(retry_after)) => {
+            debug!("Login of user {} is locked", req.email);
+            return too_many_requests(retry_after);
+        }
+        Ok(None) => {}
+        Err(e) => error!("Error checking the lockout of user {}: {}", req.email, e),
+    }
 
     let user = match auth_session.authenticate(req.clone()).await {
-        Ok(Some(user)) => user,
+        Ok(Some(user)) => Ok(user),
         Ok(None) => {
-            let user_exists = get_user_existence(req.email.clone(), auth_session.clone()).await;
+            debug!("Wrong email or password for user {}", req.email);
+            Err(AuthError::WrongCredentials)
+        }
+        Err(axum_login::Error::Backend(Error::SecondFactorRequired)) => {
+            return AuthError::SecondFactorRequired.into_response();
+        }
+        Err(axum_login::Error::Backend(Error::AccountDisabled)) => {
+            debug!("Login of disabled user {}", req.email);
+            return AuthError::AccountDisabled.into_response();
+        }
+        Err(axum_login::Error::Backend(Error::WrongSecondFactor)) => {
+            debug!("Wrong second factor for user {}", req.email);
+            Err(AuthError::WrongSecondFactor)
+        }
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
 
-            if user_exists {
-                debug!("Password is wrong for user {}", req.email);
-                return AuthError::WrongPassword.into_response();
-            } else {
-                debug!(
-                    "User does not exist, creating a new user with email {}",
-                    req.email
+    let user = match user {
+        Ok(user) => user,
+        Err(error) => {
+            if let Err(e) = record_login_failure(&redis, &req.email, ip).await {
+                error!(
+                    "Error recording the failed login of user {}: {}",
+                    req.email, e
                 );
-                match sign_up(req.clone(), auth_session.clone()).await {
-                    Ok(user) => {
-                        status_code = StatusCode::CREATED;
-                        user
-                    }
-                    Err(e) => return e.into_response(),
-                }
             }
+
+            return error.into_response();
         }
-        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     };
 
+    if let Err(e) = clear_login_failures(&redis, &req.email, ip).await {
+        error!(
+            "Error clearing the failed logins of user {}: {}",
+            req.email, e
+        );
+    }
+
     if auth_session.login(&user).await.is_err() {
         return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     }
 
     info!("Successfully logged in as {}", user.email);
 
-    match status_code {
-        StatusCode::CREATED => (
-            StatusCode::CREATED,
-            Sonic(LoginResponse {
-                status: "User was created".to_string(),
-            }),
-        )
-            .into_response(),
-        StatusCode::OK => Sonic(LoginResponse {
-            status: "User was logged in".to_string(),
-        })
-        .into_response(),
-        _ => unreachable!(),
-    }
+    audit
+        .event(AuditAction::Login, AuditTarget::User, user.id)
+        .actor(user.id)
+        .after(json!({ "method": "password" }))
+        .record()
+        .await;
+
+    Sonic(LoginResponse {
+        status: "User was logged in".to_string(),
+    })
+    .into_response()
 }
 
-pub async fn re_auth_after_sign_up(
-    auth_session: AuthSession,
-    credentials: Credentials,
-) -> Result<(), AuthError> {
-    match auth_session.authenticate(credentials.clone()).await {
-        Ok(Some(_)) => Ok(()),
-        Ok(None) => {
-            debug!("User does not exist after signup");
-            Err(AuthError::UserNotExistingAfterSignUp)
-        }
-        Err(_) => Err(AuthError::FailedToReAuthenticateAfterSignUp),
-    }
-}
-
-pub async fn get_user_existence(email: String, auth_session: AuthSession) -> bool {
-    let user = sqlx::query!(
-        r#"
-        SELECT email
-        FROM "user"
-        WHERE email = $1;
-        "#,
-        email
-    )
-    .fetch_optional(&auth_session.backend.db)
-    .await
-    .expect("Failed to check if the user exists");
-
-    user.is_some()
-}
-
-const DEFAULT_TIMEZONE: Tz = Tz::UTC;
-const DEFAULT_LANGUAGE: &str = "en";
-
 fn serialize_sqlx_error<S>(error: &sqlx::Error, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
     serializer.serialize_str(&error.to_string())
 }
-
-pub async fn sign_up(
-    credentials: Credentials,
-    auth_session: AuthSession,
-) -> Result<User, AuthError> {
-    let password = credentials.password.clone();
-
-    let encrypted_password = task::spawn_blocking(move || generate_hash(password.as_bytes()))
-        .await
-        .map_err(|_| AuthError::FailedToGenerateHash)?;
-
-    let user = sqlx::query_as!(
-        User,
-        r#"
-        INSERT INTO "user" (email, password, timezone, language)
-        VALUES ($1, $2, $3, $4)
-        RETURNING id, email, password, timezone, language;
-        "#,
-        credentials.email,
-        encrypted_password,
-        DEFAULT_TIMEZONE.to_string(),
-        DEFAULT_LANGUAGE
-    )
-    .fetch_one(&auth_session.backend.db)
-    .await?;
-
-    re_auth_after_sign_up(auth_session, credentials).await?;
-
-    Ok(user)
-}
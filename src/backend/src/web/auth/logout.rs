@@ -1,7 +1,12 @@
 use axum::response::IntoResponse;
 use http::StatusCode;
 
-use crate::{app::openapi::AUTH_TAG, users::AuthSession};
+use crate::{
+    app::openapi::AUTH_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    middleware::track_session::forget_session,
+    users::AuthSession,
+};
 
 #[utoipa::path(
     get,
@@ -17,10 +22,22 @@ use crate::{app::openapi::AUTH_TAG, users::AuthSession};
     ),
     tag = AUTH_TAG
 )]
-pub async fn logout(mut auth_session: AuthSession) -> impl IntoResponse {
-    if auth_session.logout().await.is_err() {
+pub async fn logout(mut auth_session: AuthSession, audit: Audit) -> impl IntoResponse {
+    if forget_session(&auth_session).await.is_err() {
         return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     }
 
+    let user = match auth_session.logout().await {
+        Ok(user) => user,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    if let Some(user) = user {
+        audit
+            .event(AuditAction::Logout, AuditTarget::User, user.id)
+            .record()
+            .await;
+    }
+
     StatusCode::OK.into_response()
 }
@@ -1,10 +1,38 @@
-mod login;
+mod confirm_email_change;
+pub mod email_token;
+pub mod login;
 mod logout;
+pub mod oidc;
+pub mod passkey;
+mod request_password_reset;
+mod reset_password;
+pub mod signup;
+pub mod totp;
+mod verify_email;
 
+use axum::middleware;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
+use crate::middleware::rate_limit::{AUTH_RATE_LIMIT, rate_limit};
+
 pub fn router() -> OpenApiRouter {
     OpenApiRouter::new()
         .routes(routes![login::login])
+        .routes(routes![signup::signup])
+        .routes(routes![verify_email::verify_email])
+        .routes(routes![confirm_email_change::confirm_email_change])
+        .routes(routes![request_password_reset::request_password_reset])
+        .routes(routes![reset_password::reset_password])
+        .routes(routes![passkey::login::passkey_login_start])
+        .routes(routes![passkey::login::passkey_login_finish])
+        // The route layer only applies to the routes above
+        .route_layer(middleware::from_fn_with_state(
+            AUTH_RATE_LIMIT.clone(),
+            rate_limit,
+        ))
         .routes(routes![logout::logout])
+        .routes(routes![passkey::register::passkey_register_start])
+        .routes(routes![passkey::register::passkey_register_finish])
+        .routes(routes![oidc::login::oidc_login])
+        .routes(routes![oidc::callback::oidc_callback])
 }
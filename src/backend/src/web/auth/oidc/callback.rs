@@ -0,0 +1,263 @@
+use axum::{
+    extract::Query,
+    response::{IntoResponse, Redirect},
+};
+use http::StatusCode;
+use serde::Deserialize;
+use sonic_rs::json;
+use tracing::{debug, error, info};
+use utoipa::IntoParams;
+
+use crate::{
+    REGISTRATION_MODE, SITE_URL,
+    app::openapi::AUTH_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+    web::auth::{
+        login::AuthError,
+        oidc::{OidcState, login::OIDC_STATE_KEY, provision_user},
+    },
+};
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct OidcCallbackQuery {
+    /// The authorization code
+    code: Option<String>,
+    /// The state the login was started with
+    state: Option<String>,
+    /// Set by the provider when the login failed
+    error: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/oidc_callback",
+    summary = "Single Sign-On Callback",
+    description = "The endpoint the identity provider redirects to after the login. The user is created on the first login and then redirected to the site. Users logged in by the provider are not asked for 2FA",
+    params(OidcCallbackQuery),
+    responses(
+        (status = SEE_OTHER, description = "User was logged in, redirect to the site"),
+        (status = BAD_REQUEST, description = "Identity provider did not return an email"),
+        (status = UNAUTHORIZED, description = "Single sign-on failed", body = AuthError),
+        (status = FORBIDDEN, description = "Email domain is not allowed, email is not verified, registration is disabled or the account is disabled", body = AuthError),
+        (status = NOT_FOUND, description = "Single sign-on is not configured", body = AuthError),
+        (status = CONFLICT, description = "Email is already in use by another account", body = AuthError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    tag = AUTH_TAG
+)]
+pub async fn oidc_callback(
+    mut auth_session: AuthSession,
+    audit: Audit,
+    Query(query): Query<OidcCallbackQuery>,
+) -> impl IntoResponse {
+    let Some(oidc) = auth_session.backend.oidc.clone() else {
+        return AuthError::OidcNotConfigured.into_response();
+    };
+
+    // Each login can only be completed once
+    let state = match auth_session
+        .session
+        .remove::<OidcState>(OIDC_STATE_KEY)
+        .await
+    {
+        Ok(state) => state,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    if let Some(error) = query.error {
+        debug!("Identity provider returned an error: {}", error);
+        return AuthError::OidcFailed.into_response();
+    }
+
+    let (Some(state), Some(code)) = (
+        state.filter(|state| query.state.as_deref() == Some(state.state.as_str())),
+        query.code,
+    ) else {
+        debug!("Single sign-on callback does not match the session");
+        return AuthError::OidcFailed.into_response();
+    };
+
+    let claims = match oidc.discover().await {
+        Ok(metadata) => match oidc.exchange_code(&metadata, &code, &state).await {
+            Ok(id_token) => oidc.verify_id_token(&metadata, &id_token, &state).await,
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e),
+    };
+    let claims = match claims {
+        Ok(claims) => claims,
+        Err(e) => {
+            error!("Error completing the single sign-on: {}", e);
+            return AuthError::OidcFailed.into_response();
+        }
+    };
+
+    let Some(email) = claims.email.as_deref().map(str::trim) else {
+        return AuthError::InvalidEmail.into_response();
+    };
+
+    if oidc.requires_verified_email() && claims.email_verified != Some(true) {
+        debug!("Email {} is not verified by the identity provider", email);
+        return AuthError::EmailNotVerified.into_response();
+    }
+
+    if !oidc.is_email_allowed(email) {
+        debug!("Email domain of {} is not allowed", email);
+        return AuthError::EmailDomainNotAllowed.into_response();
+    }
+
+    let user =
+        match provision_user(&auth_session.backend.db, *REGISTRATION_MODE, &claims, email).await {
+            Ok(user) => user,
+            Err(e) => return e.into_response(),
+        };
+
+    if user.disabled_at.is_some() {
+        debug!("Single sign-on of disabled user {}", user.email);
+        return AuthError::AccountDisabled.into_response();
+    }
+
+    if auth_session.login(&user).await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    info!(
+        "Successfully logged in with single sign-on as {}",
+        user.email
+    );
+
+    audit
+        .event(AuditAction::Login, AuditTarget::User, user.id)
+        .actor(user.id)
+        .after(json!({ "method": "oidc" }))
+        .record()
+        .await;
+
+    Redirect::to(SITE_URL.as_str()).into_response()
+}
+
+#[cfg(test)]
+mod test {
+    use http::header;
+    use reqwest::{Client, redirect::Policy};
+    use sqlx::PgPool;
+
+    use crate::{
+        app::test_app::TestApp,
+        web::auth::oidc::mock_idp::{MockIdp, MockLogin},
+    };
+
+    use super::*;
+
+    fn login(sub: &str, email: &str, email_verified: bool) -> MockLogin {
+        MockLogin {
+            sub: sub.to_string(),
+            email: email.to_string(),
+            email_verified,
+        }
+    }
+
+    /// Goes through the single sign-on like a browser, returning the response
+    /// of the callback
+    async fn sign_on(app: &TestApp, idp: &MockIdp, login: MockLogin) -> reqwest::Response {
+        let client = Client::builder().redirect(Policy::none()).build().unwrap();
+
+        let response = client.get(app.url("/oidc_login")).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let cookie = response.headers()[header::SET_COOKIE]
+            .to_str()
+            .unwrap()
+            .split(';')
+            .next()
+            .unwrap()
+            .to_string();
+        let location = response.headers()[header::LOCATION].to_str().unwrap();
+        assert!(location.starts_with(&idp.issuer_url));
+
+        let query = idp.authorize(location, login);
+
+        client
+            .get(app.url(&format!("/oidc_callback?{query}")))
+            .header(header::COOKIE, cookie)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a PostgreSQL database (set DATABASE_URL)"]
+    async fn test_oidc_provisioning(db: PgPool) {
+        let idp = MockIdp::spawn().await;
+        let app = TestApp::spawn_with_oidc(db, Some(idp.client(&["example.com"]))).await;
+
+        let response = sign_on(&app, &idp, login("alice", "alice@example.com", true)).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(response.headers()[header::LOCATION], SITE_URL.as_str());
+
+        let user = sqlx::query!(
+            r#"
+            SELECT id, password, email_verified_at FROM "user" WHERE oidc_subject = 'alice'
+            "#
+        )
+        .fetch_one(&app.db)
+        .await
+        .unwrap();
+        assert!(user.password.is_none());
+        assert!(user.email_verified_at.is_some());
+
+        // The user is found by the subject, even if the email changed
+        let response = sign_on(&app, &idp, login("alice", "alice.smith@example.com", true)).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let users = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM "user""#)
+            .fetch_one(&app.db)
+            .await
+            .unwrap();
+        assert_eq!(users, 1);
+
+        let response = sign_on(&app, &idp, login("mallory", "mallory@evil.com", true)).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // The allowed domains are only trusted for the verified emails
+        let response = sign_on(&app, &idp, login("eve", "eve@example.com", false)).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // An existing account is only linked if the provider verified the email
+        let owner_id = app.create_user("owner@example.com").await;
+        let response = sign_on(&app, &idp, login("owner", "owner@example.com", false)).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = sign_on(&app, &idp, login("owner", "owner@example.com", true)).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let subject =
+            sqlx::query_scalar!(r#"SELECT oidc_subject FROM "user" WHERE id = $1"#, owner_id)
+                .fetch_one(&app.db)
+                .await
+                .unwrap();
+        assert_eq!(subject.as_deref(), Some("owner"));
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a PostgreSQL database (set DATABASE_URL)"]
+    async fn test_oidc_callback_needs_session(db: PgPool) {
+        let idp = MockIdp::spawn().await;
+        let app = TestApp::spawn_with_oidc(db, Some(idp.client(&[]))).await;
+
+        let response = app
+            .client
+            .get(app.url("/oidc_callback?code=forged&state=forged"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let app_without_oidc = TestApp::spawn(app.db.clone()).await;
+        let response = app_without_oidc
+            .client
+            .get(app_without_oidc.url("/oidc_login"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
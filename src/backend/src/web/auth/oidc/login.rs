@@ -0,0 +1,55 @@
+use axum::response::{IntoResponse, Redirect};
+use http::StatusCode;
+use tracing::error;
+
+use crate::{
+    app::openapi::AUTH_TAG,
+    users::AuthSession,
+    web::auth::{login::AuthError, oidc::OidcState},
+};
+
+/// The session key of the login in progress
+pub const OIDC_STATE_KEY: &str = "oidc_state";
+
+#[utoipa::path(
+    get,
+    path = "/oidc_login",
+    summary = "Single Sign-On",
+    description = "Start logging in with the identity provider of the instance, redirecting to it. The provider redirects back to the callback endpoint",
+    responses(
+        (status = SEE_OTHER, description = "Redirect to the identity provider"),
+        (status = NOT_FOUND, description = "Single sign-on is not configured", body = AuthError),
+        (status = BAD_GATEWAY, description = "Identity provider cannot be reached"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    tag = AUTH_TAG
+)]
+pub async fn oidc_login(auth_session: AuthSession) -> impl IntoResponse {
+    let Some(oidc) = auth_session.backend.oidc.as_deref() else {
+        return AuthError::OidcNotConfigured.into_response();
+    };
+
+    let state = OidcState::generate();
+    let authorization_url = match oidc
+        .discover()
+        .await
+        .and_then(|metadata| oidc.authorization_url(&metadata, &state))
+    {
+        Ok(authorization_url) => authorization_url,
+        Err(e) => {
+            error!("Error starting the single sign-on: {}", e);
+            return StatusCode::BAD_GATEWAY.into_response();
+        }
+    };
+
+    if auth_session
+        .session
+        .insert(OIDC_STATE_KEY, state)
+        .await
+        .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    Redirect::to(&authorization_url).into_response()
+}
@@ -0,0 +1,672 @@
+pub mod callback;
+pub mod login;
+
+use std::str::FromStr;
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use color_eyre::eyre::{Context, eyre};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, Validation,
+    jwk::{Jwk, JwkSet},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use thiserror::Error;
+use tracing::debug;
+use url::Url;
+
+use crate::{
+    users::User,
+    web::{
+        auth::{
+            login::AuthError,
+            signup::{DEFAULT_LANGUAGE, DEFAULT_TIMEZONE, RegistrationMode},
+        },
+        utils::token::generate_url_safe_token,
+    },
+};
+
+#[derive(Debug, Error)]
+pub enum OidcError {
+    #[error("Request to the identity provider failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Identity provider metadata is not valid: {0}")]
+    InvalidMetadata(String),
+    #[error("Identity provider rejected the code: {0}")]
+    TokenExchange(String),
+    #[error("ID token is not valid: {0}")]
+    InvalidIdToken(#[from] jsonwebtoken::errors::Error),
+    #[error("ID token is signed with an unknown key")]
+    UnknownKey,
+    #[error("ID token is signed with an algorithm that is not accepted")]
+    UnsupportedAlgorithm,
+    #[error("ID token was not issued for this login")]
+    NonceMismatch,
+}
+
+/// The algorithms accepted for the signature of the ID tokens
+const ID_TOKEN_ALGORITHMS: [Algorithm; 2] = [Algorithm::RS256, Algorithm::ES256];
+
+/// The identity provider used for single sign-on, configured with the
+/// `OIDC_*` environment variables
+#[derive(Clone)]
+pub struct OidcClient {
+    pub issuer_url: String,
+    pub client_id: String,
+    /// Missing for public clients, which rely on PKCE only
+    pub client_secret: Option<String>,
+    /// The URL of the callback endpoint, as registered with the provider
+    pub redirect_url: String,
+    /// The email domains allowed to log in, any domain if empty
+    pub allowed_email_domains: Vec<String>,
+    http_client: reqwest::Client,
+}
+
+// Here we've implemented `Debug` manually to avoid accidentally logging the
+// client secret.
+impl std::fmt::Debug for OidcClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OidcClient")
+            .field("issuer_url", &self.issuer_url)
+            .field("client_id", &self.client_id)
+            .field(
+                "client_secret",
+                &self.client_secret.as_ref().map(|_| "[redacted]"),
+            )
+            .field("redirect_url", &self.redirect_url)
+            .field("allowed_email_domains", &self.allowed_email_domains)
+            .finish()
+    }
+}
+
+impl OidcClient {
+    pub fn new(
+        issuer_url: String,
+        client_id: String,
+        client_secret: Option<String>,
+        redirect_url: String,
+        allowed_email_domains: Vec<String>,
+    ) -> Self {
+        Self {
+            issuer_url: issuer_url.trim_end_matches('/').to_string(),
+            client_id,
+            client_secret,
+            redirect_url,
+            allowed_email_domains: allowed_email_domains
+                .into_iter()
+                .map(|domain| domain.trim().trim_start_matches('@').to_lowercase())
+                .filter(|domain| !domain.is_empty())
+                .collect(),
+            http_client: reqwest::Client::new(),
+        }
+    }
+
+    /// Reads the configuration from the environment, single sign-on is disabled
+    /// if `OIDC_ISSUER_URL` is not set
+    pub fn from_env() -> color_eyre::Result<Option<Self>> {
+        let Ok(issuer_url) = std::env::var("OIDC_ISSUER_URL") else {
+            return Ok(None);
+        };
+
+        let client_id = std::env::var("OIDC_CLIENT_ID")
+            .wrap_err("OIDC_CLIENT_ID must be set to use single sign-on")?;
+        let redirect_url = std::env::var("OIDC_REDIRECT_URL")
+            .wrap_err("OIDC_REDIRECT_URL must be set to use single sign-on")?;
+        Url::parse(&redirect_url).map_err(|e| eyre!("Invalid OIDC_REDIRECT_URL: {e}"))?;
+
+        let allowed_email_domains = std::env::var("OIDC_ALLOWED_EMAIL_DOMAINS")
+            .map(|domains| domains.split(',').map(str::to_string).collect())
+            .unwrap_or_default();
+
+        Ok(Some(Self::new(
+            issuer_url,
+            client_id,
+            std::env::var("OIDC_CLIENT_SECRET").ok(),
+            redirect_url,
+            allowed_email_domains,
+        )))
+    }
+
+    /// Fetches the endpoints of the provider from its discovery document
+    pub async fn discover(&self) -> Result<ProviderMetadata, OidcError> {
+        let metadata: ProviderMetadata = self
+            .http_client
+            .get(format!(
+                "{}/.well-known/openid-configuration",
+                self.issuer_url
+            ))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        if metadata.issuer.trim_end_matches('/') != self.issuer_url {
+            return Err(OidcError::InvalidMetadata(format!(
+                "issuer {} does not match {}",
+                metadata.issuer, self.issuer_url
+            )));
+        }
+
+        Ok(metadata)
+    }
+
+    /// The URL of the provider the user is sent to, to log in
+    pub fn authorization_url(
+        &self,
+        metadata: &ProviderMetadata,
+        state: &OidcState,
+    ) -> Result<String, OidcError> {
+        let mut url = Url::parse(&metadata.authorization_endpoint)
+            .map_err(|e| OidcError::InvalidMetadata(e.to_string()))?;
+
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.client_id)
+            .append_pair("redirect_uri", &self.redirect_url)
+            .append_pair("scope", "openid email")
+            .append_pair("state", &state.state)
+            .append_pair("nonce", &state.nonce)
+            .append_pair("code_challenge", &state.code_challenge())
+            .append_pair("code_challenge_method", "S256");
+
+        Ok(url.into())
+    }
+
+    /// Exchanges the authorization code for the ID token
+    pub async fn exchange_code(
+        &self,
+        metadata: &ProviderMetadata,
+        code: &str,
+        state: &OidcState,
+    ) -> Result<String, OidcError> {
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &self.redirect_url),
+            ("code_verifier", &state.code_verifier),
+        ];
+
+        let mut request = self.http_client.post(&metadata.token_endpoint);
+        match &self.client_secret {
+            Some(client_secret) => {
+                request = request.basic_auth(&self.client_id, Some(client_secret));
+            }
+            None => form.push(("client_id", &self.client_id)),
+        }
+
+        let response = request.form(&form).send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(OidcError::TokenExchange(format!("{status} {body}")));
+        }
+
+        let token: TokenResponse = response.json().await?;
+
+        Ok(token.id_token)
+    }
+
+    /// Verifies the signature (with the keys published by the provider), the
+    /// issuer, the audience, the expiry and the nonce of the ID token
+    pub async fn verify_id_token(
+        &self,
+        metadata: &ProviderMetadata,
+        id_token: &str,
+        state: &OidcState,
+    ) -> Result<IdTokenClaims, OidcError> {
+        let header = jsonwebtoken::decode_header(id_token)?;
+
+        let jwks: JwkSet = self
+            .http_client
+            .get(&metadata.jwks_uri)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let jwk = match &header.kid {
+            Some(kid) => jwks.find(kid),
+            None => jwks.keys.first(),
+        }
+        .ok_or(OidcError::UnknownKey)?;
+
+        let mut validation = Validation::new(id_token_algorithm(metadata, jwk, header.alg)?);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&self.client_id]);
+
+        let claims = jsonwebtoken::decode::<IdTokenClaims>(
+            id_token,
+            &DecodingKey::from_jwk(jwk)?,
+            &validation,
+        )?
+        .claims;
+
+        if claims.nonce.as_deref() != Some(state.nonce.as_str()) {
+            return Err(OidcError::NonceMismatch);
+        }
+
+        Ok(claims)
+    }
+
+    /// Whether the provider must have verified the email of the users, which
+    /// is the case when only some domains are allowed (an unverified address
+    /// could be in any of them)
+    pub fn requires_verified_email(&self) -> bool {
+        !self.allowed_email_domains.is_empty()
+    }
+
+    pub fn is_email_allowed(&self, email: &str) -> bool {
+        if self.allowed_email_domains.is_empty() {
+            return true;
+        }
+
+        email
+            .rsplit_once('@')
+            .is_some_and(|(_, domain)| self.allowed_email_domains.contains(&domain.to_lowercase()))
+    }
+}
+
+/// The algorithm the ID token is verified with: the one of its header, as long
+/// as it is accepted here, supported by the provider and the one of the key
+/// (if the key tells), so that the token cannot pick a weaker one
+fn id_token_algorithm(
+    metadata: &ProviderMetadata,
+    jwk: &Jwk,
+    header_algorithm: Algorithm,
+) -> Result<Algorithm, OidcError> {
+    let key_algorithm = jwk
+        .common
+        .key_algorithm
+        .map(|algorithm| Algorithm::from_str(&algorithm.to_string()))
+        .transpose()
+        .map_err(|_| OidcError::UnsupportedAlgorithm)?;
+
+    let supported_by_provider = metadata.id_token_signing_alg_values_supported.is_empty()
+        || metadata
+            .id_token_signing_alg_values_supported
+            .iter()
+            .filter_map(|algorithm| Algorithm::from_str(algorithm).ok())
+            .any(|algorithm| algorithm == header_algorithm);
+
+    if !ID_TOKEN_ALGORITHMS.contains(&header_algorithm)
+        || !supported_by_provider
+        || key_algorithm.is_some_and(|algorithm| algorithm != header_algorithm)
+    {
+        return Err(OidcError::UnsupportedAlgorithm);
+    }
+
+    Ok(header_algorithm)
+}
+
+/// The parts of the discovery document that are used, see
+/// <https://openid.net/specs/openid-connect-discovery-1_0.html#ProviderMetadata>
+#[derive(Debug, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+    /// The algorithms the provider signs the ID tokens with
+    #[serde(default)]
+    pub id_token_signing_alg_values_supported: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IdTokenClaims {
+    /// The identifier of the user at the provider, which never changes
+    pub sub: String,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+    pub nonce: Option<String>,
+}
+
+/// The state of a login, kept in the session while the user is at the provider
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OidcState {
+    /// Binds the callback to the session that started the login
+    pub state: String,
+    /// Binds the ID token to the login
+    pub nonce: String,
+    /// The PKCE verifier, its hash is sent to the provider as the challenge
+    pub code_verifier: String,
+}
+
+impl OidcState {
+    pub fn generate() -> Self {
+        Self {
+            state: generate_url_safe_token(),
+            nonce: generate_url_safe_token(),
+            code_verifier: generate_url_safe_token(),
+        }
+    }
+
+    pub fn code_challenge(&self) -> String {
+        URL_SAFE_NO_PAD.encode(Sha256::digest(self.code_verifier.as_bytes()))
+    }
+}
+
+/// Finds the user the provider logged in, creating it on the first login.
+/// An existing account with the same email is linked to the provider only if
+/// the provider verified the address
+pub async fn provision_user(
+    db: &PgPool,
+    mode: RegistrationMode,
+    claims: &IdTokenClaims,
+    email: &str,
+) -> Result<User, AuthError> {
+    if let Some(user) = sqlx::query_as!(
+        User,
+        r#"
+        SELECT * FROM "user" WHERE oidc_subject = $1
+        "#,
+        claims.sub
+    )
+    .fetch_optional(db)
+    .await?
+    {
+        return Ok(user);
+    }
+
+    let email_verified = claims.email_verified == Some(true);
+
+    if email_verified
+        && let Some(user) = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE "user"
+            SET oidc_subject      = $2,
+                email_verified_at = COALESCE(email_verified_at, NOW())
+            WHERE email = $1
+              AND oidc_subject IS NULL
+            RETURNING *
+            "#,
+            email,
+            claims.sub
+        )
+        .fetch_optional(db)
+        .await?
+    {
+        debug!("Linked user {} to the identity provider", email);
+        return Ok(user);
+    }
+
+    // The provider decides who can log in, so invites are not needed
+    if mode == RegistrationMode::Disabled {
+        return Err(AuthError::RegistrationDisabled);
+    }
+
+    // The account would be created for an address nobody proved to own
+    if !email_verified {
+        return Err(AuthError::EmailNotVerified);
+    }
+
+    sqlx::query_as!(
+        User,
+        r#"
+        INSERT INTO "user" (email, timezone, language, oidc_subject, email_verified_at)
+        VALUES ($1, $2, $3, $4, NOW())
+        ON CONFLICT (email) DO NOTHING
+        RETURNING *
+        "#,
+        email,
+        DEFAULT_TIMEZONE.to_string(),
+        DEFAULT_LANGUAGE,
+        claims.sub
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or(AuthError::EmailAlreadyInUse)
+}
+
+/// An identity provider served on a local port, to run the single sign-on in
+/// the tests
+#[cfg(test)]
+pub mod mock_idp {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use axum::{
+        Form, Router,
+        extract::State,
+        response::{IntoResponse, Response},
+        routing::{get, post},
+    };
+    use axum_serde::Sonic;
+    use chrono::Utc;
+    use http::{HeaderMap, StatusCode, header};
+    use jsonwebtoken::{Algorithm, EncodingKey, Header};
+    use p256::{ecdsa::SigningKey, pkcs8::EncodePrivateKey};
+    use rand::RngCore;
+    use sonic_rs::json;
+
+    use super::*;
+
+    pub const CLIENT_ID: &str = "monitor";
+    const CLIENT_SECRET: &str = "secret";
+
+    /// The user that logs in at the provider
+    #[derive(Debug, Clone)]
+    pub struct MockLogin {
+        pub sub: String,
+        pub email: String,
+        pub email_verified: bool,
+    }
+
+    struct Grant {
+        login: MockLogin,
+        code_challenge: String,
+        nonce: String,
+    }
+
+    #[derive(Clone)]
+    pub struct MockIdp {
+        pub issuer_url: String,
+        signing_key: Arc<SigningKey>,
+        grants: Arc<Mutex<HashMap<String, Grant>>>,
+    }
+
+    impl MockIdp {
+        pub async fn spawn() -> Self {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let mut secret = [0u8; 32];
+            rand::rng().fill_bytes(&mut secret);
+
+            let idp = Self {
+                issuer_url: format!("http://{}", listener.local_addr().unwrap()),
+                signing_key: Arc::new(SigningKey::from_slice(&secret).unwrap()),
+                grants: Arc::default(),
+            };
+
+            let router = Router::new()
+                .route("/.well-known/openid-configuration", get(discovery))
+                .route("/jwks", get(jwks))
+                .route("/token", post(token))
+                .with_state(idp.clone());
+            tokio::spawn(async move { axum::serve(listener, router).await });
+
+            idp
+        }
+
+        pub fn client(&self, allowed_email_domains: &[&str]) -> OidcClient {
+            OidcClient::new(
+                self.issuer_url.clone(),
+                CLIENT_ID.to_string(),
+                Some(CLIENT_SECRET.to_string()),
+                "http://localhost:3000/oidc_callback".to_string(),
+                allowed_email_domains
+                    .iter()
+                    .map(|d| d.to_string())
+                    .collect(),
+            )
+        }
+
+        /// Logs the user in at the authorization URL, as the browser would,
+        /// returning the query of the redirect to the callback
+        pub fn authorize(&self, authorization_url: &str, login: MockLogin) -> String {
+            let url = Url::parse(authorization_url).unwrap();
+            let param = |name: &str| {
+                url.query_pairs()
+                    .find(|(key, _)| key == name)
+                    .map(|(_, value)| value.into_owned())
+                    .unwrap()
+            };
+            assert_eq!(param("client_id"), CLIENT_ID);
+            assert_eq!(param("code_challenge_method"), "S256");
+
+            let code = generate_url_safe_token();
+            self.grants.lock().unwrap().insert(
+                code.clone(),
+                Grant {
+                    login,
+                    code_challenge: param("code_challenge"),
+                    nonce: param("nonce"),
+                },
+            );
+
+            format!("code={}&state={}", code, param("state"))
+        }
+    }
+
+    async fn discovery(State(idp): State<MockIdp>) -> impl IntoResponse {
+        Sonic(json!({
+            "issuer": idp.issuer_url,
+            "authorization_endpoint": format!("{}/authorize", idp.issuer_url),
+            "token_endpoint": format!("{}/token", idp.issuer_url),
+            "jwks_uri": format!("{}/jwks", idp.issuer_url),
+            "id_token_signing_alg_values_supported": ["ES256"],
+        }))
+    }
+
+    async fn jwks(State(idp): State<MockIdp>) -> impl IntoResponse {
+        let point = idp.signing_key.verifying_key().to_encoded_point(false);
+
+        Sonic(json!({
+            "keys": [{
+                "kty": "EC",
+                "crv": "P-256",
+                "x": URL_SAFE_NO_PAD.encode(point.x().unwrap()),
+                "y": URL_SAFE_NO_PAD.encode(point.y().unwrap()),
+                "kid": "test",
+                "alg": "ES256",
+                "use": "sig",
+            }]
+        }))
+    }
+
+    async fn token(
+        State(idp): State<MockIdp>,
+        headers: HeaderMap,
+        Form(form): Form<HashMap<String, String>>,
+    ) -> Response {
+        let basic = format!(
+            "Basic {}",
+            base64::engine::general_purpose::STANDARD
+                .encode(format!("{CLIENT_ID}:{CLIENT_SECRET}"))
+        );
+        if headers
+            .get(header::AUTHORIZATION)
+            .and_then(|h| h.to_str().ok())
+            != Some(&basic)
+        {
+            return (StatusCode::UNAUTHORIZED, "invalid_client").into_response();
+        }
+
+        let grant = form
+            .get("code")
+            .and_then(|code| idp.grants.lock().unwrap().remove(code));
+        let verifier_hash = form
+            .get("code_verifier")
+            .map(|verifier| URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes())));
+
+        let Some(grant) =
+            grant.filter(|grant| Some(&grant.code_challenge) == verifier_hash.as_ref())
+        else {
+            return (StatusCode::BAD_REQUEST, "invalid_grant").into_response();
+        };
+
+        let now = Utc::now().timestamp();
+        let claims = json!({
+            "iss": idp.issuer_url,
+            "aud": CLIENT_ID,
+            "sub": grant.login.sub,
+            "email": grant.login.email,
+            "email_verified": grant.login.email_verified,
+            "nonce": grant.nonce,
+            "iat": now,
+            "exp": now + 300,
+        });
+
+        let mut header = Header::new(Algorithm::ES256);
+        header.kid = Some("test".to_string());
+        let key = EncodingKey::from_ec_der(idp.signing_key.to_pkcs8_der().unwrap().as_bytes());
+        let id_token = jsonwebtoken::encode(&header, &claims, &key).unwrap();
+
+        Sonic(json!({
+            "access_token": "access",
+            "token_type": "Bearer",
+            "id_token": id_token,
+        }))
+        .into_response()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn metadata(algorithms: &[&str]) -> ProviderMetadata {
+        ProviderMetadata {
+            issuer: "https://idp.example.org".to_string(),
+            authorization_endpoint: "https://idp.example.org/authorize".to_string(),
+            token_endpoint: "https://idp.example.org/token".to_string(),
+            jwks_uri: "https://idp.example.org/jwks".to_string(),
+            id_token_signing_alg_values_supported: algorithms
+                .iter()
+                .map(|algorithm| algorithm.to_string())
+                .collect(),
+        }
+    }
+
+    fn ec_key(algorithm: Option<&str>) -> Jwk {
+        let mut jwk = sonic_rs::json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "x": "f83OJ3D2xF1Bg8vub9tLe1gHMzV76e8Tus9uPHvRVEU",
+            "y": "x_FEzRu9m36HLN_tue659LNpXW6pCyStikYjKIWI5a0",
+        });
+        if let Some(algorithm) = algorithm {
+            jwk["alg"] = algorithm.into();
+        }
+
+        sonic_rs::from_value(&jwk).unwrap()
+    }
+
+    #[test]
+    fn test_id_token_algorithm() {
+        let any = metadata(&[]);
+
+        assert!(matches!(
+            id_token_algorithm(&any, &ec_key(Some("ES256")), Algorithm::ES256),
+            Ok(Algorithm::ES256)
+        ));
+        assert!(id_token_algorithm(&any, &ec_key(None), Algorithm::ES256).is_ok());
+
+        // The header cannot pick an algorithm that is not accepted, or that is not
+        // the one of the key or of the provider
+        assert!(id_token_algorithm(&any, &ec_key(None), Algorithm::HS256).is_err());
+        assert!(id_token_algorithm(&any, &ec_key(Some("ES256")), Algorithm::RS256).is_err());
+        assert!(
+            id_token_algorithm(&metadata(&["RS256"]), &ec_key(None), Algorithm::ES256).is_err()
+        );
+    }
+}
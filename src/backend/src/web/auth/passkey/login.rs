@@ -0,0 +1,352 @@
+use axum::response::IntoResponse;
+use axum_login::AuthnBackend;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use sonic_rs::json;
+use sqlx::types::Json;
+use tracing::{debug, error, info};
+use utoipa::ToSchema;
+use webauthn_rs::prelude::{
+    DiscoverableAuthentication, DiscoverableKey, Passkey, PublicKeyCredential,
+    RequestChallengeResponse,
+};
+
+use crate::{
+    app::openapi::AUTH_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+    web::auth::{
+        login::{AuthError, LoginResponse},
+        passkey::{user_handle, webauthn},
+    },
+};
+
+/// The session key of the authentication ceremony in progress
+const AUTHENTICATION_KEY: &str = "passkey_authentication";
+
+/// The options to pass to `navigator.credentials.get`, as
+/// `{ "publicKey": { ... } }`
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(transparent)]
+#[schema(value_type = Object)]
+pub struct PasskeyLoginOptions(RequestChallengeResponse);
+
+#[utoipa::path(
+    post,
+    path = "/passkey_login_start",
+    summary = "Start Passkey Login",
+    description = "Start logging in with a passkey, returning the options to pass to `navigator.credentials.get`. The user is chosen with the passkey, so no email is needed",
+    responses(
+        (status = OK, description = "Login was started", body = PasskeyLoginOptions),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    tag = AUTH_TAG
+)]
+pub async fn passkey_login_start(auth_session: AuthSession) -> impl IntoResponse {
+    let webauthn = match webauthn() {
+        Ok(webauthn) => webauthn,
+        Err(e) => {
+            error!("Error starting the passkey login: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let (mut options, authentication) = match webauthn.start_discoverable_authentication() {
+        Ok(authentication) => authentication,
+        Err(e) => {
+            error!("Error starting the passkey login: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    // The login is started from a button rather than from the autofill of the
+    // email field, so the browser shows its usual prompt
+    options.mediation = None;
+
+    if auth_session
+        .session
+        .insert(AUTHENTICATION_KEY, authentication)
+        .await
+        .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    Sonic(PasskeyLoginOptions(options)).into_response()
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PasskeyLoginFinishRequest {
+    /// The credential returned by `navigator.credentials.get`, with the binary
+    /// fields base64url encoded
+    #[schema(value_type = Object)]
+    credential: PublicKeyCredential,
+}
+
+#[utoipa::path(
+    post,
+    path = "/passkey_login_finish",
+    summary = "Finish Passkey Login",
+    description = "Log in with the passkey chosen by the user. Passkeys verify the user on the authenticator, so 2FA is not asked for",
+    request_body = PasskeyLoginFinishRequest,
+    responses(
+        (status = OK, description = "User was logged in", body = LoginResponse),
+        (status = UNAUTHORIZED, description = "Passkey is not valid", body = AuthError),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    tag = AUTH_TAG
+)]
+pub async fn passkey_login_finish(
+    mut auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<PasskeyLoginFinishRequest>,
+) -> impl IntoResponse {
+    let webauthn = match webauthn() {
+        Ok(webauthn) => webauthn,
+        Err(e) => {
+            error!("Error finishing the passkey login: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    // Each challenge can only be used once
+    let authentication = match auth_session
+        .session
+        .remove::<DiscoverableAuthentication>(AUTHENTICATION_KEY)
+        .await
+    {
+        Ok(Some(authentication)) => authentication,
+        Ok(None) => {
+            debug!("No passkey login was started in the session");
+            return AuthError::InvalidPasskey.into_response();
+        }
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let Ok((handle, credential_id)) =
+        webauthn.identify_discoverable_authentication(&request.credential)
+    else {
+        return AuthError::InvalidPasskey.into_response();
+    };
+
+    let passkey = match sqlx::query!(
+        r#"
+        SELECT id, user_id, credential AS "credential: Json<Passkey>"
+        FROM passkey
+        WHERE credential_id = $1
+        "#,
+        credential_id
+    )
+    .fetch_optional(&auth_session.backend.db)
+    .await
+    {
+        Ok(Some(passkey)) if user_handle(passkey.user_id) == handle => passkey,
+        Ok(_) => {
+            debug!("Unknown passkey used to log in");
+            return AuthError::InvalidPasskey.into_response();
+        }
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let Json(mut credential) = passkey.credential;
+
+    // The signature counter is checked too, a counter that did not increase
+    // may come from a cloned authenticator
+    let result = match webauthn.finish_discoverable_authentication(
+        &request.credential,
+        authentication,
+        &[DiscoverableKey::from(&credential)],
+    ) {
+        Ok(result) => result,
+        Err(e) => {
+            debug!("Passkey login failed for passkey {}: {}", passkey.id, e);
+            return AuthError::InvalidPasskey.into_response();
+        }
+    };
+    credential.update_credential(&result);
+
+    if sqlx::query!(
+        r#"
+        UPDATE passkey SET credential = $2, last_used_at = NOW() WHERE id = $1
+        "#,
+        passkey.id,
+        Json(&credential) as _
+    )
+    .execute(&auth_session.backend.db)
+    .await
+    .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    let user = match auth_session.backend.get_user(&passkey.user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return AuthError::InvalidPasskey.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    if auth_session.login(&user).await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    info!("Successfully logged in with a passkey as {}", user.email);
+
+    audit
+        .event(AuditAction::Login, AuditTarget::User, user.id)
+        .actor(user.id)
+        .after(json!({ "method": "passkey", "passkey_id": passkey.id }))
+        .record()
+        .await;
+
+    Sonic(LoginResponse {
+        status: "User was logged in".to_string(),
+    })
+    .into_response()
+}
+
+#[cfg(test)]
+mod test {
+    use http::header;
+    use reqwest::Response;
+    use sonic_rs::{JsonContainerTrait, JsonValueTrait, Value, json};
+    use sqlx::PgPool;
+
+    use crate::{
+        app::test_app::TestApp, web::auth::passkey::test_authenticator::TestAuthenticator,
+    };
+
+    use super::*;
+
+    fn session_cookie(response: &Response) -> Option<String> {
+        response
+            .headers()
+            .get(header::SET_COOKIE)
+            .and_then(|cookie| cookie.to_str().ok())
+            .and_then(|cookie| cookie.split(';').next())
+            .map(str::to_string)
+    }
+
+    async fn json(response: Response) -> Value {
+        sonic_rs::from_str(&response.text().await.unwrap()).unwrap()
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a PostgreSQL database (set DATABASE_URL)"]
+    async fn test_passkey_register_and_login(db: PgPool) {
+        let app = TestApp::spawn(db).await;
+        let user_id = app.create_user("owner@example.com").await;
+        let cookie = app.login(user_id).await;
+        let mut authenticator = TestAuthenticator::default();
+
+        let response = app
+            .client
+            .post(app.url("/passkey_register_start"))
+            .header(header::COOKIE, &cookie)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let options = json(response).await;
+        assert_eq!(
+            options["publicKey"]["user"]["name"].as_str(),
+            Some("owner@example.com")
+        );
+        assert_eq!(
+            options["publicKey"]["authenticatorSelection"]["residentKey"].as_str(),
+            Some("required")
+        );
+
+        let response = app
+            .client
+            .post(app.url("/passkey_register_finish"))
+            .header(header::COOKIE, &cookie)
+            .json(&json!({ "name": "Laptop", "credential": authenticator.create(&options) }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        // The passkey is excluded from the next registrations
+        let response = app
+            .client
+            .post(app.url("/passkey_register_start"))
+            .header(header::COOKIE, &cookie)
+            .send()
+            .await
+            .unwrap();
+        let options = json(response).await;
+        assert_eq!(
+            options["publicKey"]["excludeCredentials"]
+                .as_array()
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let response = app
+            .client
+            .post(app.url("/passkey_login_start"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let login_cookie = session_cookie(&response).unwrap();
+        let options = json(response).await;
+        let credential = authenticator.get(&options);
+
+        let response = app
+            .client
+            .post(app.url("/passkey_login_finish"))
+            .header(header::COOKIE, &login_cookie)
+            .json(&json!({ "credential": credential }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let cookie = session_cookie(&response).unwrap();
+
+        let response = app
+            .client
+            .get(app.url("/user/list_passkeys"))
+            .header(header::COOKIE, &cookie)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let passkeys = json(response).await;
+        assert_eq!(passkeys["passkeys"][0]["name"].as_str(), Some("Laptop"));
+        assert!(passkeys["passkeys"][0]["last_used_at"].is_str());
+
+        // The challenge can only be used once
+        let response = app
+            .client
+            .post(app.url("/passkey_login_finish"))
+            .header(header::COOKIE, &login_cookie)
+            .json(&json!({ "credential": credential }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // The assertions signed for another site are rejected
+        let response = app
+            .client
+            .post(app.url("/passkey_login_start"))
+            .send()
+            .await
+            .unwrap();
+        let login_cookie = session_cookie(&response).unwrap();
+        let options = json(response).await;
+        authenticator.origin = "https://phishing.example.com".to_string();
+
+        let response = app
+            .client
+            .post(app.url("/passkey_login_finish"))
+            .header(header::COOKIE, &login_cookie)
+            .json(&json!({ "credential": authenticator.get(&options) }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
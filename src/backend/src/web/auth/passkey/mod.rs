@@ -0,0 +1,183 @@
+pub mod login;
+pub mod register;
+
+use url::Url;
+use uuid::Uuid;
+use webauthn_rs::prelude::{Webauthn, WebauthnBuilder, WebauthnError};
+
+use crate::SITE_URL;
+
+/// The name of the relying party shown by the authenticators
+const RELYING_PARTY_NAME: &str = "Monitor";
+
+/// The relying party the passkeys are bound to, derived from the URL of the
+/// frontend (its domain is the relying party ID)
+pub fn webauthn() -> Result<Webauthn, WebauthnError> {
+    let origin = Url::parse(SITE_URL.as_str()).map_err(|_| WebauthnError::Configuration)?;
+    let id = origin.host_str().ok_or(WebauthnError::Configuration)?;
+
+    WebauthnBuilder::new(id, &origin)?
+        .rp_name(RELYING_PARTY_NAME)
+        .build()
+}
+
+/// The user handle of the passkeys of the user, which is returned by the
+/// authenticator when logging in
+pub fn user_handle(user_id: i32) -> Uuid {
+    Uuid::from_u64_pair(0, user_id as u64)
+}
+
+/// A software authenticator, to run the ceremonies in the tests
+#[cfg(test)]
+pub mod test_authenticator {
+    use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+    use ciborium::Value as Cbor;
+    use p256::ecdsa::{Signature, SigningKey, signature::Signer};
+    use rand::RngCore;
+    use sha2::{Digest, Sha256};
+    use sonic_rs::{JsonValueTrait, Value, json};
+
+    use super::*;
+
+    /// The COSE identifier of ECDSA with SHA-256 on P-256
+    const ES256: i64 = -7;
+
+    const FLAG_USER_PRESENT: u8 = 0x01;
+    const FLAG_USER_VERIFIED: u8 = 0x04;
+    const FLAG_ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+    fn encode(data: &[u8]) -> String {
+        URL_SAFE_NO_PAD.encode(data)
+    }
+
+    pub struct TestAuthenticator {
+        signing_key: SigningKey,
+        pub credential_id: Vec<u8>,
+        /// The user handle stored with the credential when it was created
+        pub user_handle: String,
+        pub sign_count: u32,
+        pub origin: String,
+    }
+
+    impl Default for TestAuthenticator {
+        fn default() -> Self {
+            let mut secret = [0u8; 32];
+            rand::rng().fill_bytes(&mut secret);
+            let mut credential_id = vec![0u8; 16];
+            rand::rng().fill_bytes(&mut credential_id);
+
+            Self {
+                signing_key: SigningKey::from_slice(&secret).unwrap(),
+                credential_id,
+                user_handle: String::new(),
+                sign_count: 0,
+                origin: Url::parse(SITE_URL.as_str())
+                    .unwrap()
+                    .origin()
+                    .ascii_serialization(),
+            }
+        }
+    }
+
+    impl TestAuthenticator {
+        fn authenticator_data(&self, relying_party_id: &str, attested: bool) -> Vec<u8> {
+            let mut flags = FLAG_USER_PRESENT | FLAG_USER_VERIFIED;
+            if attested {
+                flags |= FLAG_ATTESTED_CREDENTIAL_DATA;
+            }
+
+            let mut data = Sha256::digest(relying_party_id.as_bytes()).to_vec();
+            data.push(flags);
+            data.extend(self.sign_count.to_be_bytes());
+
+            if attested {
+                let point = self.signing_key.verifying_key().to_encoded_point(false);
+                let cose_key = Cbor::Map(vec![
+                    (Cbor::Integer(1.into()), Cbor::Integer(2.into())),
+                    (Cbor::Integer(3.into()), Cbor::Integer(ES256.into())),
+                    (Cbor::Integer((-1).into()), Cbor::Integer(1.into())),
+                    (
+                        Cbor::Integer((-2).into()),
+                        Cbor::Bytes(point.x().unwrap().to_vec()),
+                    ),
+                    (
+                        Cbor::Integer((-3).into()),
+                        Cbor::Bytes(point.y().unwrap().to_vec()),
+                    ),
+                ]);
+
+                data.extend([0u8; 16]);
+                data.extend((self.credential_id.len() as u16).to_be_bytes());
+                data.extend(&self.credential_id);
+                ciborium::into_writer(&cose_key, &mut data).unwrap();
+            }
+
+            data
+        }
+
+        fn client_data(&self, kind: &str, options: &Value) -> Vec<u8> {
+            let challenge = options["publicKey"]["challenge"].as_str().unwrap();
+
+            sonic_rs::to_vec(&json!({
+                "type": kind,
+                "challenge": challenge,
+                "origin": self.origin,
+            }))
+            .unwrap()
+        }
+
+        /// The credential returned by `navigator.credentials.create`
+        pub fn create(&mut self, options: &Value) -> Value {
+            let relying_party_id = options["publicKey"]["rp"]["id"].as_str().unwrap();
+            self.user_handle = options["publicKey"]["user"]["id"]
+                .as_str()
+                .unwrap()
+                .to_string();
+
+            let attestation_object = Cbor::Map(vec![
+                (Cbor::Text("fmt".into()), Cbor::Text("none".into())),
+                (Cbor::Text("attStmt".into()), Cbor::Map(vec![])),
+                (
+                    Cbor::Text("authData".into()),
+                    Cbor::Bytes(self.authenticator_data(relying_party_id, true)),
+                ),
+            ]);
+            let mut attestation_object_bytes = vec![];
+            ciborium::into_writer(&attestation_object, &mut attestation_object_bytes).unwrap();
+
+            json!({
+                "id": encode(&self.credential_id),
+                "rawId": encode(&self.credential_id),
+                "type": "public-key",
+                "response": {
+                    "clientDataJSON": encode(&self.client_data("webauthn.create", options)),
+                    "attestationObject": encode(&attestation_object_bytes),
+                }
+            })
+        }
+
+        /// The credential returned by `navigator.credentials.get`
+        pub fn get(&mut self, options: &Value) -> Value {
+            self.sign_count += 1;
+
+            let relying_party_id = options["publicKey"]["rpId"].as_str().unwrap();
+            let authenticator_data = self.authenticator_data(relying_party_id, false);
+            let client_data = self.client_data("webauthn.get", options);
+            let signed_data =
+                [authenticator_data.as_slice(), &Sha256::digest(&client_data)].concat();
+            let signature: Signature = self.signing_key.sign(&signed_data);
+
+            json!({
+                "id": encode(&self.credential_id),
+                "rawId": encode(&self.credential_id),
+                "type": "public-key",
+                "response": {
+                    "clientDataJSON": encode(&client_data),
+                    "authenticatorData": encode(&authenticator_data),
+                    "signature": encode(signature.to_der().as_bytes()),
+                    "userHandle": self.user_handle,
+                }
+            })
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use sonic_rs::json;
+use sqlx::types::Json;
+use tracing::{debug, error, info};
+use utoipa::ToSchema;
+use webauthn_rs::prelude::{
+    CreationChallengeResponse, CredentialID, PasskeyRegistration, RegisterPublicKeyCredential,
+};
+use webauthn_rs_proto::ResidentKeyRequirement;
+
+use crate::{
+    app::openapi::AUTH_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+    web::auth::passkey::{user_handle, webauthn},
+};
+
+/// The session key of the registration ceremony in progress
+const REGISTRATION_KEY: &str = "passkey_registration";
+
+/// The options to pass to `navigator.credentials.create`, as
+/// `{ "publicKey": { ... } }`
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(transparent)]
+#[schema(value_type = Object)]
+pub struct PasskeyRegistrationOptions(CreationChallengeResponse);
+
+/// The state of a registration, kept in the session between its start and
+/// finish
+#[derive(Debug, Serialize, Deserialize)]
+struct RegistrationState {
+    /// The user the passkey is being registered for
+    user_id: i32,
+    registration: PasskeyRegistration,
+}
+
+#[utoipa::path(
+    post,
+    path = "/passkey_register_start",
+    summary = "Start Passkey Registration",
+    description = "Start registering a passkey for the current user, returning the options to pass to `navigator.credentials.create`",
+    responses(
+        (status = OK, description = "Registration was started", body = PasskeyRegistrationOptions),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = AUTH_TAG
+)]
+pub async fn passkey_register_start(auth_session: AuthSession) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let webauthn = match webauthn() {
+        Ok(webauthn) => webauthn,
+        Err(e) => {
+            error!("Error starting the passkey registration: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    // The authenticator refuses to create a second passkey for the same account
+    let exclude_credentials = match sqlx::query_scalar!(
+        r#"
+        SELECT credential_id FROM passkey WHERE user_id = $1
+        "#,
+        user.id
+    )
+    .fetch_all(&auth_session.backend.db)
+    .await
+    {
+        Ok(credential_ids) => credential_ids.into_iter().map(CredentialID::from).collect(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let (mut options, registration) = match webauthn.start_passkey_registration(
+        user_handle(user.id),
+        &user.email,
+        &user.email,
+        Some(exclude_credentials),
+    ) {
+        Ok(registration) => registration,
+        Err(e) => {
+            error!("Error starting the passkey registration: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    // The passkeys are used to log in without an email, so they have to be
+    // discoverable
+    if let Some(selection) = options.public_key.authenticator_selection.as_mut() {
+        selection.require_resident_key = true;
+        selection.resident_key = Some(ResidentKeyRequirement::Required);
+    }
+
+    let state = RegistrationState {
+        user_id: user.id,
+        registration,
+    };
+
+    if auth_session
+        .session
+        .insert(REGISTRATION_KEY, state)
+        .await
+        .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    Sonic(PasskeyRegistrationOptions(options)).into_response()
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PasskeyRegisterFinishRequest {
+    /// A name to recognize the passkey
+    name: String,
+    /// The credential returned by `navigator.credentials.create`, with the
+    /// binary fields base64url encoded
+    #[schema(value_type = Object)]
+    credential: RegisterPublicKeyCredential,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PasskeyRegisterFinishResponse {
+    /// The ID of the passkey
+    id: i32,
+}
+
+#[utoipa::path(
+    post,
+    path = "/passkey_register_finish",
+    summary = "Finish Passkey Registration",
+    description = "Store the passkey created by the authenticator, which can then be used to log in",
+    request_body = PasskeyRegisterFinishRequest,
+    responses(
+        (status = CREATED, description = "Passkey was registered successfully", body = PasskeyRegisterFinishResponse),
+        (status = BAD_REQUEST, description = "Passkey is not valid"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = CONFLICT, description = "Passkey is already registered"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = AUTH_TAG
+)]
+pub async fn passkey_register_finish(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<PasskeyRegisterFinishRequest>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let name = request.name.trim();
+    if name.is_empty() {
+        return (StatusCode::BAD_REQUEST, "Name is not valid").into_response();
+    }
+
+    let webauthn = match webauthn() {
+        Ok(webauthn) => webauthn,
+        Err(e) => {
+            error!("Error finishing the passkey registration: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    // Each challenge can only be used once
+    let state = match auth_session
+        .session
+        .remove::<RegistrationState>(REGISTRATION_KEY)
+        .await
+    {
+        Ok(state) => state.filter(|state| state.user_id == user.id),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let Some(state) = state else {
+        debug!("No passkey registration was started by user {}", user.email);
+        return (StatusCode::BAD_REQUEST, "Passkey is not valid").into_response();
+    };
+
+    let passkey =
+        match webauthn.finish_passkey_registration(&request.credential, &state.registration) {
+            Ok(passkey) => passkey,
+            Err(e) => {
+                debug!("Passkey registration failed for user {}: {}", user.email, e);
+                return (StatusCode::BAD_REQUEST, "Passkey is not valid").into_response();
+            }
+        };
+
+    let id = match sqlx::query_scalar!(
+        r#"
+        INSERT INTO passkey (user_id, name, credential_id, credential)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (credential_id) DO NOTHING
+        RETURNING id
+        "#,
+        user.id,
+        name,
+        passkey.cred_id().as_ref(),
+        Json(&passkey) as _
+    )
+    .fetch_optional(&auth_session.backend.db)
+    .await
+    {
+        Ok(Some(id)) => id,
+        Ok(None) => {
+            return (StatusCode::CONFLICT, "Passkey is already registered").into_response();
+        }
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    info!("Passkey {} was registered for user {}", name, user.email);
+
+    audit
+        .event(AuditAction::PasskeyAdded, AuditTarget::Passkey, id)
+        .after(json!({ "name": name }))
+        .record()
+        .await;
+
+    (
+        StatusCode::CREATED,
+        Sonic(PasskeyRegisterFinishResponse { id }),
+    )
+        .into_response()
+}
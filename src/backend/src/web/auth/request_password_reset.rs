@@ -0,0 +1,74 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::Deserialize;
+use tracing::debug;
+use utoipa::ToSchema;
+
+use crate::{
+    app::openapi::AUTH_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    users::{AuthSession, User},
+    web::auth::email_token::EmailTokenKind,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RequestPasswordResetRequest {
+    /// The email of the user
+    email: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/request_password_reset",
+    summary = "Request Password Reset",
+    description = "Send a link to reset the password to the email address, if a user has it. The response is the same either way, so that it cannot be used to find out who has an account",
+    request_body = RequestPasswordResetRequest,
+    responses(
+        (status = OK, description = "Link was sent, if the user exists"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    tag = AUTH_TAG
+)]
+pub async fn request_password_reset(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<RequestPasswordResetRequest>,
+) -> impl IntoResponse {
+    let user = match sqlx::query_as!(
+        User,
+        r#"
+        SELECT * FROM "user" WHERE email = $1
+        "#,
+        request.email.trim()
+    )
+    .fetch_optional(&auth_session.backend.db)
+    .await
+    {
+        Ok(user) => user,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    match user {
+        Some(user) => {
+            auth_session
+                .backend
+                .send_email_token(&user, EmailTokenKind::ResetPassword);
+
+            audit
+                .event(
+                    AuditAction::PasswordResetRequested,
+                    AuditTarget::User,
+                    user.id,
+                )
+                .record()
+                .await;
+        }
+        None => debug!(
+            "Password reset requested for unknown email {}",
+            request.email
+        ),
+    }
+
+    StatusCode::OK.into_response()
+}
@@ -0,0 +1,194 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use password_auth::generate_hash;
+use serde::Deserialize;
+use tokio::task;
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::{
+    app::openapi::AUTH_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    middleware::track_session::forget_user_sessions,
+    users::AuthSession,
+    web::auth::{
+        email_token::{EmailTokenKind, consume_email_token},
+        login::AuthError,
+    },
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ResetPasswordRequest {
+    /// The token contained in the link sent to the email address
+    token: String,
+    /// The new password
+    new_password: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/reset_password",
+    summary = "Reset Password",
+    description = "Choose a new password with the token sent by email, the existing sessions of the user are logged out",
+    request_body = ResetPasswordRequest,
+    responses(
+        (status = OK, description = "Password was reset successfully"),
+        (status = BAD_REQUEST, description = "Token or password is not valid"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    tag = AUTH_TAG
+)]
+pub async fn reset_password(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<ResetPasswordRequest>,
+) -> impl IntoResponse {
+    if request.new_password.is_empty() {
+        return AuthError::InvalidPassword.into_response();
+    }
+
+    let new_password = request.new_password;
+    let Ok(encrypted_password) =
+        task::spawn_blocking(move || generate_hash(new_password.as_bytes())).await
+    else {
+        return AuthError::FailedToGenerateHash.into_response();
+    };
+
+    let Ok(mut tx) = auth_session.backend.db.begin().await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    let token =
+        match consume_email_token(&mut tx, &request.token, EmailTokenKind::ResetPassword).await {
+            Ok(Some(token)) => token,
+            Ok(None) => return AuthError::InvalidToken.into_response(),
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        };
+
+    // Changing the password invalidates the sessions (its hash is the auth hash),
+    // and the link proves that the user can read the emails sent to the address
+    match sqlx::query!(
+        r#"
+        UPDATE "user"
+        SET password = $3,
+            email_verified_at = COALESCE(email_verified_at, NOW())
+        WHERE id = $1
+          AND email = $2
+        "#,
+        token.user_id,
+        token.email,
+        encrypted_password
+    )
+    .execute(&mut *tx)
+    .await
+    {
+        Ok(result) if result.rows_affected() == 0 => {
+            return AuthError::InvalidToken.into_response();
+        }
+        Ok(_) => {}
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+
+    // The other links sent to reset the password cannot be used anymore
+    if sqlx::query!(
+        r#"
+        UPDATE email_token
+        SET used_at = NOW()
+        WHERE user_id = $1
+          AND kind = 'reset_password'
+          AND used_at IS NULL
+        "#,
+        token.user_id
+    )
+    .execute(&mut *tx)
+    .await
+    .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    if forget_user_sessions(&mut *tx, token.user_id).await.is_err() || tx.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    info!("Password was reset for user {}", token.email);
+
+    audit
+        .event(AuditAction::PasswordReset, AuditTarget::User, token.user_id)
+        .actor(token.user_id)
+        .record()
+        .await;
+
+    StatusCode::OK.into_response()
+}
+
+#[cfg(test)]
+mod test {
+    use sonic_rs::json;
+    use sqlx::PgPool;
+
+    use crate::{app::test_app::TestApp, users::User, web::auth::email_token::create_email_token};
+
+    use super::*;
+
+    #[sqlx::test]
+    #[ignore = "needs a PostgreSQL database (set DATABASE_URL)"]
+    async fn test_reset_password(db: PgPool) {
+        let app = TestApp::spawn(db).await;
+        let user_id = app.create_user("owner@example.com").await;
+        let user = sqlx::query_as!(User, r#"SELECT * FROM "user" WHERE id = $1"#, user_id)
+            .fetch_one(&app.db)
+            .await
+            .unwrap();
+
+        // Unknown addresses get the same response
+        for email in ["owner@example.com", "unknown@example.com"] {
+            let response = app
+                .client
+                .post(app.url("/request_password_reset"))
+                .json(&json!({ "email": email }))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let token = create_email_token(&app.db, &user, EmailTokenKind::ResetPassword)
+            .await
+            .unwrap();
+        let other_token = create_email_token(&app.db, &user, EmailTokenKind::ResetPassword)
+            .await
+            .unwrap();
+
+        let response = app
+            .client
+            .post(app.url("/reset_password"))
+            .json(&json!({ "token": token, "new_password": "hunter22" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .client
+            .post(app.url("/login"))
+            .json(&json!({ "email": "owner@example.com", "password": "hunter22" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Both the used token and the other ones sent before cannot be used again
+        for token in [token, other_token] {
+            let response = app
+                .client
+                .post(app.url("/reset_password"))
+                .json(&json!({ "token": token, "new_password": "hunter23" }))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+    }
+}
@@ -0,0 +1,364 @@
+use std::{fmt, str::FromStr};
+
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use chrono_tz::Tz;
+use http::StatusCode;
+use password_auth::generate_hash;
+use serde::Deserialize;
+use sonic_rs::json;
+use sqlx::PgPool;
+use tokio::task;
+use tracing::{debug, info};
+use utoipa::ToSchema;
+
+use crate::{
+    REGISTRATION_MODE,
+    app::openapi::AUTH_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    users::{AuthSession, User},
+    web::{
+        auth::{
+            email_token::EmailTokenKind,
+            login::{AuthError, LoginResponse},
+        },
+        utils::token::hash_token,
+    },
+};
+
+/// Who can create an account on the instance
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RegistrationMode {
+    /// Anyone can sign up
+    #[default]
+    Open,
+    /// Only who has an invite (created by an existing user) can sign up
+    InviteOnly,
+    /// Nobody can sign up
+    Disabled,
+}
+
+impl FromStr for RegistrationMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "open" => Ok(RegistrationMode::Open),
+            "invite_only" => Ok(RegistrationMode::InviteOnly),
+            "disabled" => Ok(RegistrationMode::Disabled),
+            _ => Err(format!(
+                "Invalid registration mode {s}, expected open, invite_only or disabled"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for RegistrationMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self {
+            RegistrationMode::Open => "open",
+            RegistrationMode::InviteOnly => "invite_only",
+            RegistrationMode::Disabled => "disabled",
+        };
+
+        f.write_str(mode)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct SignupRequest {
+    /// The email of the user
+    pub email: String,
+    /// The password of the user
+    pub password: String,
+    /// The invite code, required when registration is invite-only
+    pub invite_code: Option<String>,
+}
+
+pub const DEFAULT_TIMEZONE: Tz = Tz::UTC;
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+#[utoipa::path(
+    post,
+    path = "/signup",
+    summary = "Sign Up",
+    description = "Create an account and log in, depending on the registration mode of the instance an invite may be needed",
+    request_body = SignupRequest,
+    responses(
+        (status = CREATED, description = "User was created", body = LoginResponse),
+        (status = BAD_REQUEST, description = "Email or password is not valid"),
+        (status = FORBIDDEN, description = "Registration is disabled or the invite is not valid"),
+        (status = CONFLICT, description = "Email is already in use"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error", body = str, content_type = "text/plain")
+    ),
+    tag = AUTH_TAG
+)]
+pub async fn signup(
+    mut auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<SignupRequest>,
+) -> impl IntoResponse {
+    let user = match sign_up(&auth_session.backend.db, *REGISTRATION_MODE, request).await {
+        Ok(user) => user,
+        Err(e) => return e.into_response(),
+    };
+
+    if auth_session.login(&user).await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    info!("Successfully signed up as {}", user.email);
+
+    audit
+        .event(AuditAction::Signup, AuditTarget::User, user.id)
+        .actor(user.id)
+        .after(json!({ "email": user.email }))
+        .record()
+        .await;
+
+    // Alerts are only sent by email once the address is verified
+    auth_session
+        .backend
+        .send_email_token(&user, EmailTokenKind::VerifyEmail);
+
+    (
+        StatusCode::CREATED,
+        Sonic(LoginResponse {
+            status: "User was created".to_string(),
+        }),
+    )
+        .into_response()
+}
+
+/// Creates the user, consuming the invite in the same transaction (so that
+/// each invite can only be used once)
+pub async fn sign_up(
+    db: &PgPool,
+    mode: RegistrationMode,
+    request: SignupRequest,
+) -> Result<User, AuthError> {
+    let invite_code = match (mode, request.invite_code) {
+        (RegistrationMode::Disabled, _) => return Err(AuthError::RegistrationDisabled),
+        (RegistrationMode::InviteOnly, None) => return Err(AuthError::InvalidInvite),
+        (RegistrationMode::InviteOnly, Some(invite_code)) => Some(invite_code),
+        (RegistrationMode::Open, _) => None,
+    };
+
+    let email = request.email.trim().to_string();
+    if !email.contains('@') {
+        return Err(AuthError::InvalidEmail);
+    }
+
+    if request.password.is_empty() {
+        return Err(AuthError::InvalidPassword);
+    }
+
+    let password = request.password;
+    let encrypted_password = task::spawn_blocking(move || generate_hash(password.as_bytes()))
+        .await
+        .map_err(|_| AuthError::FailedToGenerateHash)?;
+
+    let mut tx = db.begin().await?;
+
+    let Some(user) = sqlx::query_as!(
+        User,
+        r#"
+        INSERT INTO "user" (email, password, timezone, language)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (email) DO NOTHING
+        RETURNING *;
+        "#,
+        email,
+        encrypted_password,
+        DEFAULT_TIMEZONE.to_string(),
+        DEFAULT_LANGUAGE
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    else {
+        debug!("User with email {} already exists", email);
+        return Err(AuthError::EmailAlreadyInUse);
+    };
+
+    if let Some(invite_code) = invite_code {
+        let used = sqlx::query!(
+            r#"
+            UPDATE invite
+            SET used_by = $1,
+                used_at = NOW()
+            WHERE code_hash = $2
+              AND used_at IS NULL
+              AND expires_at > NOW()
+            "#,
+            user.id,
+            hash_token(invite_code.trim())
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            > 0;
+
+        // Dropping the transaction rolls back the creation of the user
+        if !used {
+            return Err(AuthError::InvalidInvite);
+        }
+    }
+
+    tx.commit().await?;
+
+    Ok(user)
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, Utc};
+    use http::header;
+    use sonic_rs::json;
+
+    use crate::{app::test_app::TestApp, web::utils::token::generate_token};
+
+    use super::*;
+
+    #[test]
+    fn test_registration_mode() {
+        assert_eq!("open".parse(), Ok(RegistrationMode::Open));
+        assert_eq!("Invite_Only".parse(), Ok(RegistrationMode::InviteOnly));
+        assert_eq!(" disabled ".parse(), Ok(RegistrationMode::Disabled));
+        assert!("closed".parse::<RegistrationMode>().is_err());
+        assert_eq!(RegistrationMode::default(), RegistrationMode::Open);
+    }
+
+    fn request(email: &str, invite_code: Option<&str>) -> SignupRequest {
+        SignupRequest {
+            email: email.to_string(),
+            password: "hunter22".to_string(),
+            invite_code: invite_code.map(str::to_string),
+        }
+    }
+
+    async fn create_invite(db: &PgPool, user_id: i32, expires_in: Duration) -> String {
+        let (code, code_hash) = generate_token("inv_");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO invite (created_by, code_hash, expires_at) VALUES ($1, $2, $3)
+            "#,
+            user_id,
+            code_hash,
+            (Utc::now() + expires_in).naive_utc()
+        )
+        .execute(db)
+        .await
+        .unwrap();
+
+        code
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a PostgreSQL database (set DATABASE_URL)"]
+    async fn test_signup_and_login(db: PgPool) {
+        let app = TestApp::spawn(db).await;
+
+        let credentials = json!({ "email": "new@example.com", "password": "hunter22" });
+
+        // Unknown users are not signed up by the login
+        let response = app
+            .client
+            .post(app.url("/login"))
+            .json(&credentials)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app
+            .client
+            .post(app.url("/signup"))
+            .json(&credentials)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(response.headers().contains_key(header::SET_COOKIE));
+
+        let response = app
+            .client
+            .post(app.url("/signup"))
+            .json(&credentials)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let response = app
+            .client
+            .post(app.url("/login"))
+            .json(&credentials)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .client
+            .post(app.url("/login"))
+            .json(&json!({ "email": "new@example.com", "password": "wrong" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a PostgreSQL database (set DATABASE_URL)"]
+    async fn test_invite_only_signup(db: PgPool) {
+        let mode = RegistrationMode::InviteOnly;
+        let inviter_id = sign_up(&db, RegistrationMode::Open, request("a@example.com", None))
+            .await
+            .unwrap()
+            .id;
+
+        assert!(matches!(
+            sign_up(&db, mode, request("b@example.com", None)).await,
+            Err(AuthError::InvalidInvite)
+        ));
+        assert!(matches!(
+            sign_up(&db, mode, request("b@example.com", Some("inv_unknown"))).await,
+            Err(AuthError::InvalidInvite)
+        ));
+
+        let expired = create_invite(&db, inviter_id, Duration::minutes(-1)).await;
+        assert!(matches!(
+            sign_up(&db, mode, request("b@example.com", Some(&expired))).await,
+            Err(AuthError::InvalidInvite)
+        ));
+
+        let code = create_invite(&db, inviter_id, Duration::days(1)).await;
+        let user = sign_up(&db, mode, request("b@example.com", Some(&code)))
+            .await
+            .unwrap();
+        assert_eq!(user.email, "b@example.com");
+
+        // Each invite can only be used once, and the user is not created if it
+        // cannot be used
+        assert!(matches!(
+            sign_up(&db, mode, request("c@example.com", Some(&code))).await,
+            Err(AuthError::InvalidInvite)
+        ));
+        let users = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM "user""#)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(users, 2);
+
+        assert!(matches!(
+            sign_up(
+                &db,
+                RegistrationMode::Disabled,
+                request("d@example.com", Some(&code))
+            )
+            .await,
+            Err(AuthError::RegistrationDisabled)
+        ));
+    }
+}
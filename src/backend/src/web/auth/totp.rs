@@ -0,0 +1,291 @@
+use chrono::Utc;
+use rand::RngCore;
+use sqlx::{PgConnection, PgPool};
+use totp_rs::{Algorithm, Secret, TOTP, TotpUrlError};
+
+use crate::web::utils::token::hash_token;
+
+/// The name of the account shown in the authenticator apps
+const TOTP_ISSUER: &str = "Monitor";
+/// How long each code is valid for, in seconds
+const TOTP_STEP: u64 = 30;
+/// How many steps before and after the current one are accepted, to allow for
+/// clock drift
+const TOTP_SKEW: u64 = 1;
+/// How many recovery codes are generated when 2FA is confirmed
+pub const RECOVERY_CODES: usize = 10;
+
+/// Generates a new base32 secret for the authenticator app
+pub fn generate_totp_secret() -> String {
+    Secret::generate_secret().to_encoded().to_string()
+}
+
+fn totp(secret: &str, email: &str) -> Result<TOTP, TotpUrlError> {
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|_| TotpUrlError::Secret(secret.to_string()))?;
+
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        TOTP_SKEW as u8,
+        TOTP_STEP,
+        secret,
+        Some(TOTP_ISSUER.to_string()),
+        email.to_string(),
+    )
+}
+
+/// The `otpauth://` URI to add the account to an authenticator app (usually
+/// shown as a QR code)
+pub fn otpauth_uri(secret: &str, email: &str) -> Result<String, TotpUrlError> {
+    Ok(totp(secret, email)?.get_url())
+}
+
+/// The time step the code is valid for, if it is valid at the time (in
+/// seconds since the epoch)
+fn matching_step(secret: &str, code: &str, time: u64) -> Option<i64> {
+    let totp = totp(secret, "").ok()?;
+    let current_step = time / TOTP_STEP;
+
+    (current_step.saturating_sub(TOTP_SKEW)..=current_step + TOTP_SKEW)
+        .find(|step| totp.generate(step * TOTP_STEP) == code)
+        .map(|step| step as i64)
+}
+
+/// Checks the code against the TOTP secret of the user, which may not be
+/// confirmed yet, marking it as used (so that it cannot be replayed)
+pub async fn use_totp_code(
+    conn: &mut PgConnection,
+    user_id: i32,
+    secret: &str,
+    code: &str,
+) -> Result<bool, sqlx::Error> {
+    let Some(step) = matching_step(secret, code.trim(), Utc::now().timestamp() as u64) else {
+        return Ok(false);
+    };
+
+    let result = sqlx::query!(
+        r#"
+        UPDATE totp
+        SET last_used_step = $2
+        WHERE user_id = $1
+          AND (last_used_step IS NULL OR last_used_step < $2)
+        "#,
+        user_id,
+        step
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Checks the second factor of a user with 2FA enabled, which is either a code
+/// from the authenticator app or an unused recovery code
+pub async fn verify_second_factor(
+    db: &PgPool,
+    user_id: i32,
+    secret: &str,
+    code: &str,
+) -> Result<bool, sqlx::Error> {
+    let mut conn = db.acquire().await?;
+
+    if use_totp_code(&mut conn, user_id, secret, code).await? {
+        return Ok(true);
+    }
+
+    let result = sqlx::query!(
+        r#"
+        UPDATE recovery_code
+        SET used_at = NOW()
+        WHERE user_id = $1
+          AND code_hash = $2
+          AND used_at IS NULL
+        "#,
+        user_id,
+        hash_recovery_code(code)
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// The confirmed TOTP secret of the user, if 2FA is enabled
+pub async fn confirmed_totp_secret(
+    db: &PgPool,
+    user_id: i32,
+) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT secret FROM totp WHERE user_id = $1 AND confirmed_at IS NOT NULL
+        "#,
+        user_id
+    )
+    .fetch_optional(db)
+    .await
+}
+
+/// Generates a recovery code, such as `1a2b-3c4d-5e6f-7a8b`, returning it
+/// along with its hash
+pub fn generate_recovery_code() -> (String, String) {
+    let mut bytes = [0u8; 8];
+    rand::rng().fill_bytes(&mut bytes);
+
+    let code = hex::encode(bytes)
+        .as_bytes()
+        .chunks(4)
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .collect::<Vec<_>>()
+        .join("-");
+    let hash = hash_recovery_code(&code);
+
+    (code, hash)
+}
+
+/// The codes are hashed without the dashes and the case, so that they can be
+/// typed in any way
+fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+
+    hash_token(&normalized)
+}
+
+#[cfg(test)]
+mod test {
+    use http::{StatusCode, header};
+    use sonic_rs::{JsonContainerTrait, JsonValueTrait, Value, json};
+
+    use crate::app::test_app::TestApp;
+
+    use super::*;
+
+    #[test]
+    fn test_matching_step() {
+        let secret = generate_totp_secret();
+        let totp = totp(&secret, "owner@example.com").unwrap();
+        let time = 1_800_000_000;
+        let step = (time / TOTP_STEP) as i64;
+
+        let code = totp.generate(time);
+        assert_eq!(matching_step(&secret, &code, time), Some(step));
+
+        // The codes of the adjacent steps are accepted, the older ones are not
+        let previous = totp.generate(time - TOTP_STEP);
+        assert_eq!(matching_step(&secret, &previous, time), Some(step - 1));
+        let old = totp.generate(time - 3 * TOTP_STEP);
+        assert_eq!(matching_step(&secret, &old, time), None);
+    }
+
+    #[test]
+    fn test_otpauth_uri() {
+        let uri = otpauth_uri(&generate_totp_secret(), "owner@example.com").unwrap();
+        assert!(uri.starts_with("otpauth://totp/Monitor:owner%40example.com?secret="));
+        assert!(uri.contains("issuer=Monitor"));
+    }
+
+    #[test]
+    fn test_recovery_code() {
+        let (code, hash) = generate_recovery_code();
+        assert_eq!(code.len(), 19);
+        assert_eq!(
+            hash_recovery_code(&code.to_uppercase().replace('-', "")),
+            hash
+        );
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a PostgreSQL database (set DATABASE_URL)"]
+    async fn test_login_with_totp(db: PgPool) {
+        let app = TestApp::spawn(db).await;
+        let credentials = json!({ "email": "owner@example.com", "password": "hunter22" });
+
+        let response = app
+            .client
+            .post(app.url("/signup"))
+            .json(&credentials)
+            .send()
+            .await
+            .unwrap();
+        let cookie = response
+            .headers()
+            .get(header::SET_COOKIE)
+            .and_then(|cookie| cookie.to_str().ok())
+            .and_then(|cookie| cookie.split(';').next())
+            .unwrap()
+            .to_string();
+
+        let response = app
+            .client
+            .post(app.url("/user/enroll_totp"))
+            .header(header::COOKIE, &cookie)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response: Value = sonic_rs::from_str(&response.text().await.unwrap()).unwrap();
+        let secret = response["secret"].as_str().unwrap().to_string();
+        let totp = totp(&secret, "").unwrap();
+        let now = Utc::now().timestamp() as u64;
+
+        // 2FA is not required until it is confirmed
+        let login = |request: Value| app.client.post(app.url("/login")).json(&request).send();
+        assert_eq!(
+            login(credentials.clone()).await.unwrap().status(),
+            StatusCode::OK
+        );
+
+        let response = app
+            .client
+            .post(app.url("/user/confirm_totp"))
+            .header(header::COOKIE, &cookie)
+            .json(&json!({ "code": totp.generate(now - TOTP_STEP) }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response: Value = sonic_rs::from_str(&response.text().await.unwrap()).unwrap();
+        let recovery_codes = response["recovery_codes"].as_array().unwrap();
+        assert_eq!(recovery_codes.len(), RECOVERY_CODES);
+        let recovery_code = recovery_codes[0].as_str().unwrap();
+
+        let response = login(credentials.clone()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.text().await.unwrap(), "\"SecondFactorRequired\"");
+
+        let code = totp.generate(now);
+        for (code, status) in [
+            ("000000", StatusCode::UNAUTHORIZED),
+            (code.as_str(), StatusCode::OK),
+            // Each code can only be used once
+            (code.as_str(), StatusCode::UNAUTHORIZED),
+            (recovery_code, StatusCode::OK),
+            (recovery_code, StatusCode::UNAUTHORIZED),
+        ] {
+            let response = login(json!({
+                "email": "owner@example.com",
+                "password": "hunter22",
+                "totp_code": code
+            }))
+            .await
+            .unwrap();
+            assert_eq!(response.status(), status);
+        }
+
+        let response = app
+            .client
+            .delete(app.url("/user/disable_totp"))
+            .header(header::COOKIE, &cookie)
+            .json(&json!({ "password": "hunter22" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(login(credentials).await.unwrap().status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,86 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::Deserialize;
+use sonic_rs::json;
+use utoipa::ToSchema;
+
+use crate::{
+    app::openapi::AUTH_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+    web::auth::{
+        email_token::{EmailTokenKind, consume_email_token},
+        login::AuthError,
+    },
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct VerifyEmailRequest {
+    /// The token contained in the link sent to the email address
+    token: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/verify_email",
+    summary = "Verify Email",
+    description = "Verify the email address of a user with the token sent to it, alerts are only sent by email to verified addresses",
+    request_body = VerifyEmailRequest,
+    responses(
+        (status = OK, description = "Email was verified successfully"),
+        (status = BAD_REQUEST, description = "Token is not valid or expired"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    tag = AUTH_TAG
+)]
+pub async fn verify_email(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<VerifyEmailRequest>,
+) -> impl IntoResponse {
+    let Ok(mut tx) = auth_session.backend.db.begin().await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    let token =
+        match consume_email_token(&mut tx, &request.token, EmailTokenKind::VerifyEmail).await {
+            Ok(Some(token)) => token,
+            Ok(None) => return AuthError::InvalidToken.into_response(),
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        };
+
+    // The token only verifies the address it was sent to
+    match sqlx::query!(
+        r#"
+        UPDATE "user"
+        SET email_verified_at = COALESCE(email_verified_at, NOW())
+        WHERE id = $1
+          AND email = $2
+        "#,
+        token.user_id,
+        token.email
+    )
+    .execute(&mut *tx)
+    .await
+    {
+        Ok(result) if result.rows_affected() == 0 => {
+            return AuthError::InvalidToken.into_response();
+        }
+        Ok(_) => {}
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+
+    if tx.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    audit
+        .event(AuditAction::EmailVerified, AuditTarget::User, token.user_id)
+        .actor(token.user_id)
+        .after(json!({ "email": token.email }))
+        .record()
+        .await;
+
+    StatusCode::OK.into_response()
+}
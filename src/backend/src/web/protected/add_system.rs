@@ -1,28 +1,66 @@
+use std::str::FromStr;
+
 use axum::response::IntoResponse;
 use axum_serde::Sonic;
 use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
 use http::StatusCode;
 use serde::{Deserialize, Serialize};
+use sonic_rs::json;
 use sqlx::postgres::types::PgInterval;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::{
-    app::openapi::SYSTEM_TAG, users::AuthSession, web::protected::list_systems::Visibility,
+    app::openapi::SYSTEM_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    models::{ScheduleKind, Visibility},
+    users::AuthSession,
+    web::{
+        protected::organizations::{OrganizationRole, ensure_member_role},
+        utils::{
+            schedule::{Schedule, parse_cron_expression},
+            time::naive_datetime_now,
+            time_conversions::pg_interval_to_duration,
+        },
+    },
 };
 
 #[derive(Debug, Deserialize, Clone, ToSchema)]
 pub struct AddSystemRequest {
     /// The name of the system
     name: String,
-    /// The frequency in minutes of the pings
-    frequency: i64,
+    /// The kind of schedule the system follows
+    #[serde(default)]
+    schedule_kind: ScheduleKind,
+    /// The frequency in minutes of the pings (required for interval schedules)
+    frequency: Option<i64>,
+    /// The cron expression of the pings, such as `0 3 * * 1-5` (required for
+    /// cron schedules)
+    cron_expression: Option<String>,
+    /// The timezone in which the cron expression is evaluated, as defined by
+    /// the IANA Time Zone Database (defaults to the timezone of the user)
+    cron_timezone: Option<String>,
     /// The time at which the system starts pinging
     starts_at: DateTime<Utc>,
-    /// Time in minutes after which the user will get emailed
+    /// Time in minutes after which the user will get emailed (at least 1)
     down_after: i64,
+    /// Grace in minutes around the expected time of the pings, used for both
+    /// sides unless `grace_early` or `grace_late` are given (less than the
+    /// frequency)
+    grace: Option<i64>,
+    /// How many minutes before the expected time a ping still counts
+    /// (defaults to 0)
+    grace_early: Option<i64>,
+    /// How many minutes after the expected time a ping still counts, later
+    /// pings are marked as late (pings count until the next expected time if
+    /// missing)
+    grace_late: Option<i64>,
     /// The visibility of the system
     visibility: Visibility,
+    /// The organization the system belongs to (the user must be at least an
+    /// editor of it), the system is personal if missing
+    organization_id: Option<i32>,
 }
 
 #[derive(Debug, Serialize, Clone, ToSchema)]
@@ -40,39 +78,71 @@ pub struct AddSystemResponse {
         (status = CREATED, description = "System was created successfully", body = AddSystemResponse),
         (status = BAD_REQUEST, description = "Bad request"),
         (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = FORBIDDEN, description = "User cannot add systems to the organization"),
+        (status = NOT_FOUND, description = "Organization not found"),
         (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
     ),
     security(
-        ("session" = [])
+        ("session" = []),
+        ("api_token" = [])
     ),
     tag = SYSTEM_TAG
 )]
 pub async fn add_system(
     auth_session: AuthSession,
+    audit: Audit,
     Sonic(request): Sonic<AddSystemRequest>,
 ) -> impl IntoResponse {
     let Some(user) = auth_session.user else {
         return StatusCode::UNAUTHORIZED.into_response();
     };
 
-    let frequency: PgInterval = match Duration::minutes(request.frequency).try_into() {
-        Ok(interval) => interval,
-        _ => return StatusCode::BAD_REQUEST.into_response(),
+    if let Some(organization_id) = request.organization_id
+        && let Err(response) = ensure_member_role(
+            &auth_session.backend.db,
+            organization_id,
+            user.id,
+            OrganizationRole::Editor,
+        )
+        .await
+    {
+        return response;
+    }
+
+    let (frequency, cron_expression, cron_timezone) = match validate_schedule(
+        request.schedule_kind,
+        request.frequency,
+        request.cron_expression,
+        request.cron_timezone.unwrap_or(user.timezone),
+    ) {
+        Ok(schedule) => schedule,
+        Err(response) => return response.into_response(),
     };
 
     let starts_at = request.starts_at.naive_utc();
 
     let id = Uuid::new_v4();
 
-    let down_after: PgInterval = match Duration::minutes(request.down_after).try_into() {
+    let down_after = match validate_down_after(request.down_after) {
         Ok(interval) => interval,
-        _ => return StatusCode::BAD_REQUEST.into_response(),
+        Err(response) => return response.into_response(),
+    };
+
+    let (grace_early, grace_late) = match validate_grace(
+        request.grace_early.or(request.grace).unwrap_or(0),
+        request.grace_late.or(request.grace),
+        frequency,
+    ) {
+        Ok(grace) => grace,
+        Err(response) => return response.into_response(),
     };
 
     if sqlx::query!(
         r#"
-        INSERT INTO system (id, name, user_id, frequency, starts_at, down_after, visibility)
-        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        INSERT INTO system (id, name, user_id, frequency, starts_at, down_after, visibility,
+                            schedule_kind, cron_expression, cron_timezone, grace_early, grace_late,
+                            organization_id)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
         "#,
         id,
         request.name,
@@ -80,7 +150,13 @@ pub async fn add_system(
         frequency,
         starts_at,
         down_after,
-        request.visibility as Visibility
+        request.visibility.clone() as Visibility,
+        request.schedule_kind as ScheduleKind,
+        cron_expression,
+        cron_timezone,
+        grace_early,
+        grace_late,
+        request.organization_id
     )
     .execute(&auth_session.backend.db)
     .await
@@ -89,5 +165,141 @@ pub async fn add_system(
         return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     };
 
+    audit
+        .event(AuditAction::SystemCreated, AuditTarget::System, id)
+        .organization(request.organization_id)
+        .after(json!({
+            "name": request.name,
+            "visibility": request.visibility,
+            "schedule_kind": request.schedule_kind,
+        }))
+        .record()
+        .await;
+
     (StatusCode::CREATED, Sonic(AddSystemResponse { id })).into_response()
 }
+
+/// The frequency, the cron expression and the cron timezone of a system, as
+/// they are stored
+pub type ScheduleColumns = (Option<PgInterval>, Option<String>, Option<String>);
+
+/// Validates the schedule of a system, only the columns of the kind of
+/// schedule are kept
+pub fn validate_schedule(
+    schedule_kind: ScheduleKind,
+    frequency: Option<i64>,
+    cron_expression: Option<String>,
+    cron_timezone: String,
+) -> Result<ScheduleColumns, (StatusCode, &'static str)> {
+    match schedule_kind {
+        ScheduleKind::Interval => {
+            let frequency = match frequency {
+                Some(frequency) if frequency > 0 => frequency,
+                _ => return Err((StatusCode::BAD_REQUEST, "Frequency is not valid")),
+            };
+
+            Ok((Some(minutes_to_interval(frequency)?), None, None))
+        }
+        ScheduleKind::Cron => {
+            let Some(cron_expression) = cron_expression else {
+                return Err((StatusCode::BAD_REQUEST, "Cron expression is missing"));
+            };
+
+            if parse_cron_expression(&cron_expression).is_err() {
+                return Err((StatusCode::BAD_REQUEST, "Cron expression is not valid"));
+            }
+
+            let Ok(cron_timezone) = Tz::from_str(&cron_timezone) else {
+                return Err((StatusCode::BAD_REQUEST, "Timezone not valid"));
+            };
+
+            let now = naive_datetime_now();
+            let fires = Schedule::new(
+                ScheduleKind::Cron,
+                None,
+                Some(&cron_expression),
+                Some(cron_timezone.name()),
+                now,
+            )
+            .and_then(|schedule| schedule.check_fires(now));
+
+            if fires.is_err() {
+                return Err((StatusCode::BAD_REQUEST, "Cron expression never fires"));
+            }
+
+            Ok((None, Some(cron_expression), Some(cron_timezone.to_string())))
+        }
+    }
+}
+
+/// Validates the grace (in minutes) around the expected time of the pings, it
+/// has to be shorter than the frequency of interval schedules (or the windows
+/// of consecutive pings would overlap)
+pub fn validate_grace(
+    grace_early: i64,
+    grace_late: Option<i64>,
+    frequency: Option<PgInterval>,
+) -> Result<(PgInterval, Option<PgInterval>), (StatusCode, &'static str)> {
+    if grace_early < 0 || grace_late.is_some_and(|grace_late| grace_late < 0) {
+        return Err((StatusCode::BAD_REQUEST, "Grace is not valid"));
+    }
+
+    if let Some(frequency) = frequency {
+        let frequency = pg_interval_to_duration(frequency).num_minutes();
+
+        if grace_early >= frequency || grace_late.is_some_and(|grace_late| grace_late >= frequency)
+        {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Grace must be shorter than the frequency",
+            ));
+        }
+    }
+
+    Ok((
+        minutes_to_interval(grace_early)?,
+        grace_late.map(minutes_to_interval).transpose()?,
+    ))
+}
+
+/// Validates the time (in minutes) after which a system is considered down
+pub fn validate_down_after(minutes: i64) -> Result<PgInterval, (StatusCode, &'static str)> {
+    if minutes <= 0 {
+        return Err((StatusCode::BAD_REQUEST, "Down after is not valid"));
+    }
+
+    minutes_to_interval(minutes)
+}
+
+/// Converts a duration in minutes to an interval that can be stored
+pub fn minutes_to_interval(minutes: i64) -> Result<PgInterval, (StatusCode, &'static str)> {
+    Duration::try_minutes(minutes)
+        .and_then(|duration| duration.try_into().ok())
+        .ok_or((StatusCode::BAD_REQUEST, "Duration is not valid"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_grace() {
+        let hourly = minutes_to_interval(60).unwrap();
+
+        assert!(validate_grace(0, None, Some(hourly)).is_ok());
+        assert!(validate_grace(59, Some(59), Some(hourly)).is_ok());
+        assert!(validate_grace(60, None, Some(hourly)).is_err());
+        assert!(validate_grace(0, Some(60), Some(hourly)).is_err());
+        assert!(validate_grace(-1, None, Some(hourly)).is_err());
+
+        // Cron schedules have no fixed frequency
+        assert!(validate_grace(120, Some(120), None).is_ok());
+    }
+
+    #[test]
+    fn test_validate_down_after() {
+        assert!(validate_down_after(1).is_ok());
+        assert!(validate_down_after(0).is_err());
+        assert!(validate_down_after(-5).is_err());
+    }
+}
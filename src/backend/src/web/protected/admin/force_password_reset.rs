@@ -0,0 +1,77 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use crate::{
+    app::openapi::ADMIN_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    middleware::track_session::forget_user_sessions,
+    users::{AuthSession, User},
+    web::auth::email_token::EmailTokenKind,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ForcePasswordResetRequest {
+    /// The ID of the user whose password is reset
+    user_id: i32,
+}
+
+#[utoipa::path(
+    post,
+    path = "/force_password_reset",
+    summary = "Force Password Reset",
+    description = "Clear the password of a user, which is logged out everywhere and receives a link by email to choose a new one",
+    request_body = ForcePasswordResetRequest,
+    responses(
+        (status = OK, description = "Password was reset successfully"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = FORBIDDEN, description = "User is not an admin"),
+        (status = NOT_FOUND, description = "User not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = ADMIN_TAG
+)]
+pub async fn force_password_reset(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<ForcePasswordResetRequest>,
+) -> impl IntoResponse {
+    let Ok(mut tx) = auth_session.backend.db.begin().await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    // Clearing the password changes the session auth hash, which logs out the
+    // existing sessions
+    let user = match sqlx::query_as!(
+        User,
+        r#"UPDATE "user" SET password = NULL WHERE id = $1 RETURNING *"#,
+        request.user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    {
+        Ok(Some(user)) => user,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    if forget_user_sessions(&mut *tx, user.id).await.is_err() || tx.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    auth_session
+        .backend
+        .send_email_token(&user, EmailTokenKind::ResetPassword);
+
+    audit
+        .event(AuditAction::PasswordResetForced, AuditTarget::User, user.id)
+        .record()
+        .await;
+
+    StatusCode::OK.into_response()
+}
@@ -0,0 +1,128 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use chrono::NaiveDate;
+use http::StatusCode;
+use serde::Serialize;
+use tracing::error;
+use utoipa::ToSchema;
+
+use crate::{
+    app::openapi::ADMIN_TAG,
+    users::AuthSession,
+    workers::{deadlines::pending_deadlines, pending_jobs},
+};
+
+/// How many days of pings are counted
+const PING_DAYS: i32 = 30;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct InstanceStatsResponse {
+    /// The number of users
+    users: i64,
+    /// The number of disabled users
+    disabled_users: i64,
+    /// The number of admins
+    admins: i64,
+    /// The number of (not deleted) systems
+    systems: i64,
+    /// The number of organizations
+    organizations: i64,
+    /// The number of pings received in each of the last 30 days, the oldest
+    /// first (days without pings are included)
+    pings_per_day: Vec<DailyPings>,
+    /// The number of jobs waiting to be run (enqueued, retried or scheduled),
+    /// missing if the queue cannot be reached
+    pending_jobs: Option<u64>,
+    /// The number of systems waiting for their down deadline, missing if the
+    /// queue cannot be reached
+    pending_deadlines: Option<u64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DailyPings {
+    /// The day (in UTC)
+    day: NaiveDate,
+    /// The number of pings received in the day
+    pings: i64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/get_instance_stats",
+    summary = "Get Instance Stats",
+    description = "Retrieve the instance-wide counts of users, systems, pings and pending jobs",
+    responses(
+        (status = OK, description = "Stats were retrieved successfully", body = InstanceStatsResponse),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = FORBIDDEN, description = "User is not an admin"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = ADMIN_TAG
+)]
+pub async fn get_instance_stats(auth_session: AuthSession) -> impl IntoResponse {
+    let db = &auth_session.backend.db;
+
+    let Ok(counts) = sqlx::query!(
+        r#"
+        SELECT (SELECT COUNT(*) FROM "user") AS "users!",
+               (SELECT COUNT(*) FROM "user" WHERE disabled_at IS NOT NULL) AS "disabled_users!",
+               (SELECT COUNT(*) FROM "user" WHERE is_admin) AS "admins!",
+               (SELECT COUNT(*) FROM system WHERE deleted = FALSE) AS "systems!",
+               (SELECT COUNT(*) FROM organization) AS "organizations!"
+        "#
+    )
+    .fetch_one(db)
+    .await
+    else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    let Ok(pings_per_day) = sqlx::query_as!(
+        DailyPings,
+        r#"
+        SELECT d.day::date AS "day!",
+               (SELECT COUNT(*)
+                FROM ping p
+                WHERE p.timestamp >= d.day
+                  AND p.timestamp < d.day + INTERVAL '1 day') AS "pings!"
+        FROM generate_series(
+                 (NOW() AT TIME ZONE 'UTC')::date - ($1::integer - 1),
+                 (NOW() AT TIME ZONE 'UTC')::date,
+                 INTERVAL '1 day'
+             ) AS d(day)
+        ORDER BY d.day
+        "#,
+        PING_DAYS
+    )
+    .fetch_all(db)
+    .await
+    else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    // The counts of the database are still useful when Redis is down
+    let redis = &auth_session.backend.redis;
+    let pending_jobs = pending_jobs(redis)
+        .await
+        .inspect_err(|e| error!("Error counting the pending jobs: {}", e))
+        .ok();
+    let pending_deadlines = pending_deadlines(redis)
+        .await
+        .inspect_err(|e| error!("Error counting the pending deadlines: {}", e))
+        .ok();
+
+    Sonic(InstanceStatsResponse {
+        users: counts.users,
+        disabled_users: counts.disabled_users,
+        admins: counts.admins,
+        systems: counts.systems,
+        organizations: counts.organizations,
+        pings_per_day,
+        pending_jobs,
+        pending_deadlines,
+    })
+    .into_response()
+}
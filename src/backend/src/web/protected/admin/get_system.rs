@@ -0,0 +1,108 @@
+use axum::{
+    extract::{Path, Query},
+    response::IntoResponse,
+};
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    app::openapi::ADMIN_TAG,
+    models::{ScheduleKind, Visibility},
+    users::AuthSession,
+    web::protected::list_systems::{SystemData, SystemRecord, validate_paging},
+};
+
+#[derive(Debug, Deserialize, Clone, IntoParams)]
+pub struct GetSystemQuery {
+    /// The maximum number of instants to return
+    pub list_size: i64,
+    /// The page number to return
+    pub page: i64,
+}
+
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct GetSystemResponse {
+    /// The requested system's data
+    pub system: SystemData,
+    /// The email of the user that created the system
+    pub owner_email: String,
+    /// Whether the system was deleted by its owner
+    pub deleted: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/get_system/{id}",
+    summary = "Retrieve System",
+    description = "Retrieve info about any system of the instance, including the private and deleted ones",
+    params(GetSystemQuery),
+    responses(
+        (status = OK, description = "System was retrieved successfully", body = GetSystemResponse),
+        (status = BAD_REQUEST, description = "Page or list size is not valid"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = FORBIDDEN, description = "User is not an admin"),
+        (status = NOT_FOUND, description = "System not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = ADMIN_TAG
+)]
+pub async fn get_system(
+    auth_session: AuthSession,
+    Path(uuid): Path<Uuid>,
+    Query(query): Query<GetSystemQuery>,
+) -> impl IntoResponse {
+    if let Err(response) = validate_paging(query.page, query.list_size) {
+        return response.into_response();
+    }
+
+    let db = &auth_session.backend.db;
+
+    let db_system = match sqlx::query_as!(
+        SystemRecord,
+        r#"
+        SELECT id, name, user_id, frequency, starts_at, deleted, down_after, down_sent_email, visibility AS "visibility: Visibility",
+               schedule_kind AS "schedule_kind: ScheduleKind", cron_expression, cron_timezone,
+               grace_early, grace_late, organization_id
+        FROM system WHERE id = $1
+        "#,
+        uuid
+    )
+    .fetch_optional(db)
+    .await
+    {
+        Ok(Some(r)) => r,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let Ok(owner_email) = sqlx::query_scalar!(
+        r#"SELECT email FROM "user" WHERE id = $1"#,
+        db_system.user_id
+    )
+    .fetch_one(db)
+    .await
+    else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    let deleted = db_system.deleted;
+
+    let system_data =
+        match SystemData::fetch_from_db(db, query.list_size, query.page, db_system).await {
+            Ok(system_data) => system_data,
+            Err(response) => return response,
+        };
+
+    Sonic(GetSystemResponse {
+        system: system_data,
+        owner_email,
+        deleted,
+    })
+    .into_response()
+}
@@ -0,0 +1,118 @@
+use axum::{extract::Query, response::IntoResponse};
+use axum_serde::Sonic;
+use chrono::{DateTime, Utc};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{app::openapi::ADMIN_TAG, users::AuthSession, web::utils::paging::page_offset};
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListUsersResponse {
+    /// The users of the instance, ordered by email
+    users: Vec<UserData>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserData {
+    /// The ID of the user
+    id: i32,
+    /// The email of the user
+    email: String,
+    /// Whether the user administers the instance
+    is_admin: bool,
+    /// The time at which the account was disabled, missing if it is enabled
+    disabled_at: Option<DateTime<Utc>>,
+    /// The time at which the email address was verified, missing if it was not
+    email_verified_at: Option<DateTime<Utc>>,
+    /// The number of (not deleted) personal systems of the user
+    systems: i64,
+}
+
+pub const LIMIT_USER_REQUEST: i64 = 100;
+
+#[derive(Debug, Deserialize, Clone, IntoParams)]
+pub struct ListUsersQuery {
+    /// Only return the users whose email contains this text (case-insensitive)
+    pub search: Option<String>,
+    /// The page number to return
+    pub page: i64,
+    /// The maximum number of users to return
+    pub list_size: i64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/list_users",
+    params(ListUsersQuery),
+    summary = "List Users",
+    description = "List or search the users of the instance",
+    responses(
+        (status = OK, description = "List of users", body = ListUsersResponse),
+        (status = BAD_REQUEST, description = "Bad request"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = FORBIDDEN, description = "User is not an admin"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = ADMIN_TAG
+)]
+pub async fn list_users(
+    auth_session: AuthSession,
+    Query(query): Query<ListUsersQuery>,
+) -> impl IntoResponse {
+    let offset = match page_offset(query.page, query.list_size, LIMIT_USER_REQUEST) {
+        Ok(offset) => offset,
+        Err(response) => return response.into_response(),
+    };
+
+    // The wildcards of LIKE are escaped, so that the search is literal
+    let search = query.search.map(|search| {
+        let search = search
+            .trim()
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        format!("%{search}%")
+    });
+
+    let users = match sqlx::query!(
+        r#"
+        SELECT u.id,
+               u.email,
+               u.is_admin,
+               u.disabled_at,
+               u.email_verified_at,
+               (SELECT COUNT(*) FROM system s WHERE s.user_id = u.id AND s.deleted = FALSE) AS "systems!"
+        FROM "user" u
+        WHERE $1::text IS NULL OR u.email ILIKE $1
+        ORDER BY u.email
+        LIMIT $2 OFFSET $3
+        "#,
+        search,
+        query.list_size,
+        offset
+    )
+    .fetch_all(&auth_session.backend.db)
+    .await
+    {
+        Ok(users) => users,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let users = users
+        .into_iter()
+        .map(|user| UserData {
+            id: user.id,
+            email: user.email,
+            is_admin: user.is_admin,
+            disabled_at: user.disabled_at.map(|time| time.and_utc()),
+            email_verified_at: user.email_verified_at.map(|time| time.and_utc()),
+            systems: user.systems,
+        })
+        .collect();
+
+    Sonic(ListUsersResponse { users }).into_response()
+}
@@ -0,0 +1,234 @@
+mod force_password_reset;
+mod get_instance_stats;
+mod get_system;
+mod list_users;
+mod set_admin;
+mod set_disabled;
+
+use axum::{
+    extract::Request,
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+};
+use http::StatusCode;
+use sqlx::PgPool;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::users::AuthSession;
+
+pub fn router() -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes![list_users::list_users])
+        .routes(routes![set_disabled::set_disabled])
+        .routes(routes![set_admin::set_admin])
+        .routes(routes![force_password_reset::force_password_reset])
+        .routes(routes![get_system::get_system])
+        .routes(routes![get_instance_stats::get_instance_stats])
+        .route_layer(middleware::from_fn(require_admin))
+}
+
+/// Rejects the requests of the users that are not admins of the instance
+async fn require_admin(auth_session: AuthSession, request: Request, next: Next) -> Response {
+    match auth_session.user {
+        Some(user) if user.is_admin => next.run(request).await,
+        Some(_) => (StatusCode::FORBIDDEN, "You are not an admin.").into_response(),
+        None => StatusCode::UNAUTHORIZED.into_response(),
+    }
+}
+
+/// Makes admins the users with the emails (from ADMIN_EMAILS), so that the
+/// first admin does not have to be set in the database by hand
+pub async fn promote_admins(db: &PgPool, emails: &[String]) -> Result<u64, sqlx::Error> {
+    if emails.is_empty() {
+        return Ok(0);
+    }
+
+    let promoted = sqlx::query!(
+        r#"
+        UPDATE "user"
+        SET is_admin = TRUE
+        WHERE LOWER(email) = ANY ($1)
+          AND is_admin = FALSE
+        "#,
+        &emails
+            .iter()
+            .map(|email| email.trim().to_lowercase())
+            .collect::<Vec<_>>()
+    )
+    .execute(db)
+    .await?;
+
+    Ok(promoted.rows_affected())
+}
+
+#[cfg(test)]
+mod test {
+    use http::header;
+    use password_auth::generate_hash;
+    use sonic_rs::{JsonContainerTrait, Value, json};
+
+    use crate::app::test_app::TestApp;
+
+    use super::*;
+
+    async fn get(app: &TestApp, cookie: &str, path: &str) -> (StatusCode, Value) {
+        let response = app
+            .client
+            .get(app.url(path))
+            .header(header::COOKIE, cookie)
+            .send()
+            .await
+            .unwrap();
+        let status = response.status();
+        let text = response.text().await.unwrap();
+
+        (status, sonic_rs::from_str(&text).unwrap_or_default())
+    }
+
+    async fn post(app: &TestApp, cookie: &str, path: &str, body: Value) -> StatusCode {
+        app.client
+            .post(app.url(path))
+            .header(header::COOKIE, cookie)
+            .json(&body)
+            .send()
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a PostgreSQL database (set DATABASE_URL)"]
+    async fn test_admin(db: PgPool) {
+        let app = TestApp::spawn(db).await;
+        let admin_id = app.create_user("admin@example.com").await;
+        let user_id = app.create_user("alice@example.com").await;
+        let system_id = app.create_system(user_id, "Backups").await;
+
+        sqlx::query!(
+            r#"UPDATE "user" SET password = $2 WHERE id = $1"#,
+            user_id,
+            generate_hash("hunter42")
+        )
+        .execute(&app.db)
+        .await
+        .unwrap();
+
+        assert_eq!(
+            promote_admins(&app.db, &[" Admin@Example.com".to_string()])
+                .await
+                .unwrap(),
+            1
+        );
+
+        let admin_cookie = app.login(admin_id).await;
+        let user_cookie = app.login(user_id).await;
+
+        let (status, _) = get(&app, &user_cookie, "/admin/get_instance_stats").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, stats) = get(&app, &admin_cookie, "/admin/get_instance_stats").await;
+        assert_eq!(status, StatusCode::OK);
+        // The user seeded by the first migration was removed
+        assert_eq!(stats["users"], 2);
+        assert_eq!(stats["systems"], 1);
+
+        let (status, response) = get(
+            &app,
+            &admin_cookie,
+            "/admin/list_users?search=ALICE&page=0&list_size=10",
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let users = response["users"].as_array().unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0]["email"], "alice@example.com");
+        assert_eq!(users[0]["systems"], 1);
+
+        // The admin can see the systems of the other users
+        let (status, response) = get(
+            &app,
+            &admin_cookie,
+            &format!("/admin/get_system/{system_id}?page=0&list_size=1"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(response["system"]["name"], "Backups");
+        assert_eq!(response["owner_email"], "alice@example.com");
+
+        // The admin cannot lock themselves out
+        let status = post(
+            &app,
+            &admin_cookie,
+            "/admin/set_disabled",
+            json!({ "user_id": admin_id, "disabled": true }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let status = post(
+            &app,
+            &admin_cookie,
+            "/admin/set_disabled",
+            json!({ "user_id": user_id, "disabled": true }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        // The sessions of the disabled user stop working, and it cannot log in
+        let (status, _) = get(&app, &user_cookie, "/user/get_current_settings").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let response = app
+            .client
+            .post(app.url("/login"))
+            .json(&json!({ "email": "alice@example.com", "password": "hunter42" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let status = post(
+            &app,
+            &admin_cookie,
+            "/admin/set_disabled",
+            json!({ "user_id": user_id, "disabled": false }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Forcing a password reset clears the password, so that the user has to
+        // set a new one through the link
+        let status = post(
+            &app,
+            &admin_cookie,
+            "/admin/force_password_reset",
+            json!({ "user_id": user_id }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let password = sqlx::query_scalar!(r#"SELECT password FROM "user" WHERE id = $1"#, user_id)
+            .fetch_one(&app.db)
+            .await
+            .unwrap();
+        assert!(password.is_none());
+
+        let status = post(
+            &app,
+            &admin_cookie,
+            "/admin/set_admin",
+            json!({ "user_id": user_id, "is_admin": true }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let status = post(
+            &app,
+            &admin_cookie,
+            "/admin/set_admin",
+            json!({ "user_id": 0, "is_admin": true }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
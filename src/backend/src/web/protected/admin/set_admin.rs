@@ -0,0 +1,82 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::Deserialize;
+use sonic_rs::json;
+use utoipa::ToSchema;
+
+use crate::{
+    app::openapi::ADMIN_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SetAdminRequest {
+    /// The ID of the user to promote or demote
+    user_id: i32,
+    /// Whether the user administers the instance
+    is_admin: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/set_admin",
+    summary = "Grant or Revoke Admin",
+    description = "Make a user an admin of the instance, or revoke it",
+    request_body = SetAdminRequest,
+    responses(
+        (status = OK, description = "Admin was granted or revoked successfully"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = FORBIDDEN, description = "User is not an admin"),
+        (status = NOT_FOUND, description = "User not found"),
+        (status = CONFLICT, description = "Admins cannot revoke their own admin"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = ADMIN_TAG
+)]
+pub async fn set_admin(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<SetAdminRequest>,
+) -> impl IntoResponse {
+    let Some(admin) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    // So that the instance always has an admin left
+    if !request.is_admin && request.user_id == admin.id {
+        return (StatusCode::CONFLICT, "You cannot revoke your own admin.").into_response();
+    }
+
+    match sqlx::query!(
+        r#"UPDATE "user" SET is_admin = $2 WHERE id = $1"#,
+        request.user_id,
+        request.is_admin
+    )
+    .execute(&auth_session.backend.db)
+    .await
+    {
+        Ok(result) if result.rows_affected() == 0 => {
+            return StatusCode::NOT_FOUND.into_response();
+        }
+        Ok(_) => {}
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+
+    let action = if request.is_admin {
+        AuditAction::AdminGranted
+    } else {
+        AuditAction::AdminRevoked
+    };
+    audit
+        .event(action, AuditTarget::User, request.user_id)
+        .after(json!({ "is_admin": request.is_admin }))
+        .record()
+        .await;
+
+    StatusCode::OK.into_response()
+}
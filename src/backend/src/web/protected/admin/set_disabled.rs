@@ -0,0 +1,105 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::Deserialize;
+use sonic_rs::json;
+use utoipa::ToSchema;
+
+use crate::{
+    app::openapi::ADMIN_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    middleware::track_session::forget_user_sessions,
+    users::AuthSession,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SetDisabledRequest {
+    /// The ID of the user to disable or enable
+    user_id: i32,
+    /// Whether the account is disabled
+    disabled: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/set_disabled",
+    summary = "Disable or Enable User",
+    description = "Disable the account of a user, which is logged out everywhere and cannot log in anymore, or enable it again",
+    request_body = SetDisabledRequest,
+    responses(
+        (status = OK, description = "Account was disabled or enabled successfully"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = FORBIDDEN, description = "User is not an admin"),
+        (status = NOT_FOUND, description = "User not found"),
+        (status = CONFLICT, description = "Admins cannot disable their own account"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = ADMIN_TAG
+)]
+pub async fn set_disabled(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<SetDisabledRequest>,
+) -> impl IntoResponse {
+    let Some(admin) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    if request.disabled && request.user_id == admin.id {
+        return (StatusCode::CONFLICT, "You cannot disable your own account.").into_response();
+    }
+
+    let db = &auth_session.backend.db;
+
+    let Ok(mut tx) = db.begin().await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    match sqlx::query!(
+        r#"
+        UPDATE "user"
+        SET disabled_at = CASE WHEN $2 THEN COALESCE(disabled_at, NOW()) END
+        WHERE id = $1
+        "#,
+        request.user_id,
+        request.disabled
+    )
+    .execute(&mut *tx)
+    .await
+    {
+        Ok(result) if result.rows_affected() == 0 => {
+            return StatusCode::NOT_FOUND.into_response();
+        }
+        Ok(_) => {}
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+
+    // The sessions already stop working, as disabled users are not loaded
+    if request.disabled
+        && forget_user_sessions(&mut *tx, request.user_id)
+            .await
+            .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    if tx.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    let action = if request.disabled {
+        AuditAction::UserDisabled
+    } else {
+        AuditAction::UserEnabled
+    };
+    audit
+        .event(action, AuditTarget::User, request.user_id)
+        .after(json!({ "disabled": request.disabled }))
+        .record()
+        .await;
+
+    StatusCode::OK.into_response()
+}
@@ -1,12 +1,16 @@
 use axum::response::IntoResponse;
-use axum_serde::Sonic;
 use http::StatusCode;
 use serde::Deserialize;
+use sonic_rs::json;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::{
-    app::openapi::SYSTEM_TAG, users::AuthSession, web::protected::list_systems::Visibility,
+    app::openapi::SYSTEM_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    models::Visibility,
+    users::AuthSession,
+    web::utils::system_access::{EditableSystem, SystemRequest},
 };
 
 #[derive(Debug, Deserialize, ToSchema)]
@@ -17,6 +21,12 @@ pub struct ChangeVisibilityRequest {
     visibility: Visibility,
 }
 
+impl SystemRequest for ChangeVisibilityRequest {
+    fn system_id(&self) -> Uuid {
+        self.id
+    }
+}
+
 #[utoipa::path(
     patch,
     path = "/change_visibility",
@@ -26,34 +36,49 @@ pub struct ChangeVisibilityRequest {
     responses(
         (status = OK, description = "Visibility was changed successfully"),
         (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = FORBIDDEN, description = "User can only view the system"),
+        (status = NOT_FOUND, description = "System not found"),
         (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
     ),
     security(
-        ("session" = [])
+        ("session" = []),
+        ("api_token" = [])
     ),
     tag = SYSTEM_TAG
 )]
 pub async fn change_visibility(
     auth_session: AuthSession,
-    Sonic(request): Sonic<ChangeVisibilityRequest>,
+    audit: Audit,
+    EditableSystem(request): EditableSystem<ChangeVisibilityRequest>,
 ) -> impl IntoResponse {
-    if auth_session.user.is_none() {
-        return StatusCode::UNAUTHORIZED.into_response();
-    }
-
-    if sqlx::query!(
+    let Ok(system) = sqlx::query!(
         r#"
-        UPDATE system SET visibility = ($1) WHERE id = $2  
+        UPDATE system s
+        SET visibility = ($1)
+        FROM (SELECT id, visibility FROM system WHERE id = $2 FOR UPDATE) old
+        WHERE s.id = old.id
+        RETURNING old.visibility AS "old_visibility: Visibility", s.organization_id
         "#,
-        request.visibility as Visibility,
+        request.visibility.clone() as Visibility,
         request.id,
     )
-    .execute(&auth_session.backend.db)
+    .fetch_one(&auth_session.backend.db)
     .await
-    .is_err()
-    {
+    else {
         return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     };
 
+    audit
+        .event(
+            AuditAction::SystemVisibilityChanged,
+            AuditTarget::System,
+            request.id,
+        )
+        .organization(system.organization_id)
+        .before(json!({ "visibility": system.old_visibility }))
+        .after(json!({ "visibility": request.visibility }))
+        .record()
+        .await;
+
     StatusCode::OK.into_response()
 }
@@ -1,11 +1,16 @@
 use axum::response::IntoResponse;
-use axum_serde::Sonic;
 use http::StatusCode;
 use serde::Deserialize;
+use sonic_rs::json;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
-use crate::{app::openapi::SYSTEM_TAG, users::AuthSession};
+use crate::{
+    app::openapi::SYSTEM_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+    web::utils::system_access::{EditableSystem, SystemRequest},
+};
 
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct DeleteSystemRequest {
@@ -13,6 +18,12 @@ pub struct DeleteSystemRequest {
     id: Uuid,
 }
 
+impl SystemRequest for DeleteSystemRequest {
+    fn system_id(&self) -> Uuid {
+        self.id
+    }
+}
+
 #[utoipa::path(
     delete,
     path = "/delete_system",
@@ -21,33 +32,40 @@ pub struct DeleteSystemRequest {
     responses(
         (status = OK, description = "System was deleted successfully"),
         (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = FORBIDDEN, description = "User can only view the system"),
+        (status = NOT_FOUND, description = "System not found"),
         (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
     ),
     security(
-        ("session" = [])
+        ("session" = []),
+        ("api_token" = [])
     ),
     tag = SYSTEM_TAG
 )]
 pub async fn delete_system(
     auth_session: AuthSession,
-    Sonic(request): Sonic<DeleteSystemRequest>,
+    audit: Audit,
+    EditableSystem(request): EditableSystem<DeleteSystemRequest>,
 ) -> impl IntoResponse {
-    if auth_session.user.is_none() {
-        return StatusCode::UNAUTHORIZED.into_response();
-    }
-
-    if sqlx::query!(
+    let Ok(system) = sqlx::query!(
         r#"
-        UPDATE system SET deleted = true WHERE id = $1  
+        UPDATE system SET deleted = true WHERE id = $1
+        RETURNING name, organization_id
         "#,
         request.id,
     )
-    .execute(&auth_session.backend.db)
+    .fetch_one(&auth_session.backend.db)
     .await
-    .is_err()
-    {
+    else {
         return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     };
 
+    audit
+        .event(AuditAction::SystemDeleted, AuditTarget::System, request.id)
+        .organization(system.organization_id)
+        .before(json!({ "name": system.name }))
+        .record()
+        .await;
+
     StatusCode::OK.into_response()
 }
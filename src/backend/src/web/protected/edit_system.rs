@@ -0,0 +1,432 @@
+use axum::{Extension, extract::Path, response::IntoResponse};
+use axum_serde::Sonic;
+use chrono::{DateTime, Utc};
+use http::StatusCode;
+use serde::{Deserialize, Deserializer};
+use sonic_rs::{Value, json};
+use sqlx::postgres::types::PgInterval;
+use tracing::error;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    app::openapi::SYSTEM_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    middleware::api_token::TokenScope,
+    models::{ScheduleKind, Visibility},
+    users::AuthSession,
+    web::{
+        protected::{
+            add_system::{validate_down_after, validate_grace, validate_schedule},
+            list_systems::SystemRecord,
+            organizations::OrganizationRole,
+        },
+        utils::{system_access::ensure_system_role, time_conversions::pg_interval_to_duration},
+    },
+    workers::deadlines::schedule_deadline,
+};
+
+/// The fields that are missing are left unchanged, the schedule is validated
+/// as a whole (with the new fields and the current ones)
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct EditSystemRequest {
+    /// The new name of the system
+    name: Option<String>,
+    /// The new kind of schedule the system follows
+    schedule_kind: Option<ScheduleKind>,
+    /// The new frequency in minutes of the pings (for interval schedules)
+    frequency: Option<i64>,
+    /// The new cron expression of the pings (for cron schedules)
+    cron_expression: Option<String>,
+    /// The new timezone in which the cron expression is evaluated (for cron
+    /// schedules, defaults to the timezone of the user if the system had none)
+    cron_timezone: Option<String>,
+    /// The new time at which the system starts pinging
+    starts_at: Option<DateTime<Utc>>,
+    /// The new time in minutes after which the user will get emailed
+    down_after: Option<i64>,
+    /// The new grace in minutes, used for both sides unless `grace_early` or
+    /// `grace_late` are given
+    grace: Option<i64>,
+    /// How many minutes before the expected time a ping still counts
+    grace_early: Option<i64>,
+    /// How many minutes after the expected time a ping still counts, `null`
+    /// makes the pings count until the next expected time
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<i64>)]
+    grace_late: Option<Option<i64>>,
+    /// The new visibility of the system
+    visibility: Option<Visibility>,
+}
+
+// Tells a field set to null (`Some(None)`) apart from a missing one (`None`)
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+#[utoipa::path(
+    patch,
+    path = "/system/{id}",
+    request_body = EditSystemRequest,
+    summary = "Edit System",
+    description = "Edit the properties of a system, the schedule it followed before the edit is kept to compute the past instants",
+    responses(
+        (status = OK, description = "System was edited successfully"),
+        (status = BAD_REQUEST, description = "Bad request"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = FORBIDDEN, description = "User can only view the system"),
+        (status = NOT_FOUND, description = "System not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = []),
+        ("api_token" = [])
+    ),
+    tag = SYSTEM_TAG
+)]
+pub async fn edit_system(
+    auth_session: AuthSession,
+    audit: Audit,
+    scope: Option<Extension<TokenScope>>,
+    Path(id): Path<Uuid>,
+    Sonic(request): Sonic<EditSystemRequest>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    // API tokens restricted to other systems cannot tell that the system exists
+    if scope.is_some_and(|scope| !scope.allows_system(id)) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let db = &auth_session.backend.db;
+
+    if let Err(response) = ensure_system_role(db, user.id, id, OrganizationRole::Editor).await {
+        return response;
+    }
+
+    let Ok(mut tx) = db.begin().await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    let system = match sqlx::query_as!(
+        SystemRecord,
+        r#"
+        SELECT id,
+               name,
+               user_id,
+               frequency,
+               starts_at,
+               deleted,
+               down_after,
+               down_sent_email,
+               visibility AS "visibility: Visibility",
+               schedule_kind AS "schedule_kind: ScheduleKind",
+               cron_expression,
+               cron_timezone,
+               grace_early,
+               grace_late,
+               organization_id
+        FROM system
+        WHERE id = $1 AND deleted = FALSE
+        FOR UPDATE
+        "#,
+        id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    {
+        Ok(Some(system)) => system,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let schedule_kind = request.schedule_kind.unwrap_or(system.schedule_kind);
+
+    let (frequency, cron_expression, cron_timezone) = match validate_schedule(
+        schedule_kind,
+        request.frequency.or(system.frequency.map(interval_minutes)),
+        request
+            .cron_expression
+            .or_else(|| system.cron_expression.clone()),
+        request
+            .cron_timezone
+            .or_else(|| system.cron_timezone.clone())
+            .unwrap_or(user.timezone),
+    ) {
+        Ok(schedule) => schedule,
+        Err(response) => return response.into_response(),
+    };
+
+    let starts_at = request
+        .starts_at
+        .map(|starts_at| starts_at.naive_utc())
+        .unwrap_or(system.starts_at);
+
+    let down_after = match request.down_after.map(validate_down_after).transpose() {
+        Ok(down_after) => down_after.unwrap_or(system.down_after),
+        Err(response) => return response.into_response(),
+    };
+
+    let (grace_early, grace_late) = match validate_grace(
+        request
+            .grace_early
+            .or(request.grace)
+            .unwrap_or(interval_minutes(system.grace_early)),
+        request
+            .grace_late
+            .unwrap_or(request.grace.or(system.grace_late.map(interval_minutes))),
+        frequency,
+    ) {
+        Ok(grace) => grace,
+        Err(response) => return response.into_response(),
+    };
+
+    // The replaced schedule is kept (if it changed), so that the past instants
+    // are still computed against it
+    if sqlx::query!(
+        r#"
+        INSERT INTO schedule_version (system_id, schedule_kind, frequency, cron_expression,
+                                      cron_timezone, starts_at, grace_early, grace_late)
+        SELECT id, schedule_kind, frequency, cron_expression, cron_timezone, starts_at,
+               grace_early, grace_late
+        FROM system
+        WHERE id = $1
+          AND (schedule_kind, frequency, cron_expression, cron_timezone, starts_at, grace_early,
+               grace_late)
+              IS DISTINCT FROM ($2, $3, $4, $5, $6, $7, $8)
+        "#,
+        id,
+        schedule_kind as ScheduleKind,
+        frequency,
+        cron_expression,
+        cron_timezone,
+        starts_at,
+        grace_early,
+        grace_late
+    )
+    .execute(&mut *tx)
+    .await
+    .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    let Ok(edited) = sqlx::query_as!(
+        SystemRecord,
+        r#"
+        UPDATE system
+        SET name = $2,
+            schedule_kind = $3,
+            frequency = $4,
+            cron_expression = $5,
+            cron_timezone = $6,
+            starts_at = $7,
+            down_after = $8,
+            grace_early = $9,
+            grace_late = $10,
+            visibility = $11
+        WHERE id = $1
+        RETURNING id,
+                  name,
+                  user_id,
+                  frequency,
+                  starts_at,
+                  deleted,
+                  down_after,
+                  down_sent_email,
+                  visibility AS "visibility: Visibility",
+                  schedule_kind AS "schedule_kind: ScheduleKind",
+                  cron_expression,
+                  cron_timezone,
+                  grace_early,
+                  grace_late,
+                  organization_id
+        "#,
+        id,
+        request.name.unwrap_or_else(|| system.name.clone()),
+        schedule_kind as ScheduleKind,
+        frequency,
+        cron_expression,
+        cron_timezone,
+        starts_at,
+        down_after,
+        grace_early,
+        grace_late,
+        request.visibility.unwrap_or(system.visibility.clone()) as Visibility
+    )
+    .fetch_one(&mut *tx)
+    .await
+    else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    if tx.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    // The schedule and down_after move the time at which the system is down
+    if let Err(e) = schedule_deadline(&auth_session.backend.redis, db, id).await {
+        error!("Error scheduling the deadline of system {}: {}", id, e);
+    }
+
+    audit
+        .event(AuditAction::SystemEdited, AuditTarget::System, id)
+        .organization(edited.organization_id)
+        .before(audited_properties(&system))
+        .after(audited_properties(&edited))
+        .record()
+        .await;
+
+    StatusCode::OK.into_response()
+}
+
+fn interval_minutes(interval: PgInterval) -> i64 {
+    pg_interval_to_duration(interval).num_minutes()
+}
+
+// The editable properties of the system, as recorded in the audit trail
+fn audited_properties(system: &SystemRecord) -> Value {
+    json!({
+        "name": system.name,
+        "schedule_kind": system.schedule_kind,
+        "frequency": system.frequency.map(interval_minutes),
+        "cron_expression": system.cron_expression,
+        "cron_timezone": system.cron_timezone,
+        "starts_at": system.starts_at.and_utc(),
+        "down_after": interval_minutes(system.down_after),
+        "grace_early": interval_minutes(system.grace_early),
+        "grace_late": system.grace_late.map(interval_minutes),
+        "visibility": system.visibility,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use http::header;
+    use sonic_rs::{JsonContainerTrait, JsonValueTrait};
+    use sqlx::PgPool;
+
+    use crate::app::test_app::TestApp;
+
+    use super::*;
+
+    #[sqlx::test]
+    #[ignore = "needs a PostgreSQL database (set DATABASE_URL)"]
+    async fn test_edit_system(db: PgPool) {
+        let app = TestApp::spawn(db).await;
+        let user_id = app.create_user("owner@example.com").await;
+        let other_id = app.create_user("other@example.com").await;
+        let system_id = app.create_system(user_id, "Backups").await;
+        let cookie = app.login(user_id).await;
+
+        sqlx::query!(
+            r#"UPDATE system SET starts_at = NOW() - INTERVAL '12 hours' WHERE id = $1"#,
+            system_id
+        )
+        .execute(&app.db)
+        .await
+        .unwrap();
+
+        let edit = |cookie: &str, body: Value| {
+            app.client
+                .patch(app.url(&format!("/system/{system_id}")))
+                .header(header::COOKIE, cookie)
+                .json(&body)
+                .send()
+        };
+
+        // The schedule is validated along with the current fields
+        let response = edit(&cookie, json!({ "schedule_kind": "cron" }))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = edit(&cookie, json!({ "frequency": 0 })).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = edit(&cookie, json!({ "down_after": 0 })).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        // The grace is checked against the new frequency
+        let response = edit(&cookie, json!({ "frequency": 30, "grace_late": 30 }))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let other_cookie = app.login(other_id).await;
+        let response = edit(&other_cookie, json!({ "frequency": 30 }))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = edit(&cookie, json!({ "frequency": 30, "down_after": 90 }))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Only the changes of the schedule are kept as versions
+        let response = edit(&cookie, json!({ "name": "Nightly backups" }))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let versions = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM schedule_version WHERE system_id = $1"#,
+            system_id
+        )
+        .fetch_one(&app.db)
+        .await
+        .unwrap();
+        assert_eq!(versions, 1);
+
+        // Moves the edit back in time, to see both schedules in the instants
+        sqlx::query!(
+            r#"
+            UPDATE schedule_version SET valid_until = NOW() - INTERVAL '3 hours'
+            WHERE system_id = $1
+            "#,
+            system_id
+        )
+        .execute(&app.db)
+        .await
+        .unwrap();
+
+        let response = app
+            .client
+            .get(app.url("/list_systems?page=0&list_size=10"))
+            .header(header::COOKIE, &cookie)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body: Value = sonic_rs::from_str(&response.text().await.unwrap()).unwrap();
+        let system = &body["systems"][0];
+        assert_eq!(system["name"], "Nightly backups");
+        assert_eq!(system["frequency"], 30);
+
+        let expected: Vec<DateTime<Utc>> = system["instants"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|instant| {
+                instant["expected_timestamp"]
+                    .as_str()
+                    .unwrap()
+                    .parse()
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(expected.len(), 10);
+
+        let gaps: Vec<_> = expected
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]).num_minutes())
+            .collect();
+        assert_eq!(gaps[0], 60);
+        assert_eq!(gaps[gaps.len() - 1], 30);
+    }
+}
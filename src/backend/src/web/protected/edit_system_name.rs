@@ -1,11 +1,16 @@
 use axum::response::IntoResponse;
-use axum_serde::Sonic;
 use http::StatusCode;
 use serde::Deserialize;
+use sonic_rs::json;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
-use crate::{app::openapi::SYSTEM_TAG, users::AuthSession};
+use crate::{
+    app::openapi::SYSTEM_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+    web::utils::system_access::{EditableSystem, SystemRequest},
+};
 
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct EditSystemNameRequest {
@@ -15,6 +20,12 @@ pub struct EditSystemNameRequest {
     name: String,
 }
 
+impl SystemRequest for EditSystemNameRequest {
+    fn system_id(&self) -> Uuid {
+        self.id
+    }
+}
+
 #[utoipa::path(
     patch,
     path = "/edit_system_name",
@@ -23,34 +34,45 @@ pub struct EditSystemNameRequest {
     responses(
         (status = OK, description = "System name was edited successfully"),
         (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = FORBIDDEN, description = "User can only view the system"),
+        (status = NOT_FOUND, description = "System not found"),
         (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
     ),
     security(
-        ("session" = [])
+        ("session" = []),
+        ("api_token" = [])
     ),
     tag = SYSTEM_TAG
 )]
 pub async fn edit_system_name(
     auth_session: AuthSession,
-    Sonic(request): Sonic<EditSystemNameRequest>,
+    audit: Audit,
+    EditableSystem(request): EditableSystem<EditSystemNameRequest>,
 ) -> impl IntoResponse {
-    if auth_session.user.is_none() {
-        return StatusCode::UNAUTHORIZED.into_response();
-    }
-
-    if sqlx::query!(
+    let Ok(system) = sqlx::query!(
         r#"
-        UPDATE system SET name = ($1) WHERE id = $2  
+        UPDATE system s
+        SET name = ($1)
+        FROM (SELECT id, name FROM system WHERE id = $2 FOR UPDATE) old
+        WHERE s.id = old.id
+        RETURNING old.name AS old_name, s.organization_id
         "#,
         request.name,
         request.id,
     )
-    .execute(&auth_session.backend.db)
+    .fetch_one(&auth_session.backend.db)
     .await
-    .is_err()
-    {
+    else {
         return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     };
 
+    audit
+        .event(AuditAction::SystemRenamed, AuditTarget::System, request.id)
+        .organization(system.organization_id)
+        .before(json!({ "name": system.old_name }))
+        .after(json!({ "name": request.name }))
+        .record()
+        .await;
+
     StatusCode::OK.into_response()
 }
@@ -0,0 +1,119 @@
+use axum::{Extension, extract::Path, response::IntoResponse};
+use axum_serde::Sonic;
+use chrono::{DateTime, Utc};
+use http::StatusCode;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    app::openapi::INCIDENT_TAG,
+    middleware::api_token::{TokenScope, scoped_system_ids},
+    users::AuthSession,
+    web::protected::list_incidents::{IncidentData, IncidentRecord},
+};
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GetIncidentResponse {
+    /// The incident
+    incident: IncidentData,
+    /// The notifications sent about the incident, the oldest first
+    notifications: Vec<IncidentNotification>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct IncidentNotification {
+    /// The event the notification was about (`down` or `up`)
+    event: String,
+    /// The kind of channel the notification was sent through (such as `email`
+    /// or `slack`)
+    channel: String,
+    /// The time at which the notification was sent
+    sent_at: DateTime<Utc>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/get_incident/{id}",
+    summary = "Get Incident",
+    description = "Retrieve an incident along with the notifications sent about it",
+    responses(
+        (status = OK, description = "Incident was retrieved successfully", body = GetIncidentResponse),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = NOT_FOUND, description = "Incident not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = []),
+        ("api_token" = [])
+    ),
+    tag = INCIDENT_TAG
+)]
+pub async fn get_incident(
+    auth_session: AuthSession,
+    scope: Option<Extension<TokenScope>>,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let incident = match sqlx::query_as!(
+        IncidentRecord,
+        r#"
+        SELECT i.id,
+               i.system_id,
+               s.name AS system_name,
+               i.started_at,
+               i.ended_at,
+               i.duration,
+               (SELECT COUNT(*) FROM incident_notification n WHERE n.incident_id = i.id)
+                   AS "notifications_sent!"
+        FROM incident i
+            JOIN system s ON i.system_id = s.id
+        WHERE i.id = $1
+          AND s.id IN (SELECT visible_system_ids($2))
+          AND ($3::uuid[] IS NULL OR i.system_id = ANY ($3))
+        "#,
+        id,
+        user.id,
+        scoped_system_ids(scope.as_deref())
+    )
+    .fetch_optional(&auth_session.backend.db)
+    .await
+    {
+        Ok(Some(incident)) => incident,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let notifications = match sqlx::query!(
+        r#"
+        SELECT event, channel, sent_at
+        FROM incident_notification
+        WHERE incident_id = $1
+        ORDER BY sent_at
+        "#,
+        id
+    )
+    .fetch_all(&auth_session.backend.db)
+    .await
+    {
+        Ok(notifications) => notifications,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let notifications = notifications
+        .into_iter()
+        .map(|notification| IncidentNotification {
+            event: notification.event,
+            channel: notification.channel,
+            sent_at: notification.sent_at.and_utc(),
+        })
+        .collect();
+
+    Sonic(GetIncidentResponse {
+        incident: incident.into(),
+        notifications,
+    })
+    .into_response()
+}
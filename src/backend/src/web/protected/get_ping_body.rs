@@ -0,0 +1,66 @@
+use axum::{Extension, extract::Path, response::IntoResponse};
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    app::openapi::SYSTEM_TAG,
+    middleware::api_token::{TokenScope, scoped_system_ids},
+    users::AuthSession,
+};
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GetPingBodyResponse {
+    /// The output of the job that was sent along with the ping
+    body: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/get_ping_body/{id}",
+    summary = "Retrieve Ping Body",
+    description = "Retrieve the output of the job that was sent along with a ping",
+    responses(
+        (status = OK, description = "Ping body was retrieved successfully", body = GetPingBodyResponse),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = NOT_FOUND, description = "Ping not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = []),
+        ("api_token" = [])
+    ),
+    tag = SYSTEM_TAG
+)]
+pub async fn get_ping_body(
+    auth_session: AuthSession,
+    scope: Option<Extension<TokenScope>>,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let ping = match sqlx::query!(
+        r#"
+        SELECT p.body
+        FROM ping p
+        WHERE p.id = $1
+          AND p.system_id IN (SELECT visible_system_ids($2))
+          AND ($3::uuid[] IS NULL OR p.system_id = ANY ($3))
+        "#,
+        id,
+        user.id,
+        scoped_system_ids(scope.as_deref())
+    )
+    .fetch_optional(&auth_session.backend.db)
+    .await
+    {
+        Ok(Some(ping)) => ping,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    Sonic(GetPingBodyResponse { body: ping.body }).into_response()
+}
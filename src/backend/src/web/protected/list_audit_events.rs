@@ -0,0 +1,336 @@
+use axum::{extract::Query, response::IntoResponse};
+use axum_serde::Sonic;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use sonic_rs::Value;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    app::openapi::AUDIT_TAG,
+    audit::{AuditAction, AuditTarget},
+    users::AuthSession,
+    web::{
+        protected::organizations::{OrganizationRole, ensure_member_role},
+        utils::paging::page_offset,
+    },
+};
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListAuditEventsResponse {
+    /// The events of the audit trail, the most recent first
+    events: Vec<AuditEventData>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AuditEventData {
+    /// The ID of the event
+    id: i64,
+    /// The ID of the user that made the change, missing if it was not logged in
+    actor_id: Option<i32>,
+    /// The email of the user that made the change, missing if the account was
+    /// deleted
+    actor_email: Option<String>,
+    /// The organization the target belongs to
+    organization_id: Option<i32>,
+    /// What was done
+    action: AuditAction,
+    /// The kind of resource the action was done on
+    target_type: AuditTarget,
+    /// The ID of the resource the action was done on
+    target_id: Option<String>,
+    /// The values changed by the action, before it
+    #[schema(value_type = Option<Object>)]
+    before: Option<Value>,
+    /// The values changed by the action, after it
+    #[schema(value_type = Option<Object>)]
+    after: Option<Value>,
+    /// The address the change was made from
+    ip_address: Option<String>,
+    /// The time at which the change was made
+    created_at: DateTime<Utc>,
+}
+
+// Records from the tables, the values are read as text as they are parsed with
+// sonic-rs
+#[derive(Debug, sqlx::FromRow)]
+pub struct AuditEventRecord {
+    pub id: i64,
+    pub actor_id: Option<i32>,
+    pub actor_email: Option<String>,
+    pub organization_id: Option<i32>,
+    pub action: AuditAction,
+    pub target_type: AuditTarget,
+    pub target_id: Option<String>,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<AuditEventRecord> for AuditEventData {
+    fn from(record: AuditEventRecord) -> Self {
+        AuditEventData {
+            id: record.id,
+            actor_id: record.actor_id,
+            actor_email: record.actor_email,
+            organization_id: record.organization_id,
+            action: record.action,
+            target_type: record.target_type,
+            target_id: record.target_id,
+            before: record
+                .before
+                .and_then(|before| sonic_rs::from_str(&before).ok()),
+            after: record
+                .after
+                .and_then(|after| sonic_rs::from_str(&after).ok()),
+            ip_address: record.ip_address,
+            created_at: record.created_at.and_utc(),
+        }
+    }
+}
+
+pub const LIMIT_AUDIT_EVENT_REQUEST: i64 = 100;
+
+#[derive(Debug, Deserialize, Clone, IntoParams)]
+pub struct ListAuditEventsQuery {
+    /// Return the audit trail of this organization (the user must be one of its
+    /// admins) instead of the changes made by the user
+    pub organization_id: Option<i32>,
+    /// Only return the changes made by this user
+    pub actor_id: Option<i32>,
+    /// Only return the events of this action
+    pub action: Option<AuditAction>,
+    /// Only return the events about this kind of resource
+    pub target_type: Option<AuditTarget>,
+    /// Only return the events about the resource with this ID
+    pub target_id: Option<String>,
+    /// Only return the events from this time on
+    pub since: Option<DateTime<Utc>>,
+    /// Only return the events before this time
+    pub until: Option<DateTime<Utc>>,
+    /// The page number to return
+    pub page: i64,
+    /// The maximum number of events to return
+    pub list_size: i64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/list_audit_events",
+    params(ListAuditEventsQuery),
+    summary = "List Audit Events",
+    description = "List the audit trail of the changes made by the user, or of the changes to an organization and its systems",
+    responses(
+        (status = OK, description = "List of audit events", body = ListAuditEventsResponse),
+        (status = BAD_REQUEST, description = "Bad request"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = FORBIDDEN, description = "User is not an admin of the organization"),
+        (status = NOT_FOUND, description = "Organization not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = []),
+        ("api_token" = [])
+    ),
+    tag = AUDIT_TAG
+)]
+pub async fn list_audit_events(
+    auth_session: AuthSession,
+    Query(query): Query<ListAuditEventsQuery>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let offset = match page_offset(query.page, query.list_size, LIMIT_AUDIT_EVENT_REQUEST) {
+        Ok(offset) => offset,
+        Err(response) => return response.into_response(),
+    };
+
+    let db = &auth_session.backend.db;
+
+    if let Some(organization_id) = query.organization_id
+        && let Err(response) =
+            ensure_member_role(db, organization_id, user.id, OrganizationRole::Admin).await
+    {
+        return response;
+    }
+
+    let events = match sqlx::query_as!(
+        AuditEventRecord,
+        r#"
+        SELECT e.id,
+               e.actor_id,
+               u.email AS "actor_email?",
+               e.organization_id,
+               e.action AS "action: AuditAction",
+               e.target_type AS "target_type: AuditTarget",
+               e.target_id,
+               e.before::text AS before,
+               e.after::text AS after,
+               e.ip_address,
+               e.created_at
+        FROM audit_event e
+            LEFT JOIN "user" u ON u.id = e.actor_id
+        WHERE CASE
+                  WHEN $1::integer IS NULL THEN e.actor_id = $2
+                  ELSE e.organization_id = $1
+              END
+          AND ($3::integer IS NULL OR e.actor_id = $3)
+          AND ($4::audit_action IS NULL OR e.action = $4)
+          AND ($5::audit_target IS NULL OR e.target_type = $5)
+          AND ($6::text IS NULL OR e.target_id = $6)
+          AND ($7::timestamp IS NULL OR e.created_at >= $7)
+          AND ($8::timestamp IS NULL OR e.created_at < $8)
+        ORDER BY e.created_at DESC, e.id DESC
+        LIMIT $9 OFFSET $10
+        "#,
+        query.organization_id,
+        user.id,
+        query.actor_id,
+        query.action as Option<AuditAction>,
+        query.target_type as Option<AuditTarget>,
+        query.target_id,
+        query.since.map(|since| since.naive_utc()),
+        query.until.map(|until| until.naive_utc()),
+        query.list_size,
+        offset
+    )
+    .fetch_all(db)
+    .await
+    {
+        Ok(events) => events,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let events = events.into_iter().map(AuditEventData::from).collect();
+
+    Sonic(ListAuditEventsResponse { events }).into_response()
+}
+
+#[cfg(test)]
+mod test {
+    use http::header;
+    use sonic_rs::{JsonContainerTrait, JsonValueTrait, json};
+    use sqlx::PgPool;
+
+    use crate::app::test_app::TestApp;
+
+    use super::*;
+
+    async fn list(app: &TestApp, cookie: &str, query: &str) -> (StatusCode, Value) {
+        let response = app
+            .client
+            .get(app.url(&format!("/list_audit_events?page=0&list_size=100{query}")))
+            .header(header::COOKIE, cookie)
+            .send()
+            .await
+            .unwrap();
+        let status = response.status();
+        let text = response.text().await.unwrap();
+
+        (status, sonic_rs::from_str(&text).unwrap_or_default())
+    }
+
+    fn actions(response: &Value) -> Vec<String> {
+        response["events"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|event| event["action"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a PostgreSQL database (set DATABASE_URL)"]
+    async fn test_list_audit_events(db: PgPool) {
+        let app = TestApp::spawn(db).await;
+        let owner_id = app.create_user("owner@example.com").await;
+        let owner_cookie = app.login(owner_id).await;
+        let system_id = app.create_system(owner_id, "Backups").await;
+
+        let response = app
+            .client
+            .patch(app.url("/edit_system_name"))
+            .header(header::COOKIE, &owner_cookie)
+            .json(&json!({ "id": system_id, "name": "Nightly backups" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .client
+            .post(app.url("/organizations/create_organization"))
+            .header(header::COOKIE, &owner_cookie)
+            .json(&json!({ "name": "Acme" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let response: Value = sonic_rs::from_str(&response.text().await.unwrap()).unwrap();
+        let organization_id = response["id"].as_i64().unwrap();
+
+        let (status, response) = list(&app, &owner_cookie, "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            actions(&response),
+            ["organization_created", "system_renamed"]
+        );
+
+        let (_, response) = list(&app, &owner_cookie, "&action=system_renamed").await;
+        let event = &response["events"][0];
+        assert_eq!(actions(&response), ["system_renamed"]);
+        assert_eq!(event["actor_email"], "owner@example.com");
+        assert_eq!(event["target_type"], "system");
+        assert_eq!(event["target_id"], system_id.to_string());
+        assert_eq!(event["before"]["name"], "Backups");
+        assert_eq!(event["after"]["name"], "Nightly backups");
+
+        // Only the admins of the organization can read its audit trail
+        let viewer_id = app.create_user("viewer@example.com").await;
+        let viewer_cookie = app.login(viewer_id).await;
+        let query = format!("&organization_id={organization_id}");
+
+        let (status, _) = list(&app, &viewer_cookie, &query).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        sqlx::query!(
+            r#"
+            INSERT INTO organization_member (organization_id, user_id, role)
+            VALUES ($1, $2, 'viewer')
+            "#,
+            organization_id as i32,
+            viewer_id
+        )
+        .execute(&app.db)
+        .await
+        .unwrap();
+
+        let (status, _) = list(&app, &viewer_cookie, &query).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, response) = list(&app, &owner_cookie, &query).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(actions(&response), ["organization_created"]);
+
+        // The viewer did not change anything yet
+        let (_, response) = list(&app, &viewer_cookie, "").await;
+        assert!(actions(&response).is_empty());
+
+        // The events cannot be changed nor deleted
+        assert!(
+            sqlx::query!(r#"UPDATE audit_event SET actor_id = $1"#, viewer_id)
+                .execute(&app.db)
+                .await
+                .is_err()
+        );
+        assert!(
+            sqlx::query!(r#"DELETE FROM audit_event"#)
+                .execute(&app.db)
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use axum::{Extension, extract::Query, response::IntoResponse};
+use axum_serde::Sonic;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::types::PgInterval;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    app::openapi::INCIDENT_TAG,
+    middleware::api_token::{TokenScope, scoped_system_ids},
+    users::AuthSession,
+    web::utils::{paging::page_offset, time_conversions::pg_interval_to_duration},
+};
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListIncidentsResponse {
+    /// The incidents of the systems of the user, the most recent first
+    incidents: Vec<IncidentData>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct IncidentData {
+    /// The ID of the incident
+    id: i32,
+    /// The ID of the system that was down
+    system_id: Uuid,
+    /// The name of the system that was down
+    system_name: String,
+    /// The time since which the system was down
+    started_at: DateTime<Utc>,
+    /// The time at which the system was back up, missing while the incident is
+    /// open
+    ended_at: Option<DateTime<Utc>>,
+    /// The duration in seconds of the incident, missing while the incident is
+    /// open
+    duration: Option<i64>,
+    /// How many notifications were sent about the incident
+    notifications_sent: i64,
+}
+
+// Records from the tables
+#[derive(Debug, sqlx::FromRow)]
+pub struct IncidentRecord {
+    pub id: i32,
+    pub system_id: Uuid,
+    pub system_name: String,
+    pub started_at: NaiveDateTime,
+    pub ended_at: Option<NaiveDateTime>,
+    pub duration: Option<PgInterval>,
+    pub notifications_sent: i64,
+}
+
+impl From<IncidentRecord> for IncidentData {
+    fn from(record: IncidentRecord) -> Self {
+        IncidentData {
+            id: record.id,
+            system_id: record.system_id,
+            system_name: record.system_name,
+            started_at: record.started_at.and_utc(),
+            ended_at: record.ended_at.map(|ended_at| ended_at.and_utc()),
+            duration: record
+                .duration
+                .map(|duration| pg_interval_to_duration(duration).num_seconds()),
+            notifications_sent: record.notifications_sent,
+        }
+    }
+}
+
+pub const LIMIT_INCIDENT_REQUEST: i64 = 100;
+
+#[derive(Debug, Deserialize, Clone, IntoParams)]
+pub struct ListIncidentsQuery {
+    /// Only return the incidents of this system
+    pub system_id: Option<Uuid>,
+    /// The page number to return
+    pub page: i64,
+    /// The maximum number of incidents to return
+    pub list_size: i64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/list_incidents",
+    params(ListIncidentsQuery),
+    summary = "List Incidents",
+    description = "List the incidents (the periods in which a system was down) of the systems of the user",
+    responses(
+        (status = OK, description = "List of incidents", body = ListIncidentsResponse),
+        (status = BAD_REQUEST, description = "Bad request"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = []),
+        ("api_token" = [])
+    ),
+    tag = INCIDENT_TAG
+)]
+pub async fn list_incidents(
+    auth_session: AuthSession,
+    scope: Option<Extension<TokenScope>>,
+    Query(query): Query<ListIncidentsQuery>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let offset = match page_offset(query.page, query.list_size, LIMIT_INCIDENT_REQUEST) {
+        Ok(offset) => offset,
+        Err(response) => return response.into_response(),
+    };
+
+    let incidents = match sqlx::query_as!(
+        IncidentRecord,
+        r#"
+        SELECT i.id,
+               i.system_id,
+               s.name AS system_name,
+               i.started_at,
+               i.ended_at,
+               i.duration,
+               (SELECT COUNT(*) FROM incident_notification n WHERE n.incident_id = i.id)
+                   AS "notifications_sent!"
+        FROM incident i
+            JOIN system s ON i.system_id = s.id
+        WHERE s.id IN (SELECT visible_system_ids($1))
+          AND ($2::uuid IS NULL OR i.system_id = $2)
+          AND ($5::uuid[] IS NULL OR i.system_id = ANY ($5))
+        ORDER BY i.started_at DESC
+        LIMIT $3 OFFSET $4
+        "#,
+        user.id,
+        query.system_id,
+        query.list_size,
+        offset,
+        scoped_system_ids(scope.as_deref())
+    )
+    .fetch_all(&auth_session.backend.db)
+    .await
+    {
+        Ok(incidents) => incidents,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let incidents = incidents.into_iter().map(IncidentData::from).collect();
+
+    Sonic(ListIncidentsResponse { incidents }).into_response()
+}
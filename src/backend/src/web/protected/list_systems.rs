@@ -1,28 +1,37 @@
 use ahash::AHashMap;
 use axum::{
+    Extension,
     extract::Query,
     response::{IntoResponse, Response},
 };
 use axum_serde::Sonic;
-use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use chrono::{DateTime, NaiveDateTime, Utc};
 use http::StatusCode;
 use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, postgres::types::PgInterval};
+use tracing::error;
 use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
 use crate::{
     app::openapi::SYSTEM_TAG,
+    middleware::api_token::{TokenScope, scoped_system_ids},
+    models::{PingKind, ScheduleKind, Visibility},
     users::AuthSession,
-    web::utils::{
-        time::{approx_expected_timestamp, naive_datetime_now},
-        time_conversions::pg_interval_to_duration,
+    web::{
+        protected::organizations::OrganizationRole,
+        utils::{
+            schedule::{Grace, Schedule, ScheduleError, ScheduleHistory, ScheduleVersion},
+            time::{ApproxError, naive_datetime_now},
+            time_conversions::pg_interval_to_duration,
+        },
     },
 };
 
 #[derive(Debug, Serialize, Clone, ToSchema)]
 pub struct ListSystemsResponse {
-    /// The list of systems that the user has
+    /// The list of systems that the user has (their own and the ones of their
+    /// organizations)
     systems: Vec<SystemData>,
 }
 
@@ -35,16 +44,38 @@ pub struct SystemData {
     /// The list of instants (containing states for each expected ping) for the
     /// system
     instants: Vec<Instant>,
-    /// Frequency in minutes
-    frequency: u32,
+    /// The kind of schedule the system follows
+    schedule_kind: ScheduleKind,
+    /// Frequency in minutes (only for interval schedules)
+    frequency: Option<u32>,
+    /// The cron expression (only for cron schedules)
+    cron_expression: Option<String>,
+    /// The timezone in which the cron expression is evaluated, as defined by
+    /// the IANA Time Zone Database (only for cron schedules)
+    cron_timezone: Option<String>,
     /// The time at which the system starts pinging
     starts_at: DateTime<Utc>,
+    /// How many minutes before the expected time a ping still counts
+    grace_early: u32,
+    /// How many minutes after the expected time a ping still counts (pings
+    /// count until the next expected time if missing)
+    grace_late: Option<u32>,
     /// The visibility of the system
     visibility: Visibility,
+    /// The organization the system belongs to, missing for the personal systems
+    #[serde(skip_serializing_if = "Option::is_none")]
+    organization_id: Option<i32>,
+    /// The role of the user on the system (owner for their personal systems),
+    /// missing for the public systems
+    #[serde(skip_serializing_if = "Option::is_none")]
+    role: Option<OrganizationRole>,
 }
 
 #[derive(Debug, Serialize, Clone, ToSchema)]
 pub struct Instant {
+    /// The ID of the ping that determined the status, used to retrieve its
+    /// body
+    ping_id: Option<i32>,
     /// The status of the system at this instant
     status: Status,
     /// The actual timestamp of the ping
@@ -52,24 +83,64 @@ pub struct Instant {
     /// The expected timestamp of the ping (calculated from the frequency and
     /// the start time)
     expected_timestamp: DateTime<Utc>,
+    /// The duration in seconds of the job run (from the start ping to the
+    /// finishing ping), if a start ping was sent
+    duration: Option<i64>,
+    /// The exit code reported by the job, if it was sent
+    exit_code: Option<i32>,
 }
 
-#[derive(Debug, Serialize, Clone, ToSchema)]
+#[derive(Debug, Serialize, Clone, PartialEq, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum Status {
+    /// The job checked in successfully
     Up,
+    /// The job checked in successfully, but after the expected time (within
+    /// the grace)
+    Late,
+    /// The job never checked in
     Down,
+    /// The job checked in, but reported a failure
+    Failed,
+    /// The job started, but has not finished (yet)
+    Running,
+    /// The instant is before the system started being monitored
     Untracked,
 }
 
 pub const LIMIT_SYSTEM_REQUEST: i64 = 100;
 
-#[derive(Debug, Serialize, Deserialize, Clone, sqlx::Type, ToSchema)]
-#[sqlx(type_name = "visibility", rename_all = "snake_case")]
-#[serde(rename_all = "snake_case")]
-pub enum Visibility {
-    Public,
-    Private,
+/// The furthest page of instants that can be requested for cron schedules, as
+/// the expected timestamps of the skipped pages are walked one by one (the
+/// interval schedules jump straight to the page)
+pub const LIMIT_PAGE: i64 = 100;
+
+/// Validates the requested page of instants
+pub fn validate_paging(page: i64, list_size: i64) -> Result<(), (StatusCode, &'static str)> {
+    if list_size > LIMIT_SYSTEM_REQUEST {
+        return Err((StatusCode::BAD_REQUEST, "Limit of list_size exceeded"));
+    }
+
+    if page < 0 || list_size < 0 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Page and list_size must not be negative",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Validates the requested page of instants against the schedule of the system
+pub fn validate_page(
+    page: i64,
+    schedule_kind: ScheduleKind,
+) -> Result<(), (StatusCode, &'static str)> {
+    if matches!(schedule_kind, ScheduleKind::Cron) && page > LIMIT_PAGE {
+        return Err((StatusCode::BAD_REQUEST, "Limit of page exceeded"));
+    }
+
+    Ok(())
 }
 
 // Records from the tables
@@ -78,21 +149,65 @@ pub struct SystemRecord {
     pub id: Uuid,
     pub name: String,
     pub user_id: i32,
-    pub frequency: PgInterval,
+    pub frequency: Option<PgInterval>,
     pub starts_at: NaiveDateTime,
     pub deleted: bool,
     pub down_after: PgInterval,
     pub down_sent_email: bool,
     pub visibility: Visibility,
+    pub schedule_kind: ScheduleKind,
+    pub cron_expression: Option<String>,
+    pub cron_timezone: Option<String>,
+    pub grace_early: PgInterval,
+    pub grace_late: Option<PgInterval>,
+    pub organization_id: Option<i32>,
+}
+
+impl SystemRecord {
+    pub fn schedule(&self) -> Result<Schedule, ScheduleError> {
+        Schedule::new(
+            self.schedule_kind,
+            self.frequency,
+            self.cron_expression.as_deref(),
+            self.cron_timezone.as_deref(),
+            self.starts_at,
+        )
+    }
+
+    pub fn grace(&self) -> Grace {
+        Grace::new(self.grace_early, self.grace_late)
+    }
 }
 
 #[derive(Debug, sqlx::FromRow)]
 pub struct PingRecord {
-    #[allow(dead_code)]
     id: i32,
     #[allow(dead_code)]
-    system_id: String,
+    system_id: Uuid,
     timestamp: NaiveDateTime,
+    kind: PingKind,
+    duration: Option<PgInterval>,
+    exit_code: Option<i32>,
+}
+
+impl PingRecord {
+    // The time at which the run of this ping started
+    fn run_started_at(&self) -> NaiveDateTime {
+        self.timestamp
+            - self
+                .duration
+                .map(pg_interval_to_duration)
+                .unwrap_or_default()
+    }
+}
+
+// The pings that were matched to a single expected timestamp
+#[derive(Debug, Default)]
+struct SlotPings {
+    // The latest start ping
+    start: Option<PingRecord>,
+    // The latest finishing (success or fail) ping
+    finish: Option<PingRecord>,
 }
 
 #[derive(Debug, Deserialize, Clone, IntoParams)]
@@ -115,20 +230,22 @@ pub struct ListSystemsQuery {
         (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
     ),
     security(
-        ("session" = [])
+        ("session" = []),
+        ("api_token" = [])
     ),
     tag = SYSTEM_TAG
 )]
 pub async fn list_systems(
     auth_session: AuthSession,
+    scope: Option<Extension<TokenScope>>,
     Query(query): Query<ListSystemsQuery>,
 ) -> impl IntoResponse {
     let Some(user) = auth_session.user else {
         return StatusCode::UNAUTHORIZED.into_response();
     };
 
-    if query.list_size > LIMIT_SYSTEM_REQUEST {
-        return (StatusCode::BAD_REQUEST, "Limit of list_size exceeded").into_response();
+    if let Err(response) = validate_paging(query.page, query.list_size) {
+        return response.into_response();
     }
 
     let Ok(db_systems) = sqlx::query_as!(
@@ -142,13 +259,20 @@ pub async fn list_systems(
                deleted,
                down_after,
                down_sent_email,
-               visibility AS "visibility: Visibility"
+               visibility AS "visibility: Visibility",
+               schedule_kind AS "schedule_kind: ScheduleKind",
+               cron_expression,
+               cron_timezone,
+               grace_early,
+               grace_late,
+               organization_id
         FROM system
-        WHERE user_id = $1
-          AND deleted = FALSE
+        WHERE id IN (SELECT visible_system_ids($1))
+          AND ($2::uuid[] IS NULL OR id = ANY ($2))
         ORDER BY starts_at
         "#,
         user.id,
+        scoped_system_ids(scope.as_deref()),
     )
     .fetch_all(&auth_session.backend.db)
     .await
@@ -156,22 +280,65 @@ pub async fn list_systems(
         return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     };
 
+    let Ok(memberships) = sqlx::query!(
+        r#"
+        SELECT organization_id, role AS "role: OrganizationRole"
+        FROM organization_member
+        WHERE user_id = $1
+        "#,
+        user.id
+    )
+    .fetch_all(&auth_session.backend.db)
+    .await
+    else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    let roles: AHashMap<i32, OrganizationRole> = memberships
+        .into_iter()
+        .map(|membership| (membership.organization_id, membership.role))
+        .collect();
+
     let systems_fut: Vec<_> = db_systems
         .into_iter()
         .map(|db_system| async {
-            SystemData::fetch_from_db(
-                &auth_session.backend.db,
-                query.list_size,
-                query.page,
-                db_system,
-            )
-            .await
+            let organization_id = db_system.organization_id;
+            let role = match organization_id {
+                Some(organization_id) => roles.get(&organization_id).copied(),
+                None => Some(OrganizationRole::Owner),
+            };
+
+            // A system whose instants cannot be computed is still listed (without
+            // them), so that it does not hide the other systems. The cron systems
+            // have no instants past the furthest page they can be paged to
+            let instants = if validate_page(query.page, db_system.schedule_kind).is_err() {
+                Vec::new()
+            } else {
+                SystemData::fetch_instants(
+                    &auth_session.backend.db,
+                    query.list_size,
+                    query.page,
+                    &db_system,
+                )
+                .await
+                .unwrap_or_else(|_| {
+                    error!(
+                        "List systems: Error computing the instants of system {}",
+                        db_system.id
+                    );
+                    Vec::new()
+                })
+            };
+
+            let mut system = SystemData::from_record(db_system, instants);
+            system.organization_id = organization_id;
+            system.role = role;
+
+            system
         })
         .collect();
 
-    let Ok(systems) = futures::future::try_join_all(systems_fut).await else {
-        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
-    };
+    let systems = futures::future::join_all(systems_fut).await;
 
     Sonic(ListSystemsResponse { systems }).into_response()
 }
@@ -183,28 +350,79 @@ impl SystemData {
         page: i64,
         db_system: SystemRecord,
     ) -> Result<Self, Response> {
-        let frequency = pg_interval_to_duration(db_system.frequency);
+        let instants = Self::fetch_instants(pg_pool, list_size, page, &db_system).await?;
+
+        Ok(Self::from_record(db_system, instants))
+    }
+
+    // The system as it is returned, along with the given instants
+    fn from_record(db_system: SystemRecord, instants: Vec<Instant>) -> Self {
+        let grace = db_system.grace();
+
+        SystemData {
+            id: db_system.id,
+            name: db_system.name,
+            instants,
+            schedule_kind: db_system.schedule_kind,
+            frequency: db_system
+                .frequency
+                .map(|frequency| pg_interval_to_duration(frequency).num_seconds() as u32 / 60),
+            cron_expression: db_system.cron_expression,
+            cron_timezone: db_system.cron_timezone,
+            starts_at: db_system.starts_at.and_utc(),
+            grace_early: grace.early.num_minutes() as u32,
+            grace_late: grace.late.map(|late| late.num_minutes() as u32),
+            visibility: db_system.visibility,
+            organization_id: None,
+            role: None,
+        }
+    }
+
+    // The requested page of instants of the system
+    async fn fetch_instants(
+        pg_pool: &PgPool,
+        list_size: i64,
+        page: i64,
+        db_system: &SystemRecord,
+    ) -> Result<Vec<Instant>, Response> {
+        validate_page(page, db_system.schedule_kind).map_err(IntoResponse::into_response)?;
 
-        let now = approx_expected_timestamp(naive_datetime_now(), frequency, db_system.starts_at)
-            .unwrap();
+        let Some(skip) = page.checked_mul(list_size) else {
+            return Err(StatusCode::BAD_REQUEST.into_response());
+        };
 
-        let nearest_datetime = now - (frequency * (page * list_size) as i32);
-        let furthest_datetime = nearest_datetime - (frequency * list_size as i32);
+        let Ok(history) = Self::fetch_schedule_history(pg_pool, db_system).await else {
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        };
 
-        let nearest_datetime_max_expected = nearest_datetime + frequency;
-        let furthest_datetime_min_expected = furthest_datetime + frequency;
+        let Ok(expected_timestamps) =
+            history.expected_timestamps(naive_datetime_now(), skip, list_size)
+        else {
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        };
+
+        let (Some(&(nearest, nearest_version)), Some(&(furthest, furthest_version))) =
+            (expected_timestamps.first(), expected_timestamps.last())
+        else {
+            return Ok(Vec::new());
+        };
+
+        let Ok(nearest_max_expected) = nearest_version.schedule.next(nearest) else {
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        };
 
         let Ok(db_instants) = sqlx::query_as!(
             PingRecord,
             r#"
-            SELECT * FROM ping WHERE system_id = $1
-                                AND timestamp < $2
-                                AND timestamp > $3
-                               ORDER BY timestamp DESC
+            SELECT id, system_id, timestamp, kind AS "kind: PingKind", duration, exit_code
+            FROM ping WHERE system_id = $1
+                        AND timestamp < $2
+                        AND timestamp > $3
+                       ORDER BY timestamp DESC
             "#,
             db_system.id,
-            nearest_datetime_max_expected,
-            furthest_datetime_min_expected,
+            nearest_max_expected,
+            furthest - furthest_version.grace.early,
         )
         .fetch_all(pg_pool)
         .await
@@ -212,77 +430,160 @@ impl SystemData {
             return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
         };
 
-        let instants = Self::from_ping_records_to_instants(
-            db_instants,
-            frequency,
-            db_system.starts_at,
-            nearest_datetime,
-            furthest_datetime,
-            list_size,
-        )?;
+        Self::from_ping_records_to_instants(db_instants, &history, &expected_timestamps)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+    }
 
-        Ok(SystemData {
-            id: db_system.id,
-            name: db_system.name,
-            instants,
-            frequency: frequency.num_seconds() as u32 / 60,
-            starts_at: db_system.starts_at.and_utc(),
-            visibility: db_system.visibility,
-        })
+    // The current schedule of the system along with the ones it followed
+    // before being edited
+    async fn fetch_schedule_history(
+        pg_pool: &PgPool,
+        db_system: &SystemRecord,
+    ) -> Result<ScheduleHistory, Response> {
+        let Ok(schedule) = db_system.schedule() else {
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        };
+
+        let mut history = ScheduleHistory::new(schedule, db_system.grace());
+
+        let Ok(versions) = sqlx::query!(
+            r#"
+            SELECT schedule_kind AS "schedule_kind: ScheduleKind",
+                   frequency,
+                   cron_expression,
+                   cron_timezone,
+                   starts_at,
+                   grace_early,
+                   grace_late,
+                   valid_until
+            FROM schedule_version
+            WHERE system_id = $1
+            ORDER BY valid_until DESC
+            "#,
+            db_system.id
+        )
+        .fetch_all(pg_pool)
+        .await
+        else {
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        };
+
+        for version in versions {
+            let Ok(schedule) = Schedule::new(
+                version.schedule_kind,
+                version.frequency,
+                version.cron_expression.as_deref(),
+                version.cron_timezone.as_deref(),
+                version.starts_at,
+            ) else {
+                return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+            };
+
+            history.push_previous(
+                schedule,
+                Grace::new(version.grace_early, version.grace_late),
+                version.valid_until,
+            );
+        }
+
+        Ok(history)
     }
 
     // Here we convert the records from the ping table to a vector of Instant
-    // objects, we calculate from the starts_at timestamp (system.starts_at) to all
-    // the expected times to fill up the "Down" moments
-    // uses the approx_expected_timestamp function to calculate the expected
-    // timestamp
+    // objects, we walk the expected timestamps (the newest first) to fill up
+    // the "Down" moments
+    // every ping is matched with the match_expected_timestamp method of the
+    // schedule that was in effect when its run started (taking the grace into
+    // account), finished runs are matched using the time at which they started
     fn from_ping_records_to_instants(
         ping_records: Vec<PingRecord>,
-        frequency: Duration,
-        starts_at: NaiveDateTime,
-        mut nearest_datetime: NaiveDateTime,
-        furthest_datetime: NaiveDateTime,
-        list_size: i64,
-    ) -> Result<Vec<Instant>, Response> {
+        history: &ScheduleHistory,
+        expected_timestamps: &[(NaiveDateTime, &ScheduleVersion)],
+    ) -> Result<Vec<Instant>, ApproxError> {
         // Hashmap that contains the key as the expected timestamp and the value as the
-        // actual timestamp
-        let hashmap: AHashMap<NaiveDateTime, NaiveDateTime> = ping_records
-            .into_iter()
-            .map(|t| {
-                (
-                    approx_expected_timestamp(t.timestamp, frequency, starts_at).unwrap(),
-                    t.timestamp,
-                )
-            })
-            .collect();
+        // pings that were matched to it
+        let mut hashmap: AHashMap<NaiveDateTime, SlotPings> = AHashMap::new();
 
-        let mut instants = Vec::with_capacity(list_size as usize);
+        // The records are ordered by timestamp descending, so the first ping of each
+        // kind is the latest one
+        for record in ping_records {
+            let version = history.version_at(record.run_started_at());
+            let Some(expected) = version
+                .schedule
+                .match_expected_timestamp(record.run_started_at(), &version.grace)?
+            else {
+                continue;
+            };
+            let slot = hashmap.entry(expected).or_default();
 
-        while nearest_datetime > furthest_datetime {
-            let instant = match hashmap.get(&nearest_datetime) {
-                Some(status) => Instant {
-                    status: Status::Up,
-                    timestamp: Some(status.and_utc()),
+            match record.kind {
+                PingKind::Start => {
+                    slot.start.get_or_insert(record);
+                }
+                PingKind::Success | PingKind::Fail => {
+                    slot.finish.get_or_insert(record);
+                }
+            }
+        }
+
+        let mut instants = Vec::with_capacity(expected_timestamps.len());
+
+        for &(nearest_datetime, version) in expected_timestamps {
+            let slot = hashmap.get(&nearest_datetime);
+
+            let instant = match slot {
+                Some(SlotPings {
+                    finish: Some(record),
+                    ..
+                }) => Instant {
+                    ping_id: Some(record.id),
+                    status: match record.kind {
+                        PingKind::Fail => Status::Failed,
+                        _ if version
+                            .grace
+                            .is_late(record.run_started_at(), nearest_datetime) =>
+                        {
+                            Status::Late
+                        }
+                        _ => Status::Up,
+                    },
+                    timestamp: Some(record.timestamp.and_utc()),
                     expected_timestamp: nearest_datetime.and_utc(),
+                    duration: record
+                        .duration
+                        .map(|d| pg_interval_to_duration(d).num_seconds()),
+                    exit_code: record.exit_code,
                 },
-                None => {
-                    let status = if nearest_datetime > starts_at {
+                Some(SlotPings {
+                    start: Some(record),
+                    finish: None,
+                }) => Instant {
+                    ping_id: Some(record.id),
+                    status: Status::Running,
+                    timestamp: Some(record.timestamp.and_utc()),
+                    expected_timestamp: nearest_datetime.and_utc(),
+                    duration: None,
+                    exit_code: None,
+                },
+                _ => {
+                    let status = if nearest_datetime > version.schedule.starts_at() {
                         Status::Down
                     } else {
                         Status::Untracked
                     };
 
                     Instant {
+                        ping_id: None,
                         status,
                         timestamp: None,
                         expected_timestamp: nearest_datetime.and_utc(),
+                        duration: None,
+                        exit_code: None,
                     }
                 }
             };
 
             instants.push(instant);
-
-            nearest_datetime -= frequency;
         }
 
         instants.reverse();
@@ -290,3 +591,187 @@ impl SystemData {
         Ok(instants)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use chrono::Duration;
+
+    use super::*;
+
+    fn ping(timestamp: NaiveDateTime, kind: PingKind, duration: Option<Duration>) -> PingRecord {
+        PingRecord {
+            id: 0,
+            system_id: Uuid::nil(),
+            timestamp,
+            kind,
+            duration: duration.map(|d| d.try_into().unwrap()),
+            exit_code: None,
+        }
+    }
+
+    #[test]
+    fn test_from_ping_records_to_instants() {
+        let starts_at = naive_datetime_now() - Duration::hours(4);
+        let frequency = Duration::hours(1);
+        let nearest = starts_at + Duration::hours(3);
+
+        // Ordered by timestamp descending, as they come from the database
+        let records = vec![
+            ping(starts_at + Duration::minutes(185), PingKind::Start, None),
+            ping(
+                starts_at + Duration::minutes(130),
+                PingKind::Fail,
+                Some(Duration::minutes(10)),
+            ),
+            ping(starts_at + Duration::minutes(120), PingKind::Start, None),
+            // The run started in the previous slot, so it belongs to that one
+            ping(
+                starts_at + Duration::minutes(61),
+                PingKind::Success,
+                Some(Duration::minutes(2)),
+            ),
+            ping(starts_at + Duration::minutes(59), PingKind::Start, None),
+        ];
+
+        let schedule = Schedule::Interval {
+            frequency,
+            starts_at,
+        };
+
+        let history = ScheduleHistory::new(schedule, Grace::default());
+        let expected_timestamps = history.expected_timestamps(nearest, 0, 5).unwrap();
+
+        let instants =
+            SystemData::from_ping_records_to_instants(records, &history, &expected_timestamps)
+                .unwrap();
+
+        let statuses: Vec<_> = instants.iter().map(|i| i.status.clone()).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                Status::Untracked,
+                Status::Up,
+                Status::Down,
+                Status::Failed,
+                Status::Running
+            ]
+        );
+        assert_eq!(instants[1].duration, Some(120));
+        assert_eq!(instants[3].duration, Some(600));
+        assert_eq!(instants[4].duration, None);
+    }
+
+    #[test]
+    fn test_from_ping_records_to_instants_with_grace() {
+        let starts_at = naive_datetime_now() - Duration::hours(4);
+        let frequency = Duration::hours(1);
+        let nearest = starts_at + Duration::hours(3);
+
+        let records = vec![
+            // Fired a few seconds early, it counts for the slot that follows
+            ping(
+                starts_at + Duration::hours(3) - Duration::seconds(5),
+                PingKind::Success,
+                None,
+            ),
+            // Outside of the grace, it does not count
+            ping(starts_at + Duration::minutes(150), PingKind::Success, None),
+            ping(starts_at + Duration::minutes(63), PingKind::Success, None),
+        ];
+
+        let schedule = Schedule::Interval {
+            frequency,
+            starts_at,
+        };
+        let grace = Grace {
+            early: Duration::minutes(1),
+            late: Some(Duration::minutes(5)),
+        };
+
+        let history = ScheduleHistory::new(schedule, grace);
+        let expected_timestamps = history.expected_timestamps(nearest, 0, 3).unwrap();
+
+        let instants =
+            SystemData::from_ping_records_to_instants(records, &history, &expected_timestamps)
+                .unwrap();
+
+        let statuses: Vec<_> = instants.iter().map(|i| i.status.clone()).collect();
+        assert_eq!(statuses, vec![Status::Late, Status::Down, Status::Up]);
+    }
+
+    #[test]
+    fn test_from_ping_records_to_instants_with_history() {
+        let starts_at = naive_datetime_now() - Duration::hours(6);
+        let edited_at = starts_at + Duration::hours(3);
+        let nearest = starts_at + Duration::hours(5);
+
+        // Hourly until the edit, every 30 minutes since
+        let mut history = ScheduleHistory::new(
+            Schedule::Interval {
+                frequency: Duration::minutes(30),
+                starts_at,
+            },
+            Grace::default(),
+        );
+        history.push_previous(
+            Schedule::Interval {
+                frequency: Duration::hours(1),
+                starts_at,
+            },
+            Grace::default(),
+            edited_at,
+        );
+
+        let records = vec![
+            ping(starts_at + Duration::minutes(275), PingKind::Success, None),
+            ping(starts_at + Duration::minutes(185), PingKind::Success, None),
+            // Matched to the hourly schedule that was in effect back then
+            ping(starts_at + Duration::minutes(150), PingKind::Success, None),
+        ];
+
+        let expected_timestamps = history.expected_timestamps(nearest, 0, 6).unwrap();
+        let instants =
+            SystemData::from_ping_records_to_instants(records, &history, &expected_timestamps)
+                .unwrap();
+
+        let expected: Vec<_> = instants
+            .iter()
+            .map(|i| (i.expected_timestamp.naive_utc() - starts_at).num_minutes())
+            .collect();
+        assert_eq!(expected, vec![120, 180, 210, 240, 270, 300]);
+
+        let statuses: Vec<_> = instants.iter().map(|i| i.status.clone()).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                Status::Up,
+                Status::Up,
+                Status::Down,
+                Status::Down,
+                Status::Up,
+                Status::Down
+            ]
+        );
+
+        // Paging skips over the edit as well
+        let page = history.expected_timestamps(nearest, 4, 2).unwrap();
+        assert_eq!(page[0].0, edited_at);
+        assert_eq!(page[1].0, starts_at + Duration::hours(2));
+    }
+
+    #[test]
+    fn test_validate_paging() {
+        assert!(validate_paging(0, LIMIT_SYSTEM_REQUEST).is_ok());
+        assert!(validate_paging(LIMIT_PAGE + 1, 10).is_ok());
+        assert!(validate_paging(0, LIMIT_SYSTEM_REQUEST + 1).is_err());
+        assert!(validate_paging(-1, 10).is_err());
+        assert!(validate_paging(0, -10).is_err());
+    }
+
+    #[test]
+    fn test_validate_page() {
+        assert!(validate_page(LIMIT_PAGE, ScheduleKind::Cron).is_ok());
+        assert!(validate_page(LIMIT_PAGE + 1, ScheduleKind::Cron).is_err());
+        assert!(validate_page(LIMIT_PAGE * 100, ScheduleKind::Interval).is_ok());
+    }
+}
@@ -1,18 +1,34 @@
 use utoipa_axum::{router::OpenApiRouter, routes};
 
 pub mod add_system;
+pub mod admin;
 pub mod change_visibility;
 pub mod delete_system;
+pub mod edit_system;
 pub mod edit_system_name;
+pub mod get_incident;
+pub mod get_ping_body;
+pub mod list_audit_events;
+pub mod list_incidents;
 pub mod list_systems;
+pub mod notification_channels;
+pub mod organizations;
 pub mod user;
 
 pub fn router() -> OpenApiRouter {
     OpenApiRouter::new()
         .nest("/user", user::router())
+        .nest("/notification_channels", notification_channels::router())
+        .nest("/organizations", organizations::router())
+        .nest("/admin", admin::router())
         .routes(routes![add_system::add_system])
         .routes(routes![delete_system::delete_system])
         .routes(routes![list_systems::list_systems])
+        .routes(routes![edit_system::edit_system])
         .routes(routes![edit_system_name::edit_system_name])
         .routes(routes![change_visibility::change_visibility])
+        .routes(routes![get_ping_body::get_ping_body])
+        .routes(routes![list_incidents::list_incidents])
+        .routes(routes![get_incident::get_incident])
+        .routes(routes![list_audit_events::list_audit_events])
 }
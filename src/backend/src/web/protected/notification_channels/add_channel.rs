@@ -0,0 +1,116 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use sonic_rs::json;
+use sqlx::types::Json;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    ALLOW_PRIVATE_TARGETS,
+    app::openapi::NOTIFICATION_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    notifiers::ChannelConfig,
+    users::AuthSession,
+    web::{protected::organizations::OrganizationRole, utils::system_access::ensure_system_role},
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AddChannelRequest {
+    /// The system the channel is notified about, all the systems of the user if
+    /// missing
+    system_id: Option<Uuid>,
+    /// The configuration of the channel
+    config: ChannelConfig,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AddChannelResponse {
+    /// The ID of the channel that was created
+    id: i32,
+    /// The configuration of the channel (including the generated secrets)
+    config: ChannelConfig,
+}
+
+#[utoipa::path(
+    post,
+    path = "/add_channel",
+    summary = "Add Notification Channel",
+    request_body = AddChannelRequest,
+    responses(
+        (status = CREATED, description = "Channel was created successfully", body = AddChannelResponse),
+        (status = BAD_REQUEST, description = "Bad request"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = NOT_FOUND, description = "System not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = []),
+        ("api_token" = [])
+    ),
+    tag = NOTIFICATION_TAG
+)]
+pub async fn add_channel(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<AddChannelRequest>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let config = match request.config.prepare(*ALLOW_PRIVATE_TARGETS) {
+        Ok(config) => config,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
+    if let Some(system_id) = request.system_id
+        && let Err(response) = ensure_system_role(
+            &auth_session.backend.db,
+            user.id,
+            system_id,
+            OrganizationRole::Viewer,
+        )
+        .await
+    {
+        return response;
+    }
+
+    let channel = match sqlx::query!(
+        r#"
+        INSERT INTO notification_channel (user_id, system_id, config)
+        VALUES ($1, $2, $3)
+        RETURNING id, config ->> 'kind' AS "kind!"
+        "#,
+        user.id,
+        request.system_id,
+        Json(&config) as _
+    )
+    .fetch_one(&auth_session.backend.db)
+    .await
+    {
+        Ok(channel) => channel,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    // The configuration is not recorded, as it holds the secrets of the channel
+    audit
+        .event(
+            AuditAction::ChannelAdded,
+            AuditTarget::NotificationChannel,
+            channel.id,
+        )
+        .after(json!({ "kind": channel.kind, "system_id": request.system_id }))
+        .record()
+        .await;
+
+    (
+        StatusCode::CREATED,
+        Sonic(AddChannelResponse {
+            id: channel.id,
+            config,
+        }),
+    )
+        .into_response()
+}
@@ -0,0 +1,73 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::Deserialize;
+use sonic_rs::json;
+use utoipa::ToSchema;
+
+use crate::{
+    app::openapi::NOTIFICATION_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct DeleteChannelRequest {
+    /// The ID of the channel to delete
+    id: i32,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/delete_channel",
+    summary = "Delete Notification Channel",
+    request_body = DeleteChannelRequest,
+    responses(
+        (status = OK, description = "Channel was deleted successfully"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = NOT_FOUND, description = "Channel not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = []),
+        ("api_token" = [])
+    ),
+    tag = NOTIFICATION_TAG
+)]
+pub async fn delete_channel(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<DeleteChannelRequest>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let channel = match sqlx::query!(
+        r#"
+        DELETE FROM notification_channel WHERE id = $1 AND user_id = $2
+        RETURNING system_id, config ->> 'kind' AS "kind!"
+        "#,
+        request.id,
+        user.id
+    )
+    .fetch_optional(&auth_session.backend.db)
+    .await
+    {
+        Ok(Some(channel)) => channel,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    audit
+        .event(
+            AuditAction::ChannelDeleted,
+            AuditTarget::NotificationChannel,
+            request.id,
+        )
+        .before(json!({ "kind": channel.kind, "system_id": channel.system_id }))
+        .record()
+        .await;
+
+    StatusCode::OK.into_response()
+}
@@ -0,0 +1,78 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use chrono::{DateTime, Utc};
+use http::StatusCode;
+use serde::Serialize;
+use sqlx::types::Json;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{app::openapi::NOTIFICATION_TAG, notifiers::ChannelConfig, users::AuthSession};
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListChannelsResponse {
+    /// The notification channels of the user
+    channels: Vec<ChannelData>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChannelData {
+    /// The ID of the channel
+    id: i32,
+    /// The system the channel is notified about, all the systems of the user if
+    /// missing
+    system_id: Option<Uuid>,
+    /// The configuration of the channel, with its secrets redacted
+    config: ChannelConfig,
+    /// The time at which the channel was created
+    created_at: DateTime<Utc>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/list_channels",
+    summary = "List Notification Channels",
+    responses(
+        (status = OK, description = "List of notification channels", body = ListChannelsResponse),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = []),
+        ("api_token" = [])
+    ),
+    tag = NOTIFICATION_TAG
+)]
+pub async fn list_channels(auth_session: AuthSession) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let channels = match sqlx::query!(
+        r#"
+        SELECT id, system_id, config AS "config: Json<ChannelConfig>", created_at
+        FROM notification_channel
+        WHERE user_id = $1
+        ORDER BY created_at
+        "#,
+        user.id
+    )
+    .fetch_all(&auth_session.backend.db)
+    .await
+    {
+        Ok(channels) => channels,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let channels = channels
+        .into_iter()
+        .map(|channel| ChannelData {
+            id: channel.id,
+            system_id: channel.system_id,
+            config: channel.config.0.redacted(),
+            created_at: channel.created_at.and_utc(),
+        })
+        .collect();
+
+    Sonic(ListChannelsResponse { channels }).into_response()
+}
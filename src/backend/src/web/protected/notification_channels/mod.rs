@@ -0,0 +1,14 @@
+mod add_channel;
+mod delete_channel;
+mod list_channels;
+mod test_channel;
+
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+pub fn router() -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes![add_channel::add_channel])
+        .routes(routes![list_channels::list_channels])
+        .routes(routes![delete_channel::delete_channel])
+        .routes(routes![test_channel::test_channel])
+}
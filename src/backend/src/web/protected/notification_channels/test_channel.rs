@@ -0,0 +1,120 @@
+use std::str::FromStr;
+
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use chrono_tz::Tz;
+use http::StatusCode;
+use serde::Deserialize;
+use sonic_rs::json;
+use sqlx::types::Json;
+use tracing::warn;
+use utoipa::ToSchema;
+
+use crate::{
+    ALLOW_PRIVATE_TARGETS,
+    app::openapi::NOTIFICATION_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    notifiers::{ChannelConfig, NotificationEvent, message::MessageLocale},
+    users::AuthSession,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TestChannelRequest {
+    /// The ID of the channel to send the test notification to
+    id: i32,
+}
+
+#[utoipa::path(
+    post,
+    path = "/test_channel",
+    summary = "Send Test Notification",
+    description = "Send a test notification through the channel, to check that it is configured correctly",
+    request_body = TestChannelRequest,
+    responses(
+        (status = OK, description = "Test notification was sent successfully"),
+        (status = BAD_REQUEST, description = "The channel points to a private address"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = NOT_FOUND, description = "Channel not found"),
+        (status = BAD_GATEWAY, description = "The channel rejected the notification", body = String),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = []),
+        ("api_token" = [])
+    ),
+    tag = NOTIFICATION_TAG
+)]
+pub async fn test_channel(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<TestChannelRequest>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let config = match sqlx::query_scalar!(
+        r#"
+        SELECT config AS "config: Json<ChannelConfig>"
+        FROM notification_channel
+        WHERE id = $1
+          AND user_id = $2
+        "#,
+        request.id,
+        user.id
+    )
+    .fetch_optional(&auth_session.backend.db)
+    .await
+    {
+        Ok(Some(config)) => config.0,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    // Channels added before the private addresses were rejected
+    if let Err(e) = config.check_targets(*ALLOW_PRIVATE_TARGETS) {
+        return (StatusCode::BAD_REQUEST, e).into_response();
+    }
+
+    let Ok(timezone) = Tz::from_str(&user.timezone) else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    let notifier = config.into_notifier(MessageLocale {
+        language: user.language,
+        timezone,
+    });
+
+    let result = notifier.notify(&NotificationEvent::Test).await;
+
+    audit
+        .event(
+            AuditAction::ChannelTested,
+            AuditTarget::NotificationChannel,
+            request.id,
+        )
+        .after(json!({ "delivered": result.is_ok() }))
+        .record()
+        .await;
+
+    match result {
+        Ok(_) => StatusCode::OK.into_response(),
+        Err(e) => {
+            // The error is not returned, as it would tell about the network of the
+            // instance (whether a host or port is reachable)
+            warn!(
+                "Test channel: Error sending to channel {}: {}",
+                request.id, e
+            );
+
+            let status = e.downcast_ref::<reqwest::Error>().and_then(|e| e.status());
+
+            let message = match status {
+                Some(status) => format!("The channel answered with status {}", status.as_u16()),
+                None => "The notification could not be delivered".to_string(),
+            };
+
+            (StatusCode::BAD_GATEWAY, message).into_response()
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use sonic_rs::json;
+use utoipa::ToSchema;
+
+use crate::{
+    app::openapi::ORGANIZATION_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+    web::{protected::organizations::OrganizationRole, utils::token::hash_token},
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AcceptInvitationRequest {
+    /// The code of the invitation
+    code: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AcceptInvitationResponse {
+    /// The ID of the organization the user joined
+    organization_id: i32,
+}
+
+#[utoipa::path(
+    post,
+    path = "/accept_invitation",
+    summary = "Accept Invitation",
+    description = "Join an organization, the invitation must have been sent to the email of the user",
+    request_body = AcceptInvitationRequest,
+    responses(
+        (status = OK, description = "User joined the organization", body = AcceptInvitationResponse),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = NOT_FOUND, description = "Invitation not found (or expired, already accepted or sent to another email)"),
+        (status = CONFLICT, description = "User is already a member of the organization"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = []),
+        ("api_token" = [])
+    ),
+    tag = ORGANIZATION_TAG
+)]
+pub async fn accept_invitation(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<AcceptInvitationRequest>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let Ok(mut tx) = auth_session.backend.db.begin().await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    let invitation = match sqlx::query!(
+        r#"
+        UPDATE organization_invitation
+        SET accepted_at = NOW()
+        WHERE code_hash = $1
+          AND LOWER(email) = LOWER($2)
+          AND accepted_at IS NULL
+          AND expires_at > NOW()
+        RETURNING id, organization_id, role AS "role: OrganizationRole"
+        "#,
+        hash_token(request.code.trim()),
+        user.email
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    {
+        Ok(Some(invitation)) => invitation,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    // Dropping the transaction keeps the invitation usable
+    let joined = match sqlx::query!(
+        r#"
+        INSERT INTO organization_member (organization_id, user_id, role)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (organization_id, user_id) DO NOTHING
+        "#,
+        invitation.organization_id,
+        user.id,
+        invitation.role as OrganizationRole
+    )
+    .execute(&mut *tx)
+    .await
+    {
+        Ok(result) => result.rows_affected() > 0,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    if !joined {
+        return StatusCode::CONFLICT.into_response();
+    }
+
+    if tx.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    audit
+        .event(
+            AuditAction::InvitationAccepted,
+            AuditTarget::OrganizationInvitation,
+            invitation.id,
+        )
+        .organization(Some(invitation.organization_id))
+        .after(json!({ "role": invitation.role }))
+        .record()
+        .await;
+
+    Sonic(AcceptInvitationResponse {
+        organization_id: invitation.organization_id,
+    })
+    .into_response()
+}
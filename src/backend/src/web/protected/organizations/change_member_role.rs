@@ -0,0 +1,131 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::Deserialize;
+use sonic_rs::json;
+use utoipa::ToSchema;
+
+use crate::{
+    app::openapi::ORGANIZATION_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+    web::protected::organizations::{
+        OrganizationRole, ensure_member_role, is_last_owner, member_role,
+    },
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ChangeMemberRoleRequest {
+    /// The ID of the organization
+    organization_id: i32,
+    /// The ID of the member whose role is changed
+    user_id: i32,
+    /// The new role of the member
+    role: OrganizationRole,
+}
+
+#[utoipa::path(
+    patch,
+    path = "/change_member_role",
+    summary = "Change Member Role",
+    description = "Change the role of a member, only the owners can make (or demote) other owners. The last owner of an organization cannot be demoted",
+    request_body = ChangeMemberRoleRequest,
+    responses(
+        (status = OK, description = "Role was changed successfully"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = FORBIDDEN, description = "User cannot change the role of the member"),
+        (status = NOT_FOUND, description = "Organization or member not found"),
+        (status = CONFLICT, description = "The member is the last owner of the organization"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = []),
+        ("api_token" = [])
+    ),
+    tag = ORGANIZATION_TAG
+)]
+pub async fn change_member_role(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<ChangeMemberRoleRequest>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let db = &auth_session.backend.db;
+
+    let role = match ensure_member_role(
+        db,
+        request.organization_id,
+        user.id,
+        OrganizationRole::Admin,
+    )
+    .await
+    {
+        Ok(role) => role,
+        Err(response) => return response,
+    };
+
+    let member = match member_role(db, request.organization_id, request.user_id).await {
+        Ok(Some(member)) => member,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let involves_owner =
+        member == OrganizationRole::Owner || request.role == OrganizationRole::Owner;
+    if involves_owner && role != OrganizationRole::Owner {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let Ok(mut tx) = db.begin().await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    if member == OrganizationRole::Owner && request.role != OrganizationRole::Owner {
+        match is_last_owner(&mut tx, request.organization_id, request.user_id).await {
+            Ok(false) => {}
+            Ok(true) => {
+                return (
+                    StatusCode::CONFLICT,
+                    "The last owner of the organization cannot be demoted",
+                )
+                    .into_response();
+            }
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+
+    let changed = sqlx::query!(
+        r#"
+        UPDATE organization_member
+        SET role = $3
+        WHERE organization_id = $1
+          AND user_id = $2
+        "#,
+        request.organization_id,
+        request.user_id,
+        request.role as OrganizationRole
+    )
+    .execute(&mut *tx)
+    .await;
+
+    if changed.is_err() || tx.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    audit
+        .event(
+            AuditAction::MemberRoleChanged,
+            AuditTarget::OrganizationMember,
+            request.user_id,
+        )
+        .organization(Some(request.organization_id))
+        .before(json!({ "role": member }))
+        .after(json!({ "role": request.role }))
+        .record()
+        .await;
+
+    StatusCode::OK.into_response()
+}
@@ -0,0 +1,95 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use sonic_rs::json;
+use utoipa::ToSchema;
+
+use crate::{
+    app::openapi::ORGANIZATION_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+    web::protected::organizations::OrganizationRole,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateOrganizationRequest {
+    /// The name of the organization
+    name: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreateOrganizationResponse {
+    /// The ID of the organization that was created
+    id: i32,
+}
+
+#[utoipa::path(
+    post,
+    path = "/create_organization",
+    summary = "Create Organization",
+    description = "Create an organization, the user becomes its owner (and is alerted about its systems)",
+    request_body = CreateOrganizationRequest,
+    responses(
+        (status = CREATED, description = "Organization was created successfully", body = CreateOrganizationResponse),
+        (status = BAD_REQUEST, description = "Name is not valid"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = []),
+        ("api_token" = [])
+    ),
+    tag = ORGANIZATION_TAG
+)]
+pub async fn create_organization(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<CreateOrganizationRequest>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let name = request.name.trim();
+    if name.is_empty() {
+        return (StatusCode::BAD_REQUEST, "Name is not valid").into_response();
+    }
+
+    let id = match sqlx::query_scalar!(
+        r#"
+        WITH created AS (
+            INSERT INTO organization (name) VALUES ($1) RETURNING id
+        )
+        INSERT INTO organization_member (organization_id, user_id, role, receive_alerts)
+        SELECT id, $2, $3, TRUE FROM created
+        RETURNING organization_id
+        "#,
+        name,
+        user.id,
+        OrganizationRole::Owner as OrganizationRole
+    )
+    .fetch_one(&auth_session.backend.db)
+    .await
+    {
+        Ok(id) => id,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    audit
+        .event(
+            AuditAction::OrganizationCreated,
+            AuditTarget::Organization,
+            id,
+        )
+        .organization(Some(id))
+        .after(json!({ "name": name }))
+        .record()
+        .await;
+
+    (
+        StatusCode::CREATED,
+        Sonic(CreateOrganizationResponse { id }),
+    )
+        .into_response()
+}
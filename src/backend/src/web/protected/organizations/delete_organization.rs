@@ -0,0 +1,129 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::Deserialize;
+use sonic_rs::json;
+use utoipa::ToSchema;
+
+use crate::{
+    app::openapi::ORGANIZATION_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+    web::protected::organizations::{OrganizationRole, ensure_member_role},
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct DeleteOrganizationRequest {
+    /// The ID of the organization to delete
+    organization_id: i32,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/delete_organization",
+    summary = "Delete Organization",
+    description = "Delete an organization along with its members and invitations, its systems have to be deleted (or moved) first",
+    request_body = DeleteOrganizationRequest,
+    responses(
+        (status = OK, description = "Organization was deleted successfully"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = FORBIDDEN, description = "User is not an owner of the organization"),
+        (status = NOT_FOUND, description = "Organization not found"),
+        (status = CONFLICT, description = "The organization still has systems"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = []),
+        ("api_token" = [])
+    ),
+    tag = ORGANIZATION_TAG
+)]
+pub async fn delete_organization(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<DeleteOrganizationRequest>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let db = &auth_session.backend.db;
+
+    if let Err(response) = ensure_member_role(
+        db,
+        request.organization_id,
+        user.id,
+        OrganizationRole::Owner,
+    )
+    .await
+    {
+        return response;
+    }
+
+    let Ok(mut tx) = db.begin().await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    // The deleted systems are kept (for their history), they go back to the
+    // users who created them
+    let Ok(has_systems) = sqlx::query_scalar!(
+        r#"
+        WITH released AS (
+            UPDATE system
+            SET organization_id = NULL
+            WHERE organization_id = $1
+              AND deleted = TRUE
+        )
+        SELECT EXISTS (
+            SELECT 1 FROM system WHERE organization_id = $1 AND deleted = FALSE
+        ) AS "exists!"
+        "#,
+        request.organization_id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    if has_systems {
+        return (StatusCode::CONFLICT, "The organization still has systems").into_response();
+    }
+
+    let deleted = sqlx::query!(
+        r#"
+        WITH members AS (
+            DELETE FROM organization_member WHERE organization_id = $1
+        ),
+        invitations AS (
+            DELETE FROM organization_invitation WHERE organization_id = $1
+        )
+        DELETE FROM organization WHERE id = $1
+        RETURNING name
+        "#,
+        request.organization_id
+    )
+    .fetch_one(&mut *tx)
+    .await;
+
+    let Ok(deleted) = deleted else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    if tx.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    audit
+        .event(
+            AuditAction::OrganizationDeleted,
+            AuditTarget::Organization,
+            request.organization_id,
+        )
+        .organization(Some(request.organization_id))
+        .before(json!({ "name": deleted.name }))
+        .record()
+        .await;
+
+    StatusCode::OK.into_response()
+}
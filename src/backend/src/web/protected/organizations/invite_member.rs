@@ -0,0 +1,142 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use chrono::{DateTime, Duration, Utc};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use sonic_rs::json;
+use utoipa::ToSchema;
+
+use crate::{
+    app::openapi::ORGANIZATION_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+    web::{
+        protected::organizations::{OrganizationRole, ensure_member_role},
+        utils::token::generate_token,
+    },
+};
+
+/// The prefix of the codes of the invitations to an organization
+pub const INVITATION_PREFIX: &str = "org_";
+
+/// How long an invitation can be accepted for
+const INVITATION_VALIDITY: Duration = Duration::days(7);
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct InviteMemberRequest {
+    /// The ID of the organization
+    organization_id: i32,
+    /// The email of the user to invite, only the user with this email can accept
+    /// the invitation
+    email: String,
+    /// The role the user will have in the organization
+    role: OrganizationRole,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct InviteMemberResponse {
+    /// The ID of the invitation
+    id: i32,
+    /// The code to accept the invitation with. It is not stored, so it cannot
+    /// be retrieved again
+    code: String,
+    /// The time after which the invitation cannot be accepted anymore
+    expires_at: DateTime<Utc>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/invite_member",
+    summary = "Invite Member",
+    description = "Invite a user to join the organization, only the owners can invite other owners",
+    request_body = InviteMemberRequest,
+    responses(
+        (status = CREATED, description = "Invitation was created successfully", body = InviteMemberResponse),
+        (status = BAD_REQUEST, description = "Email is not valid"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = FORBIDDEN, description = "User cannot invite members with the role"),
+        (status = NOT_FOUND, description = "Organization not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = []),
+        ("api_token" = [])
+    ),
+    tag = ORGANIZATION_TAG
+)]
+pub async fn invite_member(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<InviteMemberRequest>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let db = &auth_session.backend.db;
+
+    let role = match ensure_member_role(
+        db,
+        request.organization_id,
+        user.id,
+        OrganizationRole::Admin,
+    )
+    .await
+    {
+        Ok(role) => role,
+        Err(response) => return response,
+    };
+
+    if request.role == OrganizationRole::Owner && role != OrganizationRole::Owner {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let email = request.email.trim();
+    if !email.contains('@') {
+        return (StatusCode::BAD_REQUEST, "Email is not valid").into_response();
+    }
+
+    let (code, code_hash) = generate_token(INVITATION_PREFIX);
+    let expires_at = Utc::now() + INVITATION_VALIDITY;
+
+    let id = match sqlx::query_scalar!(
+        r#"
+        INSERT INTO organization_invitation (organization_id, email, role, code_hash, created_by, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id
+        "#,
+        request.organization_id,
+        email,
+        request.role as OrganizationRole,
+        code_hash,
+        user.id,
+        expires_at.naive_utc()
+    )
+    .fetch_one(db)
+    .await
+    {
+        Ok(id) => id,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    audit
+        .event(
+            AuditAction::MemberInvited,
+            AuditTarget::OrganizationInvitation,
+            id,
+        )
+        .organization(Some(request.organization_id))
+        .after(json!({ "email": email, "role": request.role }))
+        .record()
+        .await;
+
+    (
+        StatusCode::CREATED,
+        Sonic(InviteMemberResponse {
+            id,
+            code,
+            expires_at,
+        }),
+    )
+        .into_response()
+}
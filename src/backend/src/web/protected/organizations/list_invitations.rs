@@ -0,0 +1,108 @@
+use axum::{extract::Query, response::IntoResponse};
+use axum_serde::Sonic;
+use chrono::{DateTime, Utc};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    app::openapi::ORGANIZATION_TAG,
+    users::AuthSession,
+    web::protected::organizations::{OrganizationRole, ensure_member_role},
+};
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ListInvitationsQuery {
+    /// The ID of the organization
+    organization_id: i32,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListInvitationsResponse {
+    /// The invitations that were not accepted yet
+    invitations: Vec<InvitationData>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct InvitationData {
+    /// The ID of the invitation
+    id: i32,
+    /// The email of the invited user
+    email: String,
+    /// The role the user will have in the organization
+    role: OrganizationRole,
+    /// The email of the member who created the invitation
+    created_by: String,
+    /// The time at which the invitation was created
+    created_at: DateTime<Utc>,
+    /// The time after which the invitation cannot be accepted anymore
+    expires_at: DateTime<Utc>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/list_invitations",
+    params(ListInvitationsQuery),
+    summary = "List Invitations",
+    responses(
+        (status = OK, description = "List of invitations", body = ListInvitationsResponse),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = FORBIDDEN, description = "User cannot manage the members"),
+        (status = NOT_FOUND, description = "Organization not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = []),
+        ("api_token" = [])
+    ),
+    tag = ORGANIZATION_TAG
+)]
+pub async fn list_invitations(
+    auth_session: AuthSession,
+    Query(query): Query<ListInvitationsQuery>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let db = &auth_session.backend.db;
+
+    if let Err(response) =
+        ensure_member_role(db, query.organization_id, user.id, OrganizationRole::Admin).await
+    {
+        return response;
+    }
+
+    let invitations = match sqlx::query!(
+        r#"
+        SELECT i.id, i.email, i.role AS "role: OrganizationRole", u.email AS created_by,
+               i.created_at, i.expires_at
+        FROM organization_invitation i
+            JOIN "user" u ON i.created_by = u.id
+        WHERE i.organization_id = $1
+          AND i.accepted_at IS NULL
+        ORDER BY i.created_at
+        "#,
+        query.organization_id
+    )
+    .fetch_all(db)
+    .await
+    {
+        Ok(invitations) => invitations,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let invitations = invitations
+        .into_iter()
+        .map(|invitation| InvitationData {
+            id: invitation.id,
+            email: invitation.email,
+            role: invitation.role,
+            created_by: invitation.created_by,
+            created_at: invitation.created_at.and_utc(),
+            expires_at: invitation.expires_at.and_utc(),
+        })
+        .collect();
+
+    Sonic(ListInvitationsResponse { invitations }).into_response()
+}
@@ -0,0 +1,102 @@
+use axum::{extract::Query, response::IntoResponse};
+use axum_serde::Sonic;
+use chrono::{DateTime, Utc};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    app::openapi::ORGANIZATION_TAG,
+    users::AuthSession,
+    web::protected::organizations::{OrganizationRole, ensure_member_role},
+};
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ListMembersQuery {
+    /// The ID of the organization
+    organization_id: i32,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListMembersResponse {
+    /// The members of the organization
+    members: Vec<MemberData>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MemberData {
+    /// The ID of the user
+    user_id: i32,
+    /// The email of the user
+    email: String,
+    /// The role of the user in the organization
+    role: OrganizationRole,
+    /// Whether the user is alerted when the systems of the organization go down
+    receive_alerts: bool,
+    /// The time at which the user joined the organization
+    joined_at: DateTime<Utc>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/list_members",
+    params(ListMembersQuery),
+    summary = "List Members",
+    responses(
+        (status = OK, description = "List of members", body = ListMembersResponse),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = NOT_FOUND, description = "Organization not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = []),
+        ("api_token" = [])
+    ),
+    tag = ORGANIZATION_TAG
+)]
+pub async fn list_members(
+    auth_session: AuthSession,
+    Query(query): Query<ListMembersQuery>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let db = &auth_session.backend.db;
+
+    if let Err(response) =
+        ensure_member_role(db, query.organization_id, user.id, OrganizationRole::Viewer).await
+    {
+        return response;
+    }
+
+    let members = match sqlx::query!(
+        r#"
+        SELECT m.user_id, u.email, m.role AS "role: OrganizationRole", m.receive_alerts, m.joined_at
+        FROM organization_member m
+            JOIN "user" u ON m.user_id = u.id
+        WHERE m.organization_id = $1
+        ORDER BY m.joined_at, m.user_id
+        "#,
+        query.organization_id
+    )
+    .fetch_all(db)
+    .await
+    {
+        Ok(members) => members,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let members = members
+        .into_iter()
+        .map(|member| MemberData {
+            user_id: member.user_id,
+            email: member.email,
+            role: member.role,
+            receive_alerts: member.receive_alerts,
+            joined_at: member.joined_at.and_utc(),
+        })
+        .collect();
+
+    Sonic(ListMembersResponse { members }).into_response()
+}
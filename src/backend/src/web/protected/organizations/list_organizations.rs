@@ -0,0 +1,82 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use chrono::{DateTime, Utc};
+use http::StatusCode;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    app::openapi::ORGANIZATION_TAG, users::AuthSession,
+    web::protected::organizations::OrganizationRole,
+};
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListOrganizationsResponse {
+    /// The organizations the user is a member of
+    organizations: Vec<OrganizationData>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OrganizationData {
+    /// The ID of the organization
+    id: i32,
+    /// The name of the organization
+    name: String,
+    /// The role of the user in the organization
+    role: OrganizationRole,
+    /// Whether the user is alerted when the systems of the organization go down
+    receive_alerts: bool,
+    /// The time at which the user joined the organization
+    joined_at: DateTime<Utc>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/list_organizations",
+    summary = "List Organizations",
+    responses(
+        (status = OK, description = "List of organizations", body = ListOrganizationsResponse),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = []),
+        ("api_token" = [])
+    ),
+    tag = ORGANIZATION_TAG
+)]
+pub async fn list_organizations(auth_session: AuthSession) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let organizations = match sqlx::query!(
+        r#"
+        SELECT o.id, o.name, m.role AS "role: OrganizationRole", m.receive_alerts, m.joined_at
+        FROM organization o
+            JOIN organization_member m ON m.organization_id = o.id
+        WHERE m.user_id = $1
+        ORDER BY o.name, o.id
+        "#,
+        user.id
+    )
+    .fetch_all(&auth_session.backend.db)
+    .await
+    {
+        Ok(organizations) => organizations,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let organizations = organizations
+        .into_iter()
+        .map(|organization| OrganizationData {
+            id: organization.id,
+            name: organization.name,
+            role: organization.role,
+            receive_alerts: organization.receive_alerts,
+            joined_at: organization.joined_at.and_utc(),
+        })
+        .collect();
+
+    Sonic(ListOrganizationsResponse { organizations }).into_response()
+}
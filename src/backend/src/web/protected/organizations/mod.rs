@@ -0,0 +1,435 @@
+mod accept_invitation;
+mod change_member_role;
+mod create_organization;
+mod delete_organization;
+mod invite_member;
+mod list_invitations;
+mod list_members;
+mod list_organizations;
+mod move_system;
+mod remove_member;
+mod revoke_invitation;
+mod set_alerts;
+
+use axum::response::{IntoResponse, Response};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgPool};
+use utoipa::ToSchema;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+pub fn router() -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes![create_organization::create_organization])
+        .routes(routes![list_organizations::list_organizations])
+        .routes(routes![delete_organization::delete_organization])
+        .routes(routes![list_members::list_members])
+        .routes(routes![change_member_role::change_member_role])
+        .routes(routes![remove_member::remove_member])
+        .routes(routes![set_alerts::set_alerts])
+        .routes(routes![invite_member::invite_member])
+        .routes(routes![list_invitations::list_invitations])
+        .routes(routes![revoke_invitation::revoke_invitation])
+        .routes(routes![accept_invitation::accept_invitation])
+        .routes(routes![move_system::move_system])
+}
+
+/// What a member can do in an organization, each role can do everything the
+/// roles below it can
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "organization_role", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum OrganizationRole {
+    /// Can manage the organization itself, such as deleting it
+    Owner,
+    /// Can manage the members and the invitations
+    Admin,
+    /// Can add, edit and delete the systems
+    Editor,
+    /// Can only look at the systems
+    Viewer,
+}
+
+impl OrganizationRole {
+    fn rank(self) -> u8 {
+        match self {
+            OrganizationRole::Owner => 3,
+            OrganizationRole::Admin => 2,
+            OrganizationRole::Editor => 1,
+            OrganizationRole::Viewer => 0,
+        }
+    }
+
+    /// Whether the role allows to do what the minimum role can do
+    pub fn at_least(self, minimum: OrganizationRole) -> bool {
+        self.rank() >= minimum.rank()
+    }
+}
+
+/// The role of the user in the organization, `None` if they are not a member
+pub async fn member_role(
+    db: &PgPool,
+    organization_id: i32,
+    user_id: i32,
+) -> Result<Option<OrganizationRole>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT role AS "role: OrganizationRole"
+        FROM organization_member
+        WHERE organization_id = $1
+          AND user_id = $2
+        "#,
+        organization_id,
+        user_id
+    )
+    .fetch_optional(db)
+    .await
+}
+
+/// Checks that the user has at least the role in the organization, returning
+/// their role. The organizations the user is not a member of are reported as not
+/// found to not reveal that they exist
+pub async fn ensure_member_role(
+    db: &PgPool,
+    organization_id: i32,
+    user_id: i32,
+    minimum: OrganizationRole,
+) -> Result<OrganizationRole, Response> {
+    match member_role(db, organization_id, user_id).await {
+        Ok(Some(role)) if role.at_least(minimum) => Ok(role),
+        Ok(Some(_)) => Err(StatusCode::FORBIDDEN.into_response()),
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+}
+
+/// Whether the organization would be left without owners if the member stopped
+/// being one. The organization is locked until the end of the transaction, so
+/// that two owners cannot demote each other at the same time
+async fn is_last_owner(
+    conn: &mut PgConnection,
+    organization_id: i32,
+    user_id: i32,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
+        "SELECT id FROM organization WHERE id = $1 FOR UPDATE",
+        organization_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    sqlx::query_scalar!(
+        r#"
+        SELECT NOT EXISTS (
+            SELECT 1
+            FROM organization_member
+            WHERE organization_id = $1
+              AND user_id <> $2
+              AND role = 'owner'
+        ) AS "last!"
+        "#,
+        organization_id,
+        user_id
+    )
+    .fetch_one(conn)
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use http::header;
+    use sonic_rs::{JsonContainerTrait, JsonValueTrait, Value, json};
+    use uuid::Uuid;
+
+    use crate::app::test_app::TestApp;
+
+    use super::*;
+
+    #[test]
+    fn test_role_at_least() {
+        assert!(OrganizationRole::Owner.at_least(OrganizationRole::Admin));
+        assert!(OrganizationRole::Editor.at_least(OrganizationRole::Editor));
+        assert!(OrganizationRole::Editor.at_least(OrganizationRole::Viewer));
+        assert!(!OrganizationRole::Viewer.at_least(OrganizationRole::Editor));
+        assert!(!OrganizationRole::Admin.at_least(OrganizationRole::Owner));
+    }
+
+    async fn send(
+        cookie: &str,
+        request: reqwest::RequestBuilder,
+        body: Value,
+    ) -> (StatusCode, Value) {
+        let response = request
+            .header(header::COOKIE, cookie)
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        let status = response.status();
+        let text = response.text().await.unwrap();
+
+        (status, sonic_rs::from_str(&text).unwrap_or_default())
+    }
+
+    async fn create_organization(app: &TestApp, cookie: &str) -> i32 {
+        let (status, response) = send(
+            cookie,
+            app.client
+                .post(app.url("/organizations/create_organization")),
+            json!({ "name": "Acme" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        response["id"].as_i64().unwrap() as i32
+    }
+
+    /// Invites the user with the role and accepts the invitation as them
+    async fn add_member(
+        app: &TestApp,
+        owner_cookie: &str,
+        organization_id: i32,
+        email: &str,
+        role: &str,
+    ) -> (i32, String) {
+        let user_id = app.create_user(email).await;
+        let cookie = app.login(user_id).await;
+
+        let (status, response) = send(
+            owner_cookie,
+            app.client.post(app.url("/organizations/invite_member")),
+            json!({ "organization_id": organization_id, "email": email, "role": role }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let (status, _) = send(
+            &cookie,
+            app.client.post(app.url("/organizations/accept_invitation")),
+            json!({ "code": response["code"] }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        (user_id, cookie)
+    }
+
+    async fn listed_systems(app: &TestApp, cookie: &str) -> Vec<(String, String)> {
+        let response = app
+            .client
+            .get(app.url("/list_systems?page=0&list_size=1"))
+            .header(header::COOKIE, cookie)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response: Value = sonic_rs::from_str(&response.text().await.unwrap()).unwrap();
+        response["systems"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|system| {
+                (
+                    system["name"].as_str().unwrap().to_string(),
+                    system["role"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect()
+    }
+
+    async fn rename(app: &TestApp, cookie: &str, id: Uuid) -> StatusCode {
+        send(
+            cookie,
+            app.client.patch(app.url("/edit_system_name")),
+            json!({ "id": id, "name": "Renamed" }),
+        )
+        .await
+        .0
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a PostgreSQL database (set DATABASE_URL)"]
+    async fn test_organization_systems(db: PgPool) {
+        let app = TestApp::spawn(db).await;
+        let owner_id = app.create_user("owner@example.com").await;
+        let owner_cookie = app.login(owner_id).await;
+        let organization_id = create_organization(&app, &owner_cookie).await;
+
+        let (_, viewer_cookie) = add_member(
+            &app,
+            &owner_cookie,
+            organization_id,
+            "viewer@example.com",
+            "viewer",
+        )
+        .await;
+        let (editor_id, editor_cookie) = add_member(
+            &app,
+            &owner_cookie,
+            organization_id,
+            "editor@example.com",
+            "editor",
+        )
+        .await;
+        let outsider_id = app.create_user("outsider@example.com").await;
+        let outsider_cookie = app.login(outsider_id).await;
+
+        let system_id = app.create_system(owner_id, "Backups").await;
+        let (status, _) = send(
+            &owner_cookie,
+            app.client.patch(app.url("/organizations/move_system")),
+            json!({ "id": system_id, "organization_id": organization_id }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        app.create_system(editor_id, "Personal").await;
+
+        assert_eq!(
+            listed_systems(&app, &viewer_cookie).await,
+            [("Backups".to_string(), "viewer".to_string())]
+        );
+        assert_eq!(
+            listed_systems(&app, &editor_cookie).await,
+            [
+                ("Backups".to_string(), "editor".to_string()),
+                ("Personal".to_string(), "owner".to_string())
+            ]
+        );
+        assert!(listed_systems(&app, &outsider_cookie).await.is_empty());
+
+        assert_eq!(
+            rename(&app, &viewer_cookie, system_id).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            rename(&app, &outsider_cookie, system_id).await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            rename(&app, &editor_cookie, system_id).await,
+            StatusCode::OK
+        );
+
+        // Editors cannot take the systems out of the organization
+        let (status, _) = send(
+            &editor_cookie,
+            app.client.patch(app.url("/organizations/move_system")),
+            json!({ "id": system_id }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, _) = send(
+            &owner_cookie,
+            app.client
+                .delete(app.url("/organizations/delete_organization")),
+            json!({ "organization_id": organization_id }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a PostgreSQL database (set DATABASE_URL)"]
+    async fn test_members_and_invitations(db: PgPool) {
+        let app = TestApp::spawn(db).await;
+        let owner_id = app.create_user("owner@example.com").await;
+        let owner_cookie = app.login(owner_id).await;
+        let organization_id = create_organization(&app, &owner_cookie).await;
+
+        let (admin_id, admin_cookie) = add_member(
+            &app,
+            &owner_cookie,
+            organization_id,
+            "admin@example.com",
+            "admin",
+        )
+        .await;
+
+        // Admins cannot create owners, and invitations only work for their email
+        let (status, _) = send(
+            &admin_cookie,
+            app.client.post(app.url("/organizations/invite_member")),
+            json!({ "organization_id": organization_id, "email": "new@example.com", "role": "owner" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, response) = send(
+            &admin_cookie,
+            app.client.post(app.url("/organizations/invite_member")),
+            json!({ "organization_id": organization_id, "email": "new@example.com", "role": "viewer" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let other_id = app.create_user("other@example.com").await;
+        let other_cookie = app.login(other_id).await;
+        let (status, _) = send(
+            &other_cookie,
+            app.client.post(app.url("/organizations/accept_invitation")),
+            json!({ "code": response["code"] }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = send(
+            &admin_cookie,
+            app.client.delete(app.url("/organizations/remove_member")),
+            json!({ "organization_id": organization_id, "user_id": owner_id }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // The last owner can neither be demoted nor leave
+        let (status, _) = send(
+            &owner_cookie,
+            app.client
+                .patch(app.url("/organizations/change_member_role")),
+            json!({ "organization_id": organization_id, "user_id": owner_id, "role": "admin" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, _) = send(
+            &owner_cookie,
+            app.client.delete(app.url("/organizations/remove_member")),
+            json!({ "organization_id": organization_id, "user_id": owner_id }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, _) = send(
+            &owner_cookie,
+            app.client
+                .patch(app.url("/organizations/change_member_role")),
+            json!({ "organization_id": organization_id, "user_id": admin_id, "role": "owner" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = send(
+            &owner_cookie,
+            app.client.delete(app.url("/organizations/remove_member")),
+            json!({ "organization_id": organization_id, "user_id": owner_id }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            member_role(&app.db, organization_id, owner_id)
+                .await
+                .unwrap(),
+            None
+        );
+
+        let (status, _) = send(
+            &admin_cookie,
+            app.client
+                .delete(app.url("/organizations/delete_organization")),
+            json!({ "organization_id": organization_id }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
@@ -0,0 +1,107 @@
+use axum::response::IntoResponse;
+use http::StatusCode;
+use serde::Deserialize;
+use sonic_rs::json;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    app::openapi::ORGANIZATION_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+    web::{
+        protected::organizations::{OrganizationRole, ensure_member_role},
+        utils::system_access::{EditableSystem, SystemRequest, ensure_system_role},
+    },
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct MoveSystemRequest {
+    /// The ID of the system
+    id: Uuid,
+    /// The organization to move the system to, the system becomes a personal
+    /// system of the user if missing
+    organization_id: Option<i32>,
+}
+
+impl SystemRequest for MoveSystemRequest {
+    fn system_id(&self) -> Uuid {
+        self.id
+    }
+}
+
+#[utoipa::path(
+    patch,
+    path = "/move_system",
+    summary = "Move System",
+    description = "Move a system into an organization (or out of it). The user must be able to manage the system where it is (as its owner, or as an admin of its organization) and to add systems where it goes",
+    request_body = MoveSystemRequest,
+    responses(
+        (status = OK, description = "System was moved successfully"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = FORBIDDEN, description = "User cannot move the system"),
+        (status = NOT_FOUND, description = "System or organization not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = []),
+        ("api_token" = [])
+    ),
+    tag = ORGANIZATION_TAG
+)]
+pub async fn move_system(
+    auth_session: AuthSession,
+    audit: Audit,
+    EditableSystem(request): EditableSystem<MoveSystemRequest>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let db = &auth_session.backend.db;
+
+    if let Err(response) =
+        ensure_system_role(db, user.id, request.id, OrganizationRole::Admin).await
+    {
+        return response;
+    }
+
+    if let Some(organization_id) = request.organization_id
+        && let Err(response) =
+            ensure_member_role(db, organization_id, user.id, OrganizationRole::Editor).await
+    {
+        return response;
+    }
+
+    // A system moved out of an organization becomes a personal system of the user
+    // who moved it
+    let Ok(previous_organization_id) = sqlx::query_scalar!(
+        r#"
+        UPDATE system s
+        SET organization_id = $2,
+            user_id = CASE WHEN $2::integer IS NULL THEN $3 ELSE s.user_id END
+        FROM (SELECT id, organization_id FROM system WHERE id = $1 FOR UPDATE) old
+        WHERE s.id = old.id
+        RETURNING old.organization_id
+        "#,
+        request.id,
+        request.organization_id,
+        user.id
+    )
+    .fetch_one(db)
+    .await
+    else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    // The event goes to the organization the system was moved out of, if any
+    audit
+        .event(AuditAction::SystemMoved, AuditTarget::System, request.id)
+        .organization(previous_organization_id.or(request.organization_id))
+        .before(json!({ "organization_id": previous_organization_id }))
+        .after(json!({ "organization_id": request.organization_id }))
+        .record()
+        .await;
+
+    StatusCode::OK.into_response()
+}
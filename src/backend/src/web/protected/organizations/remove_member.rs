@@ -0,0 +1,128 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::Deserialize;
+use sonic_rs::json;
+use utoipa::ToSchema;
+
+use crate::{
+    app::openapi::ORGANIZATION_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+    web::protected::organizations::{
+        OrganizationRole, ensure_member_role, is_last_owner, member_role,
+    },
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RemoveMemberRequest {
+    /// The ID of the organization
+    organization_id: i32,
+    /// The ID of the member to remove, the user can remove themselves to leave
+    /// the organization
+    user_id: i32,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/remove_member",
+    summary = "Remove Member",
+    description = "Remove a member from the organization (or leave it), only the owners can remove other owners. The last owner of an organization cannot leave it",
+    request_body = RemoveMemberRequest,
+    responses(
+        (status = OK, description = "Member was removed successfully"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = FORBIDDEN, description = "User cannot remove the member"),
+        (status = NOT_FOUND, description = "Organization or member not found"),
+        (status = CONFLICT, description = "The member is the last owner of the organization"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = []),
+        ("api_token" = [])
+    ),
+    tag = ORGANIZATION_TAG
+)]
+pub async fn remove_member(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<RemoveMemberRequest>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let db = &auth_session.backend.db;
+
+    let role = match ensure_member_role(
+        db,
+        request.organization_id,
+        user.id,
+        OrganizationRole::Viewer,
+    )
+    .await
+    {
+        Ok(role) => role,
+        Err(response) => return response,
+    };
+
+    let member = match member_role(db, request.organization_id, request.user_id).await {
+        Ok(Some(member)) => member,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let allowed = request.user_id == user.id
+        || match member {
+            OrganizationRole::Owner => role == OrganizationRole::Owner,
+            _ => role.at_least(OrganizationRole::Admin),
+        };
+    if !allowed {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let Ok(mut tx) = db.begin().await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    if member == OrganizationRole::Owner {
+        match is_last_owner(&mut tx, request.organization_id, request.user_id).await {
+            Ok(false) => {}
+            Ok(true) => {
+                return (
+                    StatusCode::CONFLICT,
+                    "The last owner of the organization cannot be removed",
+                )
+                    .into_response();
+            }
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+
+    let removed = sqlx::query!(
+        r#"
+        DELETE FROM organization_member WHERE organization_id = $1 AND user_id = $2
+        "#,
+        request.organization_id,
+        request.user_id
+    )
+    .execute(&mut *tx)
+    .await;
+
+    if removed.is_err() || tx.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    audit
+        .event(
+            AuditAction::MemberRemoved,
+            AuditTarget::OrganizationMember,
+            request.user_id,
+        )
+        .organization(Some(request.organization_id))
+        .before(json!({ "role": member }))
+        .record()
+        .await;
+
+    StatusCode::OK.into_response()
+}
@@ -0,0 +1,95 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::Deserialize;
+use sonic_rs::json;
+use utoipa::ToSchema;
+
+use crate::{
+    app::openapi::ORGANIZATION_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+    web::protected::organizations::{OrganizationRole, ensure_member_role},
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RevokeInvitationRequest {
+    /// The ID of the organization
+    organization_id: i32,
+    /// The ID of the invitation to revoke
+    id: i32,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/revoke_invitation",
+    summary = "Revoke Invitation",
+    description = "Revoke an invitation that was not accepted yet",
+    request_body = RevokeInvitationRequest,
+    responses(
+        (status = OK, description = "Invitation was revoked successfully"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = FORBIDDEN, description = "User cannot manage the members"),
+        (status = NOT_FOUND, description = "Organization or invitation not found (or already accepted)"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = []),
+        ("api_token" = [])
+    ),
+    tag = ORGANIZATION_TAG
+)]
+pub async fn revoke_invitation(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<RevokeInvitationRequest>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let db = &auth_session.backend.db;
+
+    if let Err(response) = ensure_member_role(
+        db,
+        request.organization_id,
+        user.id,
+        OrganizationRole::Admin,
+    )
+    .await
+    {
+        return response;
+    }
+
+    let invitation = match sqlx::query!(
+        r#"
+        DELETE FROM organization_invitation
+        WHERE id = $1
+          AND organization_id = $2
+          AND accepted_at IS NULL
+        RETURNING email, role AS "role: OrganizationRole"
+        "#,
+        request.id,
+        request.organization_id
+    )
+    .fetch_optional(db)
+    .await
+    {
+        Ok(Some(invitation)) => invitation,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    audit
+        .event(
+            AuditAction::InvitationRevoked,
+            AuditTarget::OrganizationInvitation,
+            request.id,
+        )
+        .organization(Some(request.organization_id))
+        .before(json!({ "email": invitation.email, "role": invitation.role }))
+        .record()
+        .await;
+
+    StatusCode::OK.into_response()
+}
@@ -0,0 +1,81 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::Deserialize;
+use sonic_rs::json;
+use utoipa::ToSchema;
+
+use crate::{
+    app::openapi::ORGANIZATION_TAG,
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SetAlertsRequest {
+    /// The ID of the organization
+    organization_id: i32,
+    /// Whether the user is alerted (by email and through their notification
+    /// channels) when the systems of the organization go down
+    receive_alerts: bool,
+}
+
+#[utoipa::path(
+    patch,
+    path = "/set_alerts",
+    summary = "Set Alerts",
+    description = "Choose whether to be alerted about the systems of the organization",
+    request_body = SetAlertsRequest,
+    responses(
+        (status = OK, description = "Preference was saved successfully"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = NOT_FOUND, description = "Organization not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = []),
+        ("api_token" = [])
+    ),
+    tag = ORGANIZATION_TAG
+)]
+pub async fn set_alerts(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<SetAlertsRequest>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    match sqlx::query!(
+        r#"
+        UPDATE organization_member
+        SET receive_alerts = $3
+        WHERE organization_id = $1
+          AND user_id = $2
+        "#,
+        request.organization_id,
+        user.id,
+        request.receive_alerts
+    )
+    .execute(&auth_session.backend.db)
+    .await
+    {
+        Ok(result) if result.rows_affected() == 0 => return StatusCode::NOT_FOUND.into_response(),
+        Ok(_) => {}
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+
+    audit
+        .event(
+            AuditAction::MemberAlertsChanged,
+            AuditTarget::OrganizationMember,
+            user.id,
+        )
+        .organization(Some(request.organization_id))
+        .after(json!({ "receive_alerts": request.receive_alerts }))
+        .record()
+        .await;
+
+    StatusCode::OK.into_response()
+}
@@ -0,0 +1,95 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::Deserialize;
+use sonic_rs::json;
+use utoipa::ToSchema;
+
+use crate::{
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+    web::auth::login::AuthError,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ChangeEmailRequest {
+    /// The new email address
+    new_email: String,
+    /// The password of the user, to confirm the change (not needed for the
+    /// accounts without a password, such as the ones created by single sign-on)
+    password: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/change_email",
+    summary = "Change Email",
+    description = "Send a link to the new email address, which replaces the current one once the link is opened (under /confirm_email_change), and a notice to the current address",
+    request_body = ChangeEmailRequest,
+    responses(
+        (status = OK, description = "Confirmation link was sent successfully"),
+        (status = BAD_REQUEST, description = "Email is not valid"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = FORBIDDEN, description = "Password is wrong"),
+        (status = CONFLICT, description = "Email is already in use"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = "User"
+)]
+pub async fn change_email(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<ChangeEmailRequest>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let new_email = request.new_email.trim().to_string();
+    if !new_email.contains('@') || new_email == user.email {
+        return AuthError::InvalidEmail.into_response();
+    }
+
+    if user.password.is_some() {
+        match auth_session
+            .backend
+            .check_password(&user.email, request.password.unwrap_or_default())
+            .await
+        {
+            Ok(Some(_)) => {}
+            Ok(None) => return (StatusCode::FORBIDDEN, "Password is wrong").into_response(),
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+
+    // Checked again once the change is confirmed, as the address could be taken
+    // in the meantime
+    match sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM "user" WHERE email = $1) AS "exists!""#,
+        new_email
+    )
+    .fetch_one(&auth_session.backend.db)
+    .await
+    {
+        Ok(false) => {}
+        Ok(true) => return AuthError::EmailAlreadyInUse.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+
+    auth_session.backend.send_email_change(&user, &new_email);
+
+    audit
+        .event(
+            AuditAction::EmailChangeRequested,
+            AuditTarget::User,
+            user.id,
+        )
+        .after(json!({ "email": new_email }))
+        .record()
+        .await;
+
+    StatusCode::OK.into_response()
+}
@@ -5,10 +5,14 @@ use http::StatusCode;
 use rust_i18n::available_locales;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use sonic_rs::json;
 use thiserror::Error;
 use utoipa::ToSchema;
 
-use crate::users::AuthSession;
+use crate::{
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+};
 
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct ChangeLanguageRequest {
@@ -47,6 +51,7 @@ pub enum ChangeLanguageError {
 )]
 pub async fn change_language(
     auth_session: AuthSession,
+    audit: Audit,
     Sonic(request): Sonic<ChangeLanguageRequest>,
 ) -> impl IntoResponse {
     let current_user = match auth_session.user {
@@ -77,5 +82,16 @@ pub async fn change_language(
         Err(_) => return ChangeLanguageError::FailedToUpdateLanguage.into_response(),
     }
 
+    audit
+        .event(
+            AuditAction::LanguageChanged,
+            AuditTarget::User,
+            current_user.id,
+        )
+        .before(json!({ "language": current_user.language }))
+        .after(json!({ "language": request.language }))
+        .record()
+        .await;
+
     StatusCode::OK.into_response()
 }
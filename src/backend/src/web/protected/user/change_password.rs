@@ -9,7 +9,11 @@ use thiserror::Error;
 use tokio::task;
 use utoipa::ToSchema;
 
-use crate::users::{AuthSession, Credentials};
+use crate::{
+    audit::{Audit, AuditAction, AuditTarget},
+    middleware::track_session::forget_user_sessions,
+    users::AuthSession,
+};
 
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct ChangePasswordRequest {
@@ -56,6 +60,7 @@ pub enum ChangePasswordError {
 )]
 pub async fn change_password(
     auth_session: AuthSession,
+    audit: Audit,
     Sonic(request): Sonic<ChangePasswordRequest>,
 ) -> impl IntoResponse {
     let current_user = match auth_session.user {
@@ -63,12 +68,11 @@ pub async fn change_password(
         None => return ChangePasswordError::UserNotLoggedIn.into_response(),
     };
 
-    let credentials_to_authenticate = Credentials {
-        email: current_user.email.clone(),
-        password: request.old_password.clone(),
-    };
-
-    match auth_session.authenticate(credentials_to_authenticate).await {
+    match auth_session
+        .backend
+        .check_password(&current_user.email, request.old_password.clone())
+        .await
+    {
         Ok(Some(_)) => {}
         Ok(None) => return ChangePasswordError::OldPasswordIsWrong.into_response(),
         Err(_) => return ChangePasswordError::FailedToAuthenticateWithOldPassword.into_response(),
@@ -94,5 +98,22 @@ pub async fn change_password(
         Err(_) => return ChangePasswordError::FailedToUpdatePassword.into_response(),
     }
 
+    // The new password invalidates all the sessions, including the current one
+    if forget_user_sessions(&auth_session.backend.db, current_user.id)
+        .await
+        .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    audit
+        .event(
+            AuditAction::PasswordChanged,
+            AuditTarget::User,
+            current_user.id,
+        )
+        .record()
+        .await;
+
     StatusCode::OK.into_response()
 }
@@ -6,10 +6,14 @@ use axum_thiserror::ErrorStatus;
 use http::StatusCode;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use sonic_rs::json;
 use thiserror::Error;
 use utoipa::ToSchema;
 
-use crate::users::AuthSession;
+use crate::{
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+};
 
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct ChangeTimezoneRequest {
@@ -48,6 +52,7 @@ pub enum ChangeTimezoneError {
 )]
 pub async fn change_timezone(
     auth_session: AuthSession,
+    audit: Audit,
     Sonic(request): Sonic<ChangeTimezoneRequest>,
 ) -> impl IntoResponse {
     let current_user = match auth_session.user {
@@ -74,5 +79,16 @@ pub async fn change_timezone(
         Err(_) => return ChangeTimezoneError::FailedToUpdateTimezone.into_response(),
     }
 
+    audit
+        .event(
+            AuditAction::TimezoneChanged,
+            AuditTarget::User,
+            current_user.id,
+        )
+        .before(json!({ "timezone": current_user.timezone }))
+        .after(json!({ "timezone": tz.to_string() }))
+        .record()
+        .await;
+
     StatusCode::OK.into_response()
 }
@@ -0,0 +1,138 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::{
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+    web::auth::totp::{RECOVERY_CODES, generate_recovery_code, use_totp_code},
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ConfirmTotpRequest {
+    /// A code from the authenticator app
+    code: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ConfirmTotpResponse {
+    /// The single-use codes to log in without the authenticator app. They are
+    /// not stored, so they cannot be retrieved again
+    recovery_codes: Vec<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/confirm_totp",
+    summary = "Confirm TOTP",
+    description = "Enable 2FA with a code from the authenticator app the secret was added to, from now on the code is required to log in",
+    request_body = ConfirmTotpRequest,
+    responses(
+        (status = OK, description = "2FA was enabled successfully", body = ConfirmTotpResponse),
+        (status = BAD_REQUEST, description = "Code is not valid"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = NOT_FOUND, description = "There is no secret waiting to be confirmed"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = "User"
+)]
+pub async fn confirm_totp(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<ConfirmTotpRequest>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let Ok(mut tx) = auth_session.backend.db.begin().await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    let secret = match sqlx::query_scalar!(
+        r#"
+        SELECT secret FROM totp WHERE user_id = $1 AND confirmed_at IS NULL FOR UPDATE
+        "#,
+        user.id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    {
+        Ok(Some(secret)) => secret,
+        Ok(None) => {
+            return (StatusCode::NOT_FOUND, "There is no secret to confirm").into_response();
+        }
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    match use_totp_code(&mut tx, user.id, &secret, &request.code).await {
+        Ok(true) => {}
+        Ok(false) => return (StatusCode::BAD_REQUEST, "Code is not valid").into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+
+    let (recovery_codes, code_hashes): (Vec<_>, Vec<_>) = (0..RECOVERY_CODES)
+        .map(|_| generate_recovery_code())
+        .unzip();
+
+    if sqlx::query!(
+        r#"
+        UPDATE totp SET confirmed_at = NOW() WHERE user_id = $1
+        "#,
+        user.id
+    )
+    .execute(&mut *tx)
+    .await
+    .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    // The codes of a previous enrolment cannot be used anymore
+    if sqlx::query!(
+        r#"
+        DELETE FROM recovery_code WHERE user_id = $1
+        "#,
+        user.id
+    )
+    .execute(&mut *tx)
+    .await
+    .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    if sqlx::query!(
+        r#"
+        INSERT INTO recovery_code (user_id, code_hash)
+        SELECT $1, UNNEST($2::text[])
+        "#,
+        user.id,
+        &code_hashes
+    )
+    .execute(&mut *tx)
+    .await
+    .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    if tx.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    info!("2FA was enabled for user {}", user.email);
+
+    audit
+        .event(AuditAction::TotpEnabled, AuditTarget::User, user.id)
+        .record()
+        .await;
+
+    Sonic(ConfirmTotpResponse { recovery_codes }).into_response()
+}
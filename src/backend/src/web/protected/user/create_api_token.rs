@@ -0,0 +1,124 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use sonic_rs::json;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    audit::{Audit, AuditAction, AuditTarget},
+    middleware::api_token::generate_api_token,
+    users::AuthSession,
+    web::{protected::organizations::OrganizationRole, utils::system_access::ensure_system_role},
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateApiTokenRequest {
+    /// A name to recognize the token
+    name: String,
+    /// Whether the token can only be used to read
+    #[serde(default)]
+    read_only: bool,
+    /// The systems the token is restricted to, all the systems of the user if
+    /// missing
+    system_ids: Option<Vec<Uuid>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreateApiTokenResponse {
+    /// The ID of the token
+    id: i32,
+    /// The token, to be sent as `Authorization: Bearer <token>`. It is not
+    /// stored, so it cannot be retrieved again
+    token: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/create_api_token",
+    summary = "Create API Token",
+    description = "Create a personal API token, to authenticate scripts without the session cookie",
+    request_body = CreateApiTokenRequest,
+    responses(
+        (status = CREATED, description = "Token was created successfully", body = CreateApiTokenResponse),
+        (status = BAD_REQUEST, description = "Bad request"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = NOT_FOUND, description = "System not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = "User"
+)]
+pub async fn create_api_token(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<CreateApiTokenRequest>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let name = request.name.trim();
+    if name.is_empty() {
+        return (StatusCode::BAD_REQUEST, "Name is not valid").into_response();
+    }
+
+    if let Some(system_ids) = &request.system_ids {
+        if system_ids.is_empty() {
+            return (StatusCode::BAD_REQUEST, "Systems are not valid").into_response();
+        }
+
+        for &system_id in system_ids {
+            if let Err(response) = ensure_system_role(
+                &auth_session.backend.db,
+                user.id,
+                system_id,
+                OrganizationRole::Viewer,
+            )
+            .await
+            {
+                return response;
+            }
+        }
+    }
+
+    let (token, token_hash) = generate_api_token();
+
+    let id = match sqlx::query_scalar!(
+        r#"
+        INSERT INTO api_token (user_id, name, token_hash, read_only, system_ids)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id
+        "#,
+        user.id,
+        name,
+        token_hash,
+        request.read_only,
+        request.system_ids.as_deref()
+    )
+    .fetch_one(&auth_session.backend.db)
+    .await
+    {
+        Ok(id) => id,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    audit
+        .event(AuditAction::ApiTokenCreated, AuditTarget::ApiToken, id)
+        .after(json!({
+            "name": name,
+            "read_only": request.read_only,
+            "system_ids": request.system_ids,
+        }))
+        .record()
+        .await;
+
+    (
+        StatusCode::CREATED,
+        Sonic(CreateApiTokenResponse { id, token }),
+    )
+        .into_response()
+}
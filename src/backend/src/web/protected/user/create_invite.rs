@@ -0,0 +1,87 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use chrono::{DateTime, Duration, Utc};
+use http::StatusCode;
+use serde::Serialize;
+use sonic_rs::json;
+use utoipa::ToSchema;
+
+use crate::{
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+    web::utils::token::generate_token,
+};
+
+/// The prefix of the invite codes
+pub const INVITE_PREFIX: &str = "inv_";
+
+/// How long an invite can be used for
+const INVITE_VALIDITY: Duration = Duration::days(7);
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreateInviteResponse {
+    /// The ID of the invite
+    id: i32,
+    /// The invite code, to be sent along with the sign up. It is not stored, so
+    /// it cannot be retrieved again
+    code: String,
+    /// The time after which the invite cannot be used anymore
+    expires_at: DateTime<Utc>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/create_invite",
+    summary = "Create Invite",
+    description = "Create an invite, needed to sign up when registration is invite-only. Each invite can be used once",
+    responses(
+        (status = CREATED, description = "Invite was created successfully", body = CreateInviteResponse),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = "User"
+)]
+pub async fn create_invite(auth_session: AuthSession, audit: Audit) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let (code, code_hash) = generate_token(INVITE_PREFIX);
+    let expires_at = Utc::now() + INVITE_VALIDITY;
+
+    let id = match sqlx::query_scalar!(
+        r#"
+        INSERT INTO invite (created_by, code_hash, expires_at)
+        VALUES ($1, $2, $3)
+        RETURNING id
+        "#,
+        user.id,
+        code_hash,
+        expires_at.naive_utc()
+    )
+    .fetch_one(&auth_session.backend.db)
+    .await
+    {
+        Ok(id) => id,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    audit
+        .event(AuditAction::InviteCreated, AuditTarget::Invite, id)
+        .after(json!({ "expires_at": expires_at }))
+        .record()
+        .await;
+
+    (
+        StatusCode::CREATED,
+        Sonic(CreateInviteResponse {
+            id,
+            code,
+            expires_at,
+        }),
+    )
+        .into_response()
+}
@@ -0,0 +1,427 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::Deserialize;
+use sonic_rs::json;
+use tracing::error;
+use utoipa::ToSchema;
+
+use crate::{
+    audit::{Audit, AuditAction, AuditTarget},
+    middleware::track_session::destroy_sessions,
+    users::AuthSession,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct DeleteAccountRequest {
+    /// The password of the user, to confirm the deletion (not needed for the
+    /// accounts without a password, such as the ones created by single sign-on)
+    password: Option<String>,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/delete_account",
+    summary = "Delete Account",
+    description = "Delete the account of the user along with their personal systems, pings, incidents and settings, and log out all their sessions. The systems created in organizations are handed over to an owner of the organization. The audit events of the user are kept without their actor, address and email addresses",
+    request_body = DeleteAccountRequest,
+    responses(
+        (status = OK, description = "Account was deleted successfully"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = FORBIDDEN, description = "Password is wrong"),
+        (status = CONFLICT, description = "User is the last owner of an organization"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = "User"
+)]
+pub async fn delete_account(
+    mut auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<DeleteAccountRequest>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user.clone() else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    if user.password.is_some() {
+        match auth_session
+            .backend
+            .check_password(&user.email, request.password.unwrap_or_default())
+            .await
+        {
+            Ok(Some(_)) => {}
+            Ok(None) => return (StatusCode::FORBIDDEN, "Password is wrong").into_response(),
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+
+    let Ok(mut tx) = auth_session.backend.db.begin().await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    // The organizations would be left without an owner
+    let Ok(owned_organizations) = sqlx::query_scalar!(
+        r#"
+        SELECT o.name
+        FROM organization o
+            JOIN organization_member m ON m.organization_id = o.id
+        WHERE m.user_id = $1
+          AND m.role = 'owner'
+          AND NOT EXISTS (
+              SELECT 1
+              FROM organization_member other
+              WHERE other.organization_id = o.id
+                AND other.user_id <> $1
+                AND other.role = 'owner'
+          )
+        FOR UPDATE OF o
+        "#,
+        user.id
+    )
+    .fetch_all(&mut *tx)
+    .await
+    else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    if !owned_organizations.is_empty() {
+        return (
+            StatusCode::CONFLICT,
+            format!(
+                "You are the last owner of {}, transfer or delete them first",
+                owned_organizations.join(", ")
+            ),
+        )
+            .into_response();
+    }
+
+    // The systems of the organizations stay with them, under the longest-standing
+    // owner
+    let handed_over = sqlx::query!(
+        r#"
+        UPDATE system s
+        SET user_id = (
+            SELECT m.user_id
+            FROM organization_member m
+            WHERE m.organization_id = s.organization_id
+              AND m.user_id <> $1
+              AND m.role = 'owner'
+            ORDER BY m.joined_at
+            LIMIT 1
+        )
+        WHERE s.user_id = $1
+          AND s.organization_id IS NOT NULL
+        "#,
+        user.id
+    )
+    .execute(&mut *tx)
+    .await;
+
+    if handed_over.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    let systems = sqlx::query_scalar!(
+        r#"
+        WITH systems AS (
+            SELECT id FROM system WHERE user_id = $1
+        ),
+        incidents AS (
+            SELECT id FROM incident WHERE system_id IN (SELECT id FROM systems)
+        ),
+        incident_notifications AS (
+            DELETE FROM incident_notification WHERE incident_id IN (SELECT id FROM incidents)
+        ),
+        deleted_incidents AS (
+            DELETE FROM incident WHERE id IN (SELECT id FROM incidents)
+        ),
+        pings AS (
+            DELETE FROM ping WHERE system_id IN (SELECT id FROM systems)
+        ),
+        schedule_versions AS (
+            DELETE FROM schedule_version WHERE system_id IN (SELECT id FROM systems)
+        ),
+        channels AS (
+            DELETE FROM notification_channel
+            WHERE user_id = $1
+               OR system_id IN (SELECT id FROM systems)
+        )
+        DELETE FROM system WHERE id IN (SELECT id FROM systems)
+        RETURNING id
+        "#,
+        user.id
+    )
+    .fetch_all(&mut *tx)
+    .await;
+
+    let Ok(systems) = systems else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    let session_ids = sqlx::query_scalar!(
+        r#"
+        WITH api_tokens AS (
+            DELETE FROM api_token WHERE user_id = $1
+        ),
+        used_invites AS (
+            UPDATE invite SET used_by = NULL WHERE used_by = $1
+        ),
+        invites AS (
+            DELETE FROM invite WHERE created_by = $1
+        ),
+        email_tokens AS (
+            DELETE FROM email_token WHERE user_id = $1
+        ),
+        totp AS (
+            DELETE FROM totp WHERE user_id = $1
+        ),
+        recovery_codes AS (
+            DELETE FROM recovery_code WHERE user_id = $1
+        ),
+        passkeys AS (
+            DELETE FROM passkey WHERE user_id = $1
+        ),
+        memberships AS (
+            DELETE FROM organization_member WHERE user_id = $1
+        ),
+        invitations AS (
+            DELETE FROM organization_invitation WHERE created_by = $1 OR email = $2
+        ),
+        deleted_user AS (
+            DELETE FROM "user" WHERE id = $1
+        )
+        DELETE FROM user_session WHERE user_id = $1
+        RETURNING session_id
+        "#,
+        user.id,
+        user.email
+    )
+    .fetch_all(&mut *tx)
+    .await;
+
+    let Ok(session_ids) = session_ids else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    // The audit trail is kept, without what tells about the user
+    let anonymized = sqlx::query_scalar!(
+        r#"
+        SELECT anonymize_audit_events($1, $2) AS "anonymized!"
+        "#,
+        user.id,
+        user.email
+    )
+    .fetch_one(&mut *tx)
+    .await;
+
+    if anonymized.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    if tx.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    if let Err(e) = auth_session.logout().await {
+        error!("Error logging out the deleted user {}: {}", user.id, e);
+    }
+
+    // The sessions cannot be used anymore without the user, their data is
+    // destroyed as well
+    if let Err(e) = destroy_sessions(
+        &auth_session.backend.redis,
+        session_ids.into_iter().flatten().collect(),
+    )
+    .await
+    {
+        error!("Error destroying the sessions of user {}: {}", user.id, e);
+    }
+
+    audit
+        .event(AuditAction::AccountDeleted, AuditTarget::User, user.id)
+        .before(json!({ "systems": systems.len() }))
+        .anonymous()
+        .record()
+        .await;
+
+    StatusCode::OK.into_response()
+}
+
+#[cfg(test)]
+mod test {
+    use http::header;
+    use password_auth::generate_hash;
+    use sonic_rs::{JsonContainerTrait, Value};
+    use sqlx::PgPool;
+
+    use crate::app::test_app::TestApp;
+
+    use super::*;
+
+    #[sqlx::test]
+    #[ignore = "needs a PostgreSQL database (set DATABASE_URL)"]
+    async fn test_export_and_delete_account(db: PgPool) {
+        let app = TestApp::spawn(db).await;
+        let user_id = app.create_user("leaving@example.com").await;
+        let owner_id = app.create_user("owner@example.com").await;
+        let personal_id = app.create_system(user_id, "Backups").await;
+        let shared_id = app.create_system(user_id, "Shared backups").await;
+
+        sqlx::query!(
+            r#"UPDATE "user" SET password = $2 WHERE id = $1"#,
+            user_id,
+            generate_hash("hunter42")
+        )
+        .execute(&app.db)
+        .await
+        .unwrap();
+
+        sqlx::query!(r#"INSERT INTO ping (system_id) VALUES ($1)"#, personal_id)
+            .execute(&app.db)
+            .await
+            .unwrap();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO audit_event (actor_id, action, target_type, target_id, before, after, ip_address)
+            VALUES ($1, 'email_changed', 'user', $2, '{"email": "old@example.com"}',
+                    '{"email": "leaving@example.com"}', '192.0.2.1')
+            "#,
+            user_id,
+            user_id.to_string()
+        )
+        .execute(&app.db)
+        .await
+        .unwrap();
+
+        let organization_id =
+            sqlx::query_scalar!(r#"INSERT INTO organization (name) VALUES ('Acme') RETURNING id"#)
+                .fetch_one(&app.db)
+                .await
+                .unwrap();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO organization_member (organization_id, user_id, role)
+            VALUES ($1, $2, 'owner')
+            "#,
+            organization_id,
+            user_id
+        )
+        .execute(&app.db)
+        .await
+        .unwrap();
+
+        sqlx::query!(
+            r#"UPDATE system SET organization_id = $2 WHERE id = $1"#,
+            shared_id,
+            organization_id
+        )
+        .execute(&app.db)
+        .await
+        .unwrap();
+
+        let cookie = app.login(user_id).await;
+
+        let response = app
+            .client
+            .get(app.url("/user/export"))
+            .header(header::COOKIE, &cookie)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(
+            response.headers()[header::CONTENT_DISPOSITION]
+                .to_str()
+                .unwrap()
+                .starts_with("attachment")
+        );
+        let export: Value = sonic_rs::from_str(&response.text().await.unwrap()).unwrap();
+        assert_eq!(export["profile"]["email"], "leaving@example.com");
+        assert_eq!(export["systems"].as_array().unwrap().len(), 2);
+        assert_eq!(export["pings"].as_array().unwrap().len(), 1);
+        assert_eq!(export["organizations"].as_array().unwrap().len(), 1);
+
+        let delete = |password: &str| {
+            app.client
+                .delete(app.url("/user/delete_account"))
+                .header(header::COOKIE, &cookie)
+                .json(&json!({ "password": password }))
+                .send()
+        };
+
+        assert_eq!(
+            delete("hunter22").await.unwrap().status(),
+            StatusCode::FORBIDDEN
+        );
+
+        // The organization would be left without an owner
+        assert_eq!(
+            delete("hunter42").await.unwrap().status(),
+            StatusCode::CONFLICT
+        );
+
+        sqlx::query!(
+            r#"
+            INSERT INTO organization_member (organization_id, user_id, role)
+            VALUES ($1, $2, 'owner')
+            "#,
+            organization_id,
+            owner_id
+        )
+        .execute(&app.db)
+        .await
+        .unwrap();
+
+        assert_eq!(delete("hunter42").await.unwrap().status(), StatusCode::OK);
+
+        let user = sqlx::query!(r#"SELECT id FROM "user" WHERE id = $1"#, user_id)
+            .fetch_optional(&app.db)
+            .await
+            .unwrap();
+        assert!(user.is_none());
+
+        // The personal systems are gone, the ones of the organization stay with it
+        let systems = sqlx::query!(r#"SELECT id, user_id FROM system"#)
+            .fetch_all(&app.db)
+            .await
+            .unwrap();
+        assert_eq!(systems.len(), 1);
+        assert_eq!(systems[0].id, shared_id);
+        assert_eq!(systems[0].user_id, owner_id);
+
+        // The audit trail is kept, but it does not tell about the user anymore
+        let events = sqlx::query!(
+            r#"
+            SELECT actor_id, ip_address, before::text AS before, after::text AS after
+            FROM audit_event
+            WHERE target_type = 'user' AND target_id = $1
+            "#,
+            user_id.to_string()
+        )
+        .fetch_all(&app.db)
+        .await
+        .unwrap();
+        assert_eq!(events.len(), 2);
+        for event in events {
+            assert!(event.actor_id.is_none());
+            assert!(event.ip_address.is_none());
+            for values in [event.before, event.after].into_iter().flatten() {
+                assert!(!values.contains("@example.com"), "{values}");
+            }
+        }
+
+        let response = app
+            .client
+            .get(app.url("/user/get_current_settings"))
+            .header(header::COOKIE, &cookie)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
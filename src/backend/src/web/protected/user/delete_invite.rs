@@ -0,0 +1,68 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::Deserialize;
+use sonic_rs::json;
+use utoipa::ToSchema;
+
+use crate::{
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct DeleteInviteRequest {
+    /// The ID of the invite to delete
+    id: i32,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/delete_invite",
+    summary = "Delete Invite",
+    description = "Delete an invite that was not used yet",
+    request_body = DeleteInviteRequest,
+    responses(
+        (status = OK, description = "Invite was deleted successfully"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = NOT_FOUND, description = "Invite not found (or already used)"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = "User"
+)]
+pub async fn delete_invite(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<DeleteInviteRequest>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let invite = match sqlx::query!(
+        r#"
+        DELETE FROM invite WHERE id = $1 AND created_by = $2 AND used_at IS NULL
+        RETURNING expires_at
+        "#,
+        request.id,
+        user.id
+    )
+    .fetch_optional(&auth_session.backend.db)
+    .await
+    {
+        Ok(Some(invite)) => invite,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    audit
+        .event(AuditAction::InviteDeleted, AuditTarget::Invite, request.id)
+        .before(json!({ "expires_at": invite.expires_at.and_utc() }))
+        .record()
+        .await;
+
+    StatusCode::OK.into_response()
+}
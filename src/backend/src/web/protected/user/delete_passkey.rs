@@ -0,0 +1,72 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::Deserialize;
+use sonic_rs::json;
+use utoipa::ToSchema;
+
+use crate::{
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct DeletePasskeyRequest {
+    /// The ID of the passkey to delete
+    id: i32,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/delete_passkey",
+    summary = "Delete Passkey",
+    description = "Delete a passkey, it cannot be used to log in anymore",
+    request_body = DeletePasskeyRequest,
+    responses(
+        (status = OK, description = "Passkey was deleted successfully"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = NOT_FOUND, description = "Passkey not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = "User"
+)]
+pub async fn delete_passkey(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<DeletePasskeyRequest>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let passkey = match sqlx::query!(
+        r#"
+        DELETE FROM passkey WHERE id = $1 AND user_id = $2
+        RETURNING name
+        "#,
+        request.id,
+        user.id
+    )
+    .fetch_optional(&auth_session.backend.db)
+    .await
+    {
+        Ok(Some(passkey)) => passkey,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    audit
+        .event(
+            AuditAction::PasskeyDeleted,
+            AuditTarget::Passkey,
+            request.id,
+        )
+        .before(json!({ "name": passkey.name }))
+        .record()
+        .await;
+
+    StatusCode::OK.into_response()
+}
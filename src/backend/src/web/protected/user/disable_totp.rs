@@ -0,0 +1,101 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::Deserialize;
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::{
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct DisableTotpRequest {
+    /// The password of the user, to confirm
+    password: String,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/disable_totp",
+    summary = "Disable TOTP",
+    description = "Disable 2FA, removing the secret and the recovery codes",
+    request_body = DisableTotpRequest,
+    responses(
+        (status = OK, description = "2FA was disabled successfully"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = FORBIDDEN, description = "Password is wrong"),
+        (status = NOT_FOUND, description = "2FA is not enabled"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = "User"
+)]
+pub async fn disable_totp(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<DisableTotpRequest>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    match auth_session
+        .backend
+        .check_password(&user.email, request.password)
+        .await
+    {
+        Ok(Some(_)) => {}
+        Ok(None) => return (StatusCode::FORBIDDEN, "Password is wrong").into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+
+    let Ok(mut tx) = auth_session.backend.db.begin().await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    match sqlx::query!(
+        r#"
+        DELETE FROM totp WHERE user_id = $1
+        "#,
+        user.id
+    )
+    .execute(&mut *tx)
+    .await
+    {
+        Ok(result) if result.rows_affected() == 0 => {
+            return (StatusCode::NOT_FOUND, "2FA is not enabled").into_response();
+        }
+        Ok(_) => {}
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+
+    if sqlx::query!(
+        r#"
+        DELETE FROM recovery_code WHERE user_id = $1
+        "#,
+        user.id
+    )
+    .execute(&mut *tx)
+    .await
+    .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    if tx.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    info!("2FA was disabled for user {}", user.email);
+
+    audit
+        .event(AuditAction::TotpDisabled, AuditTarget::User, user.id)
+        .record()
+        .await;
+
+    StatusCode::OK.into_response()
+}
@@ -0,0 +1,83 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+    web::auth::totp::{generate_totp_secret, otpauth_uri},
+};
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EnrollTotpResponse {
+    /// The `otpauth://` URI to add the account to an authenticator app, usually
+    /// shown as a QR code
+    otpauth_uri: String,
+    /// The base32 secret, for the authenticator apps that cannot scan the QR
+    /// code
+    secret: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/enroll_totp",
+    summary = "Enroll TOTP",
+    description = "Start enabling 2FA, generating a new secret for the authenticator app. 2FA is only enabled once confirmed with a code from the app",
+    responses(
+        (status = OK, description = "Secret was generated successfully", body = EnrollTotpResponse),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = CONFLICT, description = "2FA is already enabled"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = "User"
+)]
+pub async fn enroll_totp(auth_session: AuthSession, audit: Audit) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let secret = generate_totp_secret();
+    let Ok(otpauth_uri) = otpauth_uri(&secret, &user.email) else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    // A secret that was not confirmed is replaced, a confirmed one is kept
+    match sqlx::query!(
+        r#"
+        INSERT INTO totp (user_id, secret)
+        VALUES ($1, $2)
+        ON CONFLICT (user_id) DO UPDATE
+            SET secret         = EXCLUDED.secret,
+                created_at     = NOW(),
+                last_used_step = NULL
+        WHERE totp.confirmed_at IS NULL
+        "#,
+        user.id,
+        secret
+    )
+    .execute(&auth_session.backend.db)
+    .await
+    {
+        Ok(result) if result.rows_affected() == 0 => {
+            return (StatusCode::CONFLICT, "2FA is already enabled").into_response();
+        }
+        Ok(_) => {}
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+
+    audit
+        .event(AuditAction::TotpEnrolled, AuditTarget::User, user.id)
+        .record()
+        .await;
+
+    Sonic(EnrollTotpResponse {
+        otpauth_uri,
+        secret,
+    })
+    .into_response()
+}
@@ -0,0 +1,496 @@
+use axum::{
+    body::{Body, Bytes},
+    response::IntoResponse,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use futures::{Stream, StreamExt, future, stream};
+use http::{StatusCode, header};
+use serde::Serialize;
+use sqlx::{PgPool, types::Json};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    models::{PingKind, ScheduleKind, Visibility},
+    notifiers::ChannelConfig,
+    users::{AuthSession, User},
+    web::protected::{
+        list_audit_events::{AuditEventData, AuditEventRecord},
+        organizations::OrganizationRole,
+    },
+    workers::GenericError,
+};
+
+/// The number of pings fetched at a time while the export is streamed
+const PING_PAGE_SIZE: i64 = 1000;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ExportResponse {
+    /// The time at which the export was made
+    exported_at: DateTime<Utc>,
+    /// The account of the user
+    profile: ProfileData,
+    /// The systems created by the user, including the deleted ones
+    systems: Vec<ExportedSystem>,
+    /// The periods in which the systems were down
+    incidents: Vec<ExportedIncident>,
+    /// The notification channels of the user
+    notification_channels: Vec<ExportedChannel>,
+    /// The organizations the user is a member of
+    organizations: Vec<ExportedMembership>,
+    /// The API tokens of the user (without the tokens themselves)
+    api_tokens: Vec<ExportedApiToken>,
+    /// The passkeys of the user (without the keys themselves)
+    passkeys: Vec<ExportedPasskey>,
+    /// The sessions in which the user is logged in
+    sessions: Vec<ExportedSession>,
+    /// The changes made by the user
+    audit_events: Vec<AuditEventData>,
+    /// The pings received by the systems (the last field, as they are streamed
+    /// after the others)
+    pings: Vec<ExportedPing>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProfileData {
+    /// The ID of the user
+    id: i32,
+    /// The email of the user
+    email: String,
+    /// The timezone of the user
+    timezone: String,
+    /// The language of the user
+    language: String,
+    /// The time at which the email address was verified
+    email_verified_at: Option<DateTime<Utc>>,
+    /// Whether the account is linked to the single sign-on provider
+    single_sign_on: bool,
+    /// Whether 2FA is enabled
+    totp_enabled: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ExportedSystem {
+    /// The ID of the system
+    id: Uuid,
+    /// The name of the system
+    name: String,
+    /// The organization the system belongs to
+    organization_id: Option<i32>,
+    /// The visibility of the system
+    visibility: Visibility,
+    /// Whether the system was deleted
+    deleted: bool,
+    /// The kind of schedule the system follows
+    schedule_kind: ScheduleKind,
+    /// The time between the pings in seconds (only for interval schedules)
+    frequency: Option<i64>,
+    /// The cron expression (only for cron schedules)
+    cron_expression: Option<String>,
+    /// The timezone of the cron expression (only for cron schedules)
+    cron_timezone: Option<String>,
+    /// The time at which the system starts pinging
+    starts_at: DateTime<Utc>,
+    /// The time in seconds after which the system is considered down
+    down_after: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ExportedPing {
+    /// The ID of the ping
+    id: i32,
+    /// The system that sent the ping
+    system_id: Uuid,
+    /// The time at which the ping was received
+    timestamp: DateTime<Utc>,
+    /// The kind of ping
+    kind: PingKind,
+    /// The exit code of the job, if it was sent
+    exit_code: Option<i32>,
+    /// The output of the job, if it was sent
+    body: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ExportedIncident {
+    /// The ID of the incident
+    id: i32,
+    /// The system that was down
+    system_id: Uuid,
+    /// The time at which the system went down
+    started_at: DateTime<Utc>,
+    /// The time at which the system recovered, missing if it is still down
+    ended_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ExportedChannel {
+    /// The ID of the channel
+    id: i32,
+    /// The system the channel is notified about, all the systems if missing
+    system_id: Option<Uuid>,
+    /// The configuration of the channel
+    config: ChannelConfig,
+    /// The time at which the channel was created
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ExportedMembership {
+    /// The ID of the organization
+    organization_id: i32,
+    /// The name of the organization
+    name: String,
+    /// The role of the user in the organization
+    role: OrganizationRole,
+    /// The time at which the user joined the organization
+    joined_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ExportedApiToken {
+    /// The ID of the token
+    id: i32,
+    /// The name of the token
+    name: String,
+    /// Whether the token can only read
+    read_only: bool,
+    /// The systems the token is restricted to, all of them if missing
+    system_ids: Option<Vec<Uuid>>,
+    /// The time at which the token was created
+    created_at: DateTime<Utc>,
+    /// The time at which the token was last used
+    last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ExportedPasskey {
+    /// The ID of the passkey
+    id: i32,
+    /// The name of the passkey
+    name: String,
+    /// The time at which the passkey was added
+    created_at: DateTime<Utc>,
+    /// The time at which the passkey was last used
+    last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ExportedSession {
+    /// The ID of the session
+    id: i32,
+    /// The time at which the user logged in
+    created_at: DateTime<Utc>,
+    /// The time of the latest request made with the session
+    last_seen_at: DateTime<Utc>,
+    /// The IP address of the latest request, if known
+    ip_address: Option<String>,
+    /// The user agent of the latest request, if known
+    user_agent: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/export",
+    summary = "Export Data",
+    description = "Download all the data of the user: the account, the systems with their pings and incidents, the settings and the changes made by the user",
+    responses(
+        (status = OK, description = "Data of the user, as a JSON attachment", body = ExportResponse),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = "User"
+)]
+pub async fn export(auth_session: AuthSession) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let db = auth_session.backend.db;
+    let user_id = user.id;
+
+    let Ok(export) = collect_export(&db, user).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    // The pings can be many (with the output of the jobs), so they are streamed
+    // page by page in place of the empty array that ends the export
+    let Ok(export) = sonic_rs::to_string(&export) else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let Some(head) = export.strip_suffix("[]}") else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    let body = stream::once(future::ok(Bytes::from(format!("{head}["))))
+        .chain(stream_pings(db, user_id))
+        .chain(stream::once(future::ok(Bytes::from_static(b"]}"))));
+
+    (
+        [
+            (header::CONTENT_TYPE, "application/json"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"monitor-export.json\"",
+            ),
+        ],
+        Body::from_stream(body),
+    )
+        .into_response()
+}
+
+/// The pings of the systems of the user as the items of a JSON array, fetched
+/// `PING_PAGE_SIZE` at a time
+fn stream_pings(
+    db: PgPool,
+    user_id: i32,
+) -> impl Stream<Item = Result<Bytes, GenericError>> + Send + 'static {
+    // The ping the next page starts after (None for the first page), None once
+    // the pings are over
+    let start: Option<Option<(NaiveDateTime, i32)>> = Some(None);
+
+    stream::try_unfold(start, move |after| {
+        let db = db.clone();
+
+        async move {
+            let Some(after) = after else {
+                return Ok(None);
+            };
+
+            let pings = sqlx::query_as!(
+                ExportedPing,
+                r#"
+                SELECT p.id,
+                       p.system_id,
+                       p.timestamp AT TIME ZONE 'UTC' AS "timestamp!",
+                       p.kind AS "kind: PingKind",
+                       p.exit_code,
+                       p.body
+                FROM ping p
+                    JOIN system s ON s.id = p.system_id
+                WHERE s.user_id = $1
+                  AND ($2::timestamp IS NULL OR (p.timestamp, p.id) > ($2, $3))
+                ORDER BY p.timestamp, p.id
+                LIMIT $4
+                "#,
+                user_id,
+                after.map(|(timestamp, _)| timestamp),
+                after.map(|(_, id)| id),
+                PING_PAGE_SIZE
+            )
+            .fetch_all(&db)
+            .await?;
+
+            let Some(last) = pings.last() else {
+                return Ok(None);
+            };
+
+            let next = (pings.len() as i64 == PING_PAGE_SIZE)
+                .then_some(Some((last.timestamp.naive_utc(), last.id)));
+
+            let mut chunk = Vec::new();
+            for ping in &pings {
+                // Every page but the first one follows the pings of the previous page
+                if after.is_some() || !chunk.is_empty() {
+                    chunk.push(b',');
+                }
+                sonic_rs::to_writer(&mut chunk, ping)?;
+            }
+
+            Ok(Some((Bytes::from(chunk), next)))
+        }
+    })
+}
+
+async fn collect_export(db: &PgPool, user: User) -> Result<ExportResponse, sqlx::Error> {
+    let totp_enabled = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM totp WHERE user_id = $1 AND confirmed_at IS NOT NULL
+        ) AS "exists!"
+        "#,
+        user.id
+    )
+    .fetch_one(db)
+    .await?;
+
+    let systems = sqlx::query_as!(
+        ExportedSystem,
+        r#"
+        SELECT id,
+               name,
+               organization_id,
+               visibility AS "visibility: Visibility",
+               deleted,
+               schedule_kind AS "schedule_kind: ScheduleKind",
+               EXTRACT(EPOCH FROM frequency)::bigint AS frequency,
+               cron_expression,
+               cron_timezone,
+               starts_at AT TIME ZONE 'UTC' AS "starts_at!",
+               EXTRACT(EPOCH FROM down_after)::bigint AS "down_after!"
+        FROM system
+        WHERE user_id = $1
+        ORDER BY starts_at
+        "#,
+        user.id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let incidents = sqlx::query_as!(
+        ExportedIncident,
+        r#"
+        SELECT i.id,
+               i.system_id,
+               i.started_at AT TIME ZONE 'UTC' AS "started_at!",
+               i.ended_at AT TIME ZONE 'UTC' AS ended_at
+        FROM incident i
+            JOIN system s ON s.id = i.system_id
+        WHERE s.user_id = $1
+        ORDER BY i.started_at
+        "#,
+        user.id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let notification_channels = sqlx::query!(
+        r#"
+        SELECT id, system_id, config AS "config: Json<ChannelConfig>", created_at
+        FROM notification_channel
+        WHERE user_id = $1
+        ORDER BY created_at
+        "#,
+        user.id
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|channel| ExportedChannel {
+        id: channel.id,
+        system_id: channel.system_id,
+        config: channel.config.0,
+        created_at: channel.created_at.and_utc(),
+    })
+    .collect();
+
+    let organizations = sqlx::query_as!(
+        ExportedMembership,
+        r#"
+        SELECT o.id AS organization_id,
+               o.name,
+               m.role AS "role: OrganizationRole",
+               m.joined_at AT TIME ZONE 'UTC' AS "joined_at!"
+        FROM organization_member m
+            JOIN organization o ON o.id = m.organization_id
+        WHERE m.user_id = $1
+        ORDER BY m.joined_at
+        "#,
+        user.id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let api_tokens = sqlx::query_as!(
+        ExportedApiToken,
+        r#"
+        SELECT id,
+               name,
+               read_only,
+               system_ids,
+               created_at AT TIME ZONE 'UTC' AS "created_at!",
+               last_used_at AT TIME ZONE 'UTC' AS last_used_at
+        FROM api_token
+        WHERE user_id = $1
+        ORDER BY created_at
+        "#,
+        user.id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let passkeys = sqlx::query_as!(
+        ExportedPasskey,
+        r#"
+        SELECT id,
+               name,
+               created_at AT TIME ZONE 'UTC' AS "created_at!",
+               last_used_at AT TIME ZONE 'UTC' AS last_used_at
+        FROM passkey
+        WHERE user_id = $1
+        ORDER BY created_at
+        "#,
+        user.id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let sessions = sqlx::query_as!(
+        ExportedSession,
+        r#"
+        SELECT id,
+               created_at AT TIME ZONE 'UTC' AS "created_at!",
+               last_seen_at AT TIME ZONE 'UTC' AS "last_seen_at!",
+               ip_address,
+               user_agent
+        FROM user_session
+        WHERE user_id = $1
+        ORDER BY created_at
+        "#,
+        user.id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let audit_events = sqlx::query_as!(
+        AuditEventRecord,
+        r#"
+        SELECT e.id,
+               e.actor_id,
+               $2::text AS actor_email,
+               e.organization_id,
+               e.action AS "action: _",
+               e.target_type AS "target_type: _",
+               e.target_id,
+               e.before::text AS before,
+               e.after::text AS after,
+               e.ip_address,
+               e.created_at
+        FROM audit_event e
+        WHERE e.actor_id = $1
+        ORDER BY e.created_at, e.id
+        "#,
+        user.id,
+        user.email
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(AuditEventData::from)
+    .collect();
+
+    Ok(ExportResponse {
+        exported_at: Utc::now(),
+        profile: ProfileData {
+            id: user.id,
+            email: user.email,
+            timezone: user.timezone,
+            language: user.language,
+            email_verified_at: user.email_verified_at.map(|time| time.and_utc()),
+            single_sign_on: user.oidc_subject.is_some(),
+            totp_enabled,
+        },
+        systems,
+        incidents,
+        notification_channels,
+        organizations,
+        api_tokens,
+        passkeys,
+        sessions,
+        audit_events,
+        pings: Vec::new(),
+    })
+}
@@ -13,6 +13,8 @@ pub struct GetCurrentSettingsResponse {
     pub timezone: String,
     /// The current language of the user, as defined by the IETF language tag
     pub language: String,
+    /// Whether the user has 2FA enabled
+    pub totp_enabled: bool,
 }
 
 #[utoipa::path(
@@ -36,7 +38,11 @@ pub async fn get_current_settings(auth_session: AuthSession) -> impl IntoRespons
 
     let current_settings = match sqlx::query!(
         r#"
-        SELECT timezone, language FROM "user" WHERE id = $1
+        SELECT timezone,
+               language,
+               EXISTS (SELECT 1 FROM totp WHERE totp.user_id = "user".id AND confirmed_at IS NOT NULL) AS "totp_enabled!"
+        FROM "user"
+        WHERE id = $1
         "#,
         current_user.id
     )
@@ -50,6 +56,7 @@ pub async fn get_current_settings(auth_session: AuthSession) -> impl IntoRespons
     let response = GetCurrentSettingsResponse {
         timezone: current_settings.timezone,
         language: current_settings.language,
+        totp_enabled: current_settings.totp_enabled,
     };
 
     Sonic(response).into_response()
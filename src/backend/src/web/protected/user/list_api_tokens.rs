@@ -0,0 +1,84 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use chrono::{DateTime, Utc};
+use http::StatusCode;
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::users::AuthSession;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListApiTokensResponse {
+    /// The API tokens of the user
+    tokens: Vec<ApiTokenData>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiTokenData {
+    /// The ID of the token
+    id: i32,
+    /// The name of the token
+    name: String,
+    /// Whether the token can only be used to read
+    read_only: bool,
+    /// The systems the token is restricted to, all the systems of the user if
+    /// missing
+    system_ids: Option<Vec<Uuid>>,
+    /// The time at which the token was created
+    created_at: DateTime<Utc>,
+    /// The last time the token was used, if ever
+    last_used_at: Option<DateTime<Utc>>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/list_api_tokens",
+    summary = "List API Tokens",
+    responses(
+        (status = OK, description = "List of API tokens", body = ListApiTokensResponse),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = "User"
+)]
+pub async fn list_api_tokens(auth_session: AuthSession) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let tokens = match sqlx::query!(
+        r#"
+        SELECT id, name, read_only, system_ids, created_at, last_used_at
+        FROM api_token
+        WHERE user_id = $1
+        ORDER BY created_at
+        "#,
+        user.id
+    )
+    .fetch_all(&auth_session.backend.db)
+    .await
+    {
+        Ok(tokens) => tokens,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let tokens = tokens
+        .into_iter()
+        .map(|token| ApiTokenData {
+            id: token.id,
+            name: token.name,
+            read_only: token.read_only,
+            system_ids: token.system_ids,
+            created_at: token.created_at.and_utc(),
+            last_used_at: token
+                .last_used_at
+                .map(|last_used_at| last_used_at.and_utc()),
+        })
+        .collect();
+
+    Sonic(ListApiTokensResponse { tokens }).into_response()
+}
@@ -0,0 +1,78 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use chrono::{DateTime, Utc};
+use http::StatusCode;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::users::AuthSession;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListInvitesResponse {
+    /// The invites created by the user
+    invites: Vec<InviteData>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct InviteData {
+    /// The ID of the invite
+    id: i32,
+    /// The time at which the invite was created
+    created_at: DateTime<Utc>,
+    /// The time after which the invite cannot be used anymore
+    expires_at: DateTime<Utc>,
+    /// The email of the user who signed up with the invite, if it was used
+    used_by: Option<String>,
+    /// The time at which the invite was used, if it was
+    used_at: Option<DateTime<Utc>>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/list_invites",
+    summary = "List Invites",
+    responses(
+        (status = OK, description = "List of invites", body = ListInvitesResponse),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = "User"
+)]
+pub async fn list_invites(auth_session: AuthSession) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let invites = match sqlx::query!(
+        r#"
+        SELECT i.id, i.created_at, i.expires_at, u.email AS "used_by?", i.used_at
+        FROM invite i
+            LEFT JOIN "user" u ON i.used_by = u.id
+        WHERE i.created_by = $1
+        ORDER BY i.created_at
+        "#,
+        user.id
+    )
+    .fetch_all(&auth_session.backend.db)
+    .await
+    {
+        Ok(invites) => invites,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let invites = invites
+        .into_iter()
+        .map(|invite| InviteData {
+            id: invite.id,
+            created_at: invite.created_at.and_utc(),
+            expires_at: invite.expires_at.and_utc(),
+            used_by: invite.used_by,
+            used_at: invite.used_at.map(|used_at| used_at.and_utc()),
+        })
+        .collect();
+
+    Sonic(ListInvitesResponse { invites }).into_response()
+}
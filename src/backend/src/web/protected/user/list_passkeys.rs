@@ -0,0 +1,76 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use chrono::{DateTime, Utc};
+use http::StatusCode;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::users::AuthSession;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListPasskeysResponse {
+    /// The passkeys of the user
+    passkeys: Vec<PasskeyData>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PasskeyData {
+    /// The ID of the passkey
+    id: i32,
+    /// The name of the passkey
+    name: String,
+    /// The time at which the passkey was registered
+    created_at: DateTime<Utc>,
+    /// The last time the passkey was used to log in, if ever
+    last_used_at: Option<DateTime<Utc>>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/list_passkeys",
+    summary = "List Passkeys",
+    responses(
+        (status = OK, description = "List of passkeys", body = ListPasskeysResponse),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = "User"
+)]
+pub async fn list_passkeys(auth_session: AuthSession) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let passkeys = match sqlx::query!(
+        r#"
+        SELECT id, name, created_at, last_used_at
+        FROM passkey
+        WHERE user_id = $1
+        ORDER BY created_at
+        "#,
+        user.id
+    )
+    .fetch_all(&auth_session.backend.db)
+    .await
+    {
+        Ok(passkeys) => passkeys,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let passkeys = passkeys
+        .into_iter()
+        .map(|passkey| PasskeyData {
+            id: passkey.id,
+            name: passkey.name,
+            created_at: passkey.created_at.and_utc(),
+            last_used_at: passkey
+                .last_used_at
+                .map(|last_used_at| last_used_at.and_utc()),
+        })
+        .collect();
+
+    Sonic(ListPasskeysResponse { passkeys }).into_response()
+}
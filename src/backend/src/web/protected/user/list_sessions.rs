@@ -0,0 +1,95 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use chrono::{DateTime, Utc};
+use http::StatusCode;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    middleware::track_session::{SESSION_INACTIVITY_DAYS, SESSION_KEY},
+    users::AuthSession,
+};
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListSessionsResponse {
+    /// The sessions in which the user is logged in
+    sessions: Vec<SessionData>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionData {
+    /// The ID of the session
+    id: i32,
+    /// The time at which the user logged in
+    created_at: DateTime<Utc>,
+    /// The time of the latest request made with the session
+    last_seen_at: DateTime<Utc>,
+    /// The IP address of the latest request, if known
+    ip_address: Option<String>,
+    /// The user agent of the latest request, if known
+    user_agent: Option<String>,
+    /// Whether this is the session of the request
+    current: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/list_sessions",
+    summary = "List Sessions",
+    responses(
+        (status = OK, description = "List of sessions", body = ListSessionsResponse),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = "User"
+)]
+pub async fn list_sessions(auth_session: AuthSession) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let Ok(session_key) = auth_session.session.get::<String>(SESSION_KEY).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    // The sessions that were not used for too long have expired
+    let sessions = match sqlx::query!(
+        r#"
+        WITH expired AS (
+            DELETE FROM user_session
+            WHERE user_id = $1
+              AND last_seen_at < NOW() - make_interval(days => $2)
+        )
+        SELECT id, session_key, created_at, last_seen_at, ip_address, user_agent
+        FROM user_session
+        WHERE user_id = $1
+          AND last_seen_at >= NOW() - make_interval(days => $2)
+        ORDER BY last_seen_at DESC
+        "#,
+        user.id,
+        SESSION_INACTIVITY_DAYS as i32
+    )
+    .fetch_all(&auth_session.backend.db)
+    .await
+    {
+        Ok(sessions) => sessions,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let sessions = sessions
+        .into_iter()
+        .map(|session| SessionData {
+            id: session.id,
+            created_at: session.created_at.and_utc(),
+            last_seen_at: session.last_seen_at.and_utc(),
+            ip_address: session.ip_address,
+            user_agent: session.user_agent,
+            current: session_key.as_deref() == Some(session.session_key.as_str()),
+        })
+        .collect();
+
+    Sonic(ListSessionsResponse { sessions }).into_response()
+}
@@ -1,7 +1,25 @@
+mod change_email;
 mod change_language;
 mod change_password;
 mod change_timezone;
+mod confirm_totp;
+mod create_api_token;
+pub mod create_invite;
+mod delete_account;
+mod delete_invite;
+mod delete_passkey;
+mod disable_totp;
+mod enroll_totp;
+mod export;
 mod get_current_settings;
+mod list_api_tokens;
+mod list_invites;
+mod list_passkeys;
+mod list_sessions;
+mod revoke_api_token;
+mod revoke_other_sessions;
+mod revoke_session;
+mod send_verification_email;
 
 use utoipa_axum::{router::OpenApiRouter, routes};
 
@@ -9,6 +27,24 @@ pub fn router() -> OpenApiRouter {
     OpenApiRouter::new()
         .routes(routes![change_password::change_password])
         .routes(routes![change_timezone::change_timezone])
+        .routes(routes![change_email::change_email])
         .routes(routes![get_current_settings::get_current_settings])
         .routes(routes![change_language::change_language])
+        .routes(routes![create_api_token::create_api_token])
+        .routes(routes![list_api_tokens::list_api_tokens])
+        .routes(routes![revoke_api_token::revoke_api_token])
+        .routes(routes![create_invite::create_invite])
+        .routes(routes![list_invites::list_invites])
+        .routes(routes![delete_invite::delete_invite])
+        .routes(routes![send_verification_email::send_verification_email])
+        .routes(routes![enroll_totp::enroll_totp])
+        .routes(routes![confirm_totp::confirm_totp])
+        .routes(routes![disable_totp::disable_totp])
+        .routes(routes![list_passkeys::list_passkeys])
+        .routes(routes![delete_passkey::delete_passkey])
+        .routes(routes![list_sessions::list_sessions])
+        .routes(routes![revoke_session::revoke_session])
+        .routes(routes![revoke_other_sessions::revoke_other_sessions])
+        .routes(routes![export::export])
+        .routes(routes![delete_account::delete_account])
 }
@@ -0,0 +1,72 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::Deserialize;
+use sonic_rs::json;
+use utoipa::ToSchema;
+
+use crate::{
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RevokeApiTokenRequest {
+    /// The ID of the token to revoke
+    id: i32,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/revoke_api_token",
+    summary = "Revoke API Token",
+    description = "Revoke an API token, the requests using it are rejected from now on",
+    request_body = RevokeApiTokenRequest,
+    responses(
+        (status = OK, description = "Token was revoked successfully"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = NOT_FOUND, description = "Token not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = "User"
+)]
+pub async fn revoke_api_token(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<RevokeApiTokenRequest>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let token = match sqlx::query!(
+        r#"
+        DELETE FROM api_token WHERE id = $1 AND user_id = $2
+        RETURNING name
+        "#,
+        request.id,
+        user.id
+    )
+    .fetch_optional(&auth_session.backend.db)
+    .await
+    {
+        Ok(Some(token)) => token,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    audit
+        .event(
+            AuditAction::ApiTokenRevoked,
+            AuditTarget::ApiToken,
+            request.id,
+        )
+        .before(json!({ "name": token.name }))
+        .record()
+        .await;
+
+    StatusCode::OK.into_response()
+}
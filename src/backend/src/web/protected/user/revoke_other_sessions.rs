@@ -0,0 +1,62 @@
+use axum::response::IntoResponse;
+use http::StatusCode;
+use sonic_rs::json;
+
+use crate::{
+    audit::{Audit, AuditAction, AuditTarget},
+    middleware::track_session::SESSION_KEY,
+    users::AuthSession,
+};
+
+#[utoipa::path(
+    delete,
+    path = "/revoke_other_sessions",
+    summary = "Revoke Other Sessions",
+    description = "Log out all the sessions of the user except the current one",
+    responses(
+        (status = OK, description = "Sessions were revoked successfully"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = "User"
+)]
+pub async fn revoke_other_sessions(auth_session: AuthSession, audit: Audit) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let Ok(session_key) = auth_session.session.get::<String>(SESSION_KEY).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    let revoked = match sqlx::query!(
+        r#"
+        DELETE FROM user_session
+        WHERE user_id = $1
+          AND session_key IS DISTINCT FROM $2
+        "#,
+        user.id,
+        session_key
+    )
+    .execute(&auth_session.backend.db)
+    .await
+    {
+        Ok(result) => result.rows_affected(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    audit
+        .event(
+            AuditAction::OtherSessionsRevoked,
+            AuditTarget::User,
+            user.id,
+        )
+        .after(json!({ "revoked": revoked }))
+        .record()
+        .await;
+
+    StatusCode::OK.into_response()
+}
@@ -0,0 +1,75 @@
+use axum::response::IntoResponse;
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::Deserialize;
+use sonic_rs::json;
+use utoipa::ToSchema;
+
+use crate::{
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RevokeSessionRequest {
+    /// The ID of the session to revoke
+    id: i32,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/revoke_session",
+    summary = "Revoke Session",
+    description = "Log out a session of the user, which is logged out at its next request",
+    request_body = RevokeSessionRequest,
+    responses(
+        (status = OK, description = "Session was revoked successfully"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = NOT_FOUND, description = "Session not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = "User"
+)]
+pub async fn revoke_session(
+    auth_session: AuthSession,
+    audit: Audit,
+    Sonic(request): Sonic<RevokeSessionRequest>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let session = match sqlx::query!(
+        r#"
+        DELETE FROM user_session WHERE id = $1 AND user_id = $2
+        RETURNING ip_address, user_agent
+        "#,
+        request.id,
+        user.id
+    )
+    .fetch_optional(&auth_session.backend.db)
+    .await
+    {
+        Ok(Some(session)) => session,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    audit
+        .event(
+            AuditAction::SessionRevoked,
+            AuditTarget::Session,
+            request.id,
+        )
+        .before(json!({
+            "ip_address": session.ip_address,
+            "user_agent": session.user_agent,
+        }))
+        .record()
+        .await;
+
+    StatusCode::OK.into_response()
+}
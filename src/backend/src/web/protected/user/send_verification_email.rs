@@ -0,0 +1,48 @@
+use axum::response::IntoResponse;
+use http::StatusCode;
+
+use crate::{
+    audit::{Audit, AuditAction, AuditTarget},
+    users::AuthSession,
+    web::auth::email_token::EmailTokenKind,
+};
+
+#[utoipa::path(
+    post,
+    path = "/send_verification_email",
+    summary = "Send Verification Email",
+    description = "Send again the link to verify the email address of the user",
+    responses(
+        (status = OK, description = "Link was sent"),
+        (status = UNAUTHORIZED, description = "User is not logged in"),
+        (status = CONFLICT, description = "Email is already verified")
+    ),
+    security(
+        ("session" = [])
+    ),
+    tag = "User"
+)]
+pub async fn send_verification_email(auth_session: AuthSession, audit: Audit) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    if user.email_verified_at.is_some() {
+        return (StatusCode::CONFLICT, "Email is already verified").into_response();
+    }
+
+    auth_session
+        .backend
+        .send_email_token(&user, EmailTokenKind::VerifyEmail);
+
+    audit
+        .event(
+            AuditAction::VerificationEmailSent,
+            AuditTarget::User,
+            user.id,
+        )
+        .record()
+        .await;
+
+    StatusCode::OK.into_response()
+}
@@ -3,11 +3,22 @@ mod ping_status;
 mod public_systems;
 mod sys_info;
 
+use axum::middleware;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
+use crate::middleware::rate_limit::{PING_RATE_LIMIT, rate_limit};
+
 pub fn router() -> OpenApiRouter {
     OpenApiRouter::new()
         .routes(routes![ping_status::ping_status])
+        .routes(routes![ping_status::ping_start])
+        .routes(routes![ping_status::ping_fail])
+        .routes(routes![ping_status::ping_exit_code])
+        // The route layer only applies to the routes above
+        .route_layer(middleware::from_fn_with_state(
+            PING_RATE_LIMIT.clone(),
+            rate_limit,
+        ))
         .routes(routes![sys_info::sys_info])
         .routes(routes![healthcheck::healthcheck])
         .merge(public_systems::router())
@@ -1,53 +1,380 @@
-use axum::{extract::Path, response::IntoResponse};
+use std::collections::VecDeque;
+
+use axum::{body::Body, extract::Path, response::IntoResponse};
+use chrono::Utc;
+use futures::StreamExt;
 use http::StatusCode;
-use tracing::info;
+use sidekiq::RedisPool;
+use sqlx::PgPool;
+use thiserror::Error;
+use tracing::{error, info};
 use uuid::Uuid;
 
-use crate::{app::openapi::DATA_TAG, users::AuthSession};
+use crate::{
+    PING_BODY_LIMIT,
+    app::openapi::DATA_TAG,
+    models::PingKind,
+    notifiers::{EventSystem, NotificationEvent, dispatch},
+    users::AuthSession,
+    workers::deadlines::schedule_deadline,
+};
+
+/// How many times `PING_BODY_LIMIT` a body can be (once decompressed) before
+/// it is rejected, only its tail is stored but the whole body is read
+const PING_BODY_MAX_FACTOR: usize = 100;
 
 #[utoipa::path(
     post,
     path = "/ping_status/{id}",
     summary = "Ping as system",
-    description = "Ping this endpoint to update the status of the system",
+    description = "Ping this endpoint to update the status of the system (marks the run as successful)",
     responses(
         (status = OK, description = "Ping was successful"),
         (status = NOT_FOUND, description = "System not found"),
+        (status = PAYLOAD_TOO_LARGE, description = "Output of the job is too large"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error"),
     ),
+    request_body(content = String, content_type = "text/plain", description = "Optional output of the job, only the tail is stored"),
     tag = DATA_TAG
 )]
-pub async fn ping_status(Path(id): Path<Uuid>, auth_session: AuthSession) -> impl IntoResponse {
-    info!("System {} just pinged!", id);
+pub async fn ping_status(
+    Path(id): Path<Uuid>,
+    auth_session: AuthSession,
+    body: Body,
+) -> impl IntoResponse {
+    record_ping(
+        &auth_session.backend.db,
+        &auth_session.backend.redis,
+        id,
+        PingKind::Success,
+        None,
+        body,
+    )
+    .await
+}
 
-    // Check if the system exists and is not deleted
-    if sqlx::query!(
+#[utoipa::path(
+    post,
+    path = "/ping_status/{id}/start",
+    summary = "Ping start as system",
+    description = "Ping this endpoint when the job starts, the duration of the run is computed when it finishes",
+    responses(
+        (status = OK, description = "Ping was successful"),
+        (status = NOT_FOUND, description = "System not found"),
+        (status = PAYLOAD_TOO_LARGE, description = "Output of the job is too large"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error"),
+    ),
+    request_body(content = String, content_type = "text/plain", description = "Optional output of the job, only the tail is stored"),
+    tag = DATA_TAG
+)]
+pub async fn ping_start(
+    Path(id): Path<Uuid>,
+    auth_session: AuthSession,
+    body: Body,
+) -> impl IntoResponse {
+    record_ping(
+        &auth_session.backend.db,
+        &auth_session.backend.redis,
+        id,
+        PingKind::Start,
+        None,
+        body,
+    )
+    .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/ping_status/{id}/fail",
+    summary = "Ping failure as system",
+    description = "Ping this endpoint when the job fails, the instant will be marked as failed and the system alerted as down right away (like with a nonzero exit code)",
+    responses(
+        (status = OK, description = "Ping was successful"),
+        (status = NOT_FOUND, description = "System not found"),
+        (status = PAYLOAD_TOO_LARGE, description = "Output of the job is too large"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error"),
+    ),
+    request_body(content = String, content_type = "text/plain", description = "Optional output of the job, only the tail is stored"),
+    tag = DATA_TAG
+)]
+pub async fn ping_fail(
+    Path(id): Path<Uuid>,
+    auth_session: AuthSession,
+    body: Body,
+) -> impl IntoResponse {
+    record_ping(
+        &auth_session.backend.db,
+        &auth_session.backend.redis,
+        id,
+        PingKind::Fail,
+        None,
+        body,
+    )
+    .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/ping_status/{id}/{exit_code}",
+    summary = "Ping exit code as system",
+    description = "Ping this endpoint with the exit code of the job (zero is a success, anything else a failure)",
+    responses(
+        (status = OK, description = "Ping was successful"),
+        (status = NOT_FOUND, description = "System not found"),
+        (status = PAYLOAD_TOO_LARGE, description = "Output of the job is too large"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal server error"),
+    ),
+    request_body(content = String, content_type = "text/plain", description = "Optional output of the job, only the tail is stored"),
+    tag = DATA_TAG
+)]
+pub async fn ping_exit_code(
+    Path((id, exit_code)): Path<(Uuid, i32)>,
+    auth_session: AuthSession,
+    body: Body,
+) -> impl IntoResponse {
+    let kind = if exit_code == 0 {
+        PingKind::Success
+    } else {
+        PingKind::Fail
+    };
+
+    record_ping(
+        &auth_session.backend.db,
+        &auth_session.backend.redis,
+        id,
+        kind,
+        Some(exit_code),
+        body,
+    )
+    .await
+}
+
+async fn record_ping(
+    db: &PgPool,
+    redis: &RedisPool,
+    id: Uuid,
+    kind: PingKind,
+    exit_code: Option<i32>,
+    body: Body,
+) -> axum::response::Response {
+    info!("System {} just pinged ({:?})!", id, kind);
+
+    // Check if the system exists and is not deleted (before reading the body)
+    match sqlx::query!(
         r#"
         SELECT id FROM system WHERE id = $1 AND deleted = false
         "#,
         id
     )
-    .fetch_optional(&auth_session.backend.db)
+    .fetch_optional(db)
     .await
-    .is_err()
     {
-        return StatusCode::NOT_FOUND.into_response();
+        Ok(Some(_)) => {}
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     };
 
-    // Insert the ping into the database and reset the down_sent_email flag
-    sqlx::query!(
+    let max = PING_BODY_LIMIT.saturating_mul(PING_BODY_MAX_FACTOR);
+    let body = match read_tail(body, *PING_BODY_LIMIT, max).await {
+        Ok(tail) => body_tail(&tail, *PING_BODY_LIMIT),
+        Err(ReadTailError::TooLarge) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+        Err(ReadTailError::Body(_)) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    // Insert the ping into the database, finishing pings get the duration of the
+    // run from the latest start ping that was not finished yet
+    if sqlx::query!(
         r#"
-        WITH updated AS (
-            UPDATE system
-            SET down_sent_email = false
-            WHERE id = $1
-        )
-        INSERT INTO ping (system_id) VALUES ($1)
+        INSERT INTO ping (system_id, kind, exit_code, body, duration)
+        VALUES ($1, $2, $3, $4, CASE
+            WHEN $2::ping_kind = 'start' THEN NULL
+            ELSE (
+                SELECT NOW() - p.timestamp
+                FROM ping p
+                WHERE p.system_id = $1
+                  AND p.kind = 'start'
+                  AND p.timestamp > COALESCE(
+                      (SELECT MAX(f.timestamp)
+                       FROM ping f
+                       WHERE f.system_id = $1
+                         AND f.kind <> 'start'),
+                      '-infinity'
+                  )
+                ORDER BY p.timestamp DESC
+                LIMIT 1
+            )
+        END)
         "#,
-        id
+        id,
+        kind as PingKind,
+        exit_code,
+        body
     )
-    .execute(&auth_session.backend.db)
+    .execute(db)
     .await
-    .unwrap();
+    .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    // Only a successful ping means that the system is up again, if it was alerted
+    // as down its incident is closed and a recovery notification is sent
+    if kind == PingKind::Success {
+        let recovered = match sqlx::query!(
+            r#"
+            WITH previous AS (
+                SELECT id, down_since
+                FROM system
+                WHERE id = $1
+                  AND down_sent_email = TRUE
+                FOR UPDATE
+            ),
+            closed AS (
+                UPDATE incident
+                SET ended_at = NOW(),
+                    duration = NOW() - started_at
+                WHERE system_id = $1
+                  AND ended_at IS NULL
+                RETURNING id
+            )
+            UPDATE system s
+            SET down_sent_email = FALSE,
+                down_since = NULL
+            FROM previous
+            WHERE s.id = previous.id
+            RETURNING s.name, previous.down_since,
+                (SELECT closed.id FROM closed) AS "incident_id?"
+            "#,
+            id
+        )
+        .fetch_optional(db)
+        .await
+        {
+            Ok(recovered) => recovered,
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        };
+
+        if let Some((recovered, down_since)) =
+            recovered.and_then(|recovered| recovered.down_since.map(|d| (recovered, d)))
+        {
+            let event = NotificationEvent::Up {
+                system: EventSystem {
+                    id,
+                    name: recovered.name,
+                },
+                down_since: down_since.and_utc(),
+                up_since: Utc::now(),
+            };
+
+            // The ping is stored and the incident closed by now, so failing the
+            // request would not get the notifications sent when the job retries
+            if let Err(e) = dispatch(redis, db, recovered.incident_id, &event).await {
+                error!(
+                    "Error dispatching the recovery notifications of system {}: {}",
+                    id, e
+                );
+            }
+        }
+    }
+
+    // The ping moves the deadline of the system (or makes it due right away if the
+    // run failed)
+    if let Err(e) = schedule_deadline(redis, db, id).await {
+        error!("Error scheduling the deadline of system {}: {}", id, e);
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
 
     StatusCode::OK.into_response()
 }
+
+#[derive(Debug, Error)]
+enum ReadTailError {
+    #[error("Body is larger than the maximum size")]
+    TooLarge,
+    #[error("Error reading the body: {0}")]
+    Body(#[from] axum::Error),
+}
+
+/// Reads the body as a stream keeping only its last `limit` bytes, so that the
+/// long outputs of the jobs are not held in memory. Bodies over `max` bytes are
+/// rejected, as reading them would still take time
+async fn read_tail(body: Body, limit: usize, max: usize) -> Result<Vec<u8>, ReadTailError> {
+    let mut stream = body.into_data_stream();
+    let mut tail = VecDeque::with_capacity(limit);
+    let mut read = 0usize;
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+
+        read = read.saturating_add(chunk.len());
+        if read > max {
+            return Err(ReadTailError::TooLarge);
+        }
+
+        let chunk = &chunk[chunk.len().saturating_sub(limit)..];
+        let overflow = (tail.len() + chunk.len()).saturating_sub(limit);
+        tail.drain(..overflow.min(tail.len()));
+        tail.extend(chunk);
+    }
+
+    Ok(tail.into())
+}
+
+/// Keeps only the last `limit` bytes of the body (the tail of a log is usually
+/// what explains a failure), returns `None` for an empty body. NUL bytes are
+/// left out since PostgreSQL cannot store them in a text column
+fn body_tail(body: &[u8], limit: usize) -> Option<String> {
+    if body.is_empty() || limit == 0 {
+        return None;
+    }
+
+    let mut tail = &body[body.len().saturating_sub(limit)..];
+
+    // Do not start in the middle of a multibyte UTF-8 character
+    while let Some((first, rest)) = tail.split_first() {
+        if first & 0b1100_0000 != 0b1000_0000 {
+            break;
+        }
+        tail = rest;
+    }
+
+    Some(String::from_utf8_lossy(tail).replace('\0', ""))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_body_tail() {
+        assert_eq!(body_tail(b"", 10), None);
+        assert_eq!(body_tail(b"hello", 10).as_deref(), Some("hello"));
+        assert_eq!(body_tail(b"hello world", 5).as_deref(), Some("world"));
+
+        // "é" is two bytes long, cutting it in half drops it
+        assert_eq!(body_tail("perché".as_bytes(), 1).as_deref(), Some(""));
+        assert_eq!(body_tail("perché".as_bytes(), 2).as_deref(), Some("é"));
+        assert_eq!(body_tail("perché".as_bytes(), 3).as_deref(), Some("hé"));
+
+        assert_eq!(body_tail(b"a\0b\0", 10).as_deref(), Some("ab"));
+    }
+
+    #[tokio::test]
+    async fn test_read_tail() {
+        let chunks: Vec<Result<&[u8], axum::Error>> =
+            vec![Ok(b"hello"), Ok(b" big"), Ok(b" world")];
+        let body = Body::from_stream(futures::stream::iter(chunks));
+        assert_eq!(read_tail(body, 8, 100).await.unwrap(), b"ig world");
+
+        let body = Body::from(vec![b'x'; 3 * 1024 * 1024]);
+        assert_eq!(read_tail(body, 4, 4 * 1024 * 1024).await.unwrap(), b"xxxx");
+
+        let body = Body::from(vec![b'x'; 3 * 1024 * 1024]);
+        assert!(matches!(
+            read_tail(body, 4, 1024 * 1024).await,
+            Err(ReadTailError::TooLarge)
+        ));
+
+        assert!(read_tail(Body::empty(), 4, 4).await.unwrap().is_empty());
+    }
+}
@@ -10,8 +10,9 @@ use uuid::Uuid;
 
 use crate::{
     app::openapi::PUBLIC_SYSTEM_TAG,
+    models::{ScheduleKind, Visibility},
     users::AuthSession,
-    web::protected::list_systems::{LIMIT_SYSTEM_REQUEST, SystemData, SystemRecord, Visibility},
+    web::protected::list_systems::{SystemData, SystemRecord, validate_paging},
 };
 
 #[derive(Debug, Deserialize, Clone, IntoParams)]
@@ -36,7 +37,7 @@ pub struct GetPublicResponse {
     params(GetPublicQuery),
     responses(
         (status = OK, description = "Public system was retrieved successfully", body = GetPublicResponse),
-        (status = BAD_REQUEST, description = "List size is too large"),
+        (status = BAD_REQUEST, description = "Page or list size is not valid"),
         (status = NOT_FOUND, description = "System not found"),
         (status = INTERNAL_SERVER_ERROR, description = "Internal server error")
     ),
@@ -47,14 +48,16 @@ pub async fn get_public(
     Path(uuid): Path<Uuid>,
     Query(query): Query<GetPublicQuery>,
 ) -> impl IntoResponse {
-    if query.list_size > LIMIT_SYSTEM_REQUEST {
-        return (StatusCode::BAD_REQUEST, "Limit of list_size exceeded").into_response();
+    if let Err(response) = validate_paging(query.page, query.list_size) {
+        return response.into_response();
     }
 
     let db_system = match sqlx::query_as!(
         SystemRecord,
         r#"
-        SELECT id, name, user_id, frequency, starts_at, deleted, down_after, down_sent_email, visibility AS "visibility: Visibility"
+        SELECT id, name, user_id, frequency, starts_at, deleted, down_after, down_sent_email, visibility AS "visibility: Visibility",
+               schedule_kind AS "schedule_kind: ScheduleKind", cron_expression, cron_timezone,
+               grace_early, grace_late, organization_id
         FROM system WHERE id = $1 AND visibility = 'public'
         "#,
         uuid
@@ -71,15 +74,16 @@ pub async fn get_public(
         }
     };
 
-    let Ok(system_data) = SystemData::fetch_from_db(
+    let system_data = match SystemData::fetch_from_db(
         &auth_session.backend.db,
         query.list_size,
         query.page,
         db_system,
     )
     .await
-    else {
-        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    {
+        Ok(system_data) => system_data,
+        Err(response) => return response,
     };
 
     Sonic(GetPublicResponse {
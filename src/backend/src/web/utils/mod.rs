@@ -1,3 +1,7 @@
 pub mod custom_login_required;
+pub mod paging;
+pub mod schedule;
+pub mod system_access;
 pub mod time;
 pub mod time_conversions;
+pub mod token;
@@ -0,0 +1,35 @@
+use http::StatusCode;
+
+/// Validates the requested page of a list, returning the number of rows to skip
+/// before it (the `OFFSET` of the query)
+pub fn page_offset(
+    page: i64,
+    list_size: i64,
+    max_list_size: i64,
+) -> Result<i64, (StatusCode, &'static str)> {
+    if list_size > max_list_size {
+        return Err((StatusCode::BAD_REQUEST, "Limit of list_size exceeded"));
+    }
+
+    if list_size < 0 || page < 0 {
+        return Err((StatusCode::BAD_REQUEST, "Page is not valid"));
+    }
+
+    page.checked_mul(list_size)
+        .ok_or((StatusCode::BAD_REQUEST, "Page is not valid"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_page_offset() {
+        assert_eq!(page_offset(0, 20, 100), Ok(0));
+        assert_eq!(page_offset(3, 20, 100), Ok(60));
+        assert!(page_offset(0, 101, 100).is_err());
+        assert!(page_offset(-1, 20, 100).is_err());
+        assert!(page_offset(0, -1, 100).is_err());
+        assert!(page_offset(i64::MAX, 20, 100).is_err());
+    }
+}
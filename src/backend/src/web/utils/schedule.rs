@@ -0,0 +1,583 @@
+use std::str::FromStr;
+
+use chrono::{Duration, NaiveDateTime};
+use chrono_tz::Tz;
+use croner::Cron;
+use sqlx::postgres::types::PgInterval;
+use thiserror::Error;
+
+use crate::{
+    models::ScheduleKind,
+    web::utils::{
+        time::{ApproxError, approx_expected_timestamp},
+        time_conversions::pg_interval_to_duration,
+    },
+};
+
+#[derive(Error, Debug)]
+pub enum ScheduleError {
+    #[error("Frequency is missing")]
+    MissingFrequency,
+    #[error("Invalid frequency")]
+    InvalidFrequency,
+    #[error("Cron expression is missing")]
+    MissingCronExpression,
+    #[error("Invalid cron expression")]
+    InvalidCronExpression,
+    #[error("Invalid timezone")]
+    InvalidTimezone,
+    #[error("The cron expression never fires")]
+    NeverFires,
+}
+
+/// The times at which a system is expected to ping
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    /// Pings every `frequency`, anchored at `starts_at`
+    Interval {
+        frequency: Duration,
+        starts_at: NaiveDateTime,
+    },
+    /// Pings at every occurrence of a cron expression, evaluated in `timezone`
+    Cron {
+        cron: Box<Cron>,
+        timezone: Tz,
+        starts_at: NaiveDateTime,
+    },
+}
+
+impl Schedule {
+    pub fn new(
+        kind: ScheduleKind,
+        frequency: Option<PgInterval>,
+        cron_expression: Option<&str>,
+        cron_timezone: Option<&str>,
+        starts_at: NaiveDateTime,
+    ) -> Result<Self, ScheduleError> {
+        match kind {
+            ScheduleKind::Interval => {
+                let frequency =
+                    pg_interval_to_duration(frequency.ok_or(ScheduleError::MissingFrequency)?);
+
+                if frequency <= Duration::zero() {
+                    return Err(ScheduleError::InvalidFrequency);
+                }
+
+                Ok(Schedule::Interval {
+                    frequency,
+                    starts_at,
+                })
+            }
+            ScheduleKind::Cron => {
+                let cron = parse_cron_expression(
+                    cron_expression.ok_or(ScheduleError::MissingCronExpression)?,
+                )?;
+                let timezone = Tz::from_str(cron_timezone.unwrap_or("UTC"))
+                    .map_err(|_| ScheduleError::InvalidTimezone)?;
+
+                Ok(Schedule::Cron {
+                    cron: Box::new(cron),
+                    timezone,
+                    starts_at,
+                })
+            }
+        }
+    }
+
+    pub fn starts_at(&self) -> NaiveDateTime {
+        match self {
+            Schedule::Interval { starts_at, .. } | Schedule::Cron { starts_at, .. } => *starts_at,
+        }
+    }
+
+    /// Checks that the schedule has an expected timestamp both before and
+    /// after `timestamp` (a cron expression like `0 0 30 2 *` parses, but
+    /// never fires)
+    pub fn check_fires(&self, timestamp: NaiveDateTime) -> Result<(), ScheduleError> {
+        match (
+            self.approx_expected_timestamp(timestamp),
+            self.next(timestamp),
+        ) {
+            (Ok(_), Ok(_)) => Ok(()),
+            _ => Err(ScheduleError::NeverFires),
+        }
+    }
+
+    /// The fixed frequency of the schedule, if it has one
+    pub fn frequency(&self) -> Option<Duration> {
+        match self {
+            Schedule::Interval { frequency, .. } => Some(*frequency),
+            Schedule::Cron { .. } => None,
+        }
+    }
+
+    /// Floors the timestamp to the latest expected timestamp (see
+    /// `approx_expected_timestamp`)
+    pub fn approx_expected_timestamp(
+        &self,
+        timestamp: NaiveDateTime,
+    ) -> Result<NaiveDateTime, ApproxError> {
+        match self {
+            Schedule::Interval {
+                frequency,
+                starts_at,
+            } => approx_expected_timestamp(timestamp, *frequency, *starts_at),
+            Schedule::Cron { cron, timezone, .. } => cron
+                .find_previous_occurrence(&timestamp.and_utc().with_timezone(timezone), true)
+                .map(|occurrence| occurrence.naive_utc())
+                .map_err(|_| ApproxError::NoOccurrence),
+        }
+    }
+
+    /// The expected timestamp preceding the given expected timestamp
+    pub fn previous(&self, expected: NaiveDateTime) -> Result<NaiveDateTime, ApproxError> {
+        self.nth_previous(expected, 1)
+    }
+
+    /// The expected timestamp `n` occurrences before the given expected
+    /// timestamp
+    pub fn nth_previous(
+        &self,
+        expected: NaiveDateTime,
+        n: i64,
+    ) -> Result<NaiveDateTime, ApproxError> {
+        match self {
+            Schedule::Interval { frequency, .. } => i32::try_from(n)
+                .ok()
+                .and_then(|n| frequency.checked_mul(n))
+                .and_then(|offset| expected.checked_sub_signed(offset))
+                .ok_or(ApproxError::OutOfRange),
+            Schedule::Cron { cron, timezone, .. } => {
+                let mut current = expected.and_utc().with_timezone(timezone);
+
+                for _ in 0..n {
+                    current = cron
+                        .find_previous_occurrence(&current, false)
+                        .map_err(|_| ApproxError::NoOccurrence)?;
+                }
+
+                Ok(current.naive_utc())
+            }
+        }
+    }
+
+    /// The expected timestamp following the given timestamp
+    pub fn next(&self, timestamp: NaiveDateTime) -> Result<NaiveDateTime, ApproxError> {
+        match self {
+            Schedule::Interval { frequency, .. } => {
+                Ok(self.approx_expected_timestamp(timestamp)? + *frequency)
+            }
+            Schedule::Cron { cron, timezone, .. } => cron
+                .find_next_occurrence(&timestamp.and_utc().with_timezone(timezone), false)
+                .map(|occurrence| occurrence.naive_utc())
+                .map_err(|_| ApproxError::NoOccurrence),
+        }
+    }
+
+    /// The expected timestamp a run that started at `timestamp` counts for,
+    /// `None` if it is outside of the grace of every expected timestamp
+    ///
+    /// A run that starts within `grace.early` before an expected timestamp
+    /// counts for it, otherwise it counts for the latest expected timestamp
+    /// (if it is within `grace.late` from it)
+    pub fn match_expected_timestamp(
+        &self,
+        timestamp: NaiveDateTime,
+        grace: &Grace,
+    ) -> Result<Option<NaiveDateTime>, ApproxError> {
+        let previous = self.approx_expected_timestamp(timestamp)?;
+
+        if previous == timestamp {
+            return Ok(Some(previous));
+        }
+
+        let next = self.next(timestamp)?;
+
+        if next - timestamp <= grace.early {
+            return Ok(Some(next));
+        }
+
+        match grace.late {
+            Some(late) if timestamp - previous > late => Ok(None),
+            _ => Ok(Some(previous)),
+        }
+    }
+
+    /// The timestamp after which a system whose latest successful ping is
+    /// `last_ping` is considered down
+    ///
+    /// Interval systems are down `down_after` past the expected timestamp of
+    /// their latest ping, cron systems are down `down_after` past the
+    /// occurrence following their latest ping (so that the gaps of the
+    /// schedule, like weekends, don't count)
+    pub fn down_since(
+        &self,
+        last_ping: NaiveDateTime,
+        down_after: Duration,
+    ) -> Result<NaiveDateTime, ApproxError> {
+        match self {
+            Schedule::Interval { .. } => {
+                Ok(self.approx_expected_timestamp(last_ping)? + down_after)
+            }
+            Schedule::Cron { .. } => Ok(self.next(last_ping)? + down_after),
+        }
+    }
+}
+
+/// The window around an expected timestamp in which a ping still counts for it
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Grace {
+    /// How much before the expected timestamp a ping is accepted
+    pub early: Duration,
+    /// How much after the expected timestamp a ping is accepted, `None`
+    /// accepts it until the next expected timestamp (and it is never late)
+    pub late: Option<Duration>,
+}
+
+impl Grace {
+    pub fn new(early: PgInterval, late: Option<PgInterval>) -> Self {
+        Grace {
+            early: pg_interval_to_duration(early),
+            late: late.map(pg_interval_to_duration),
+        }
+    }
+
+    /// Whether a run that started at `run_started_at` and was matched to
+    /// `expected` is late (it is inside the grace, but after the nominal time)
+    pub fn is_late(&self, run_started_at: NaiveDateTime, expected: NaiveDateTime) -> bool {
+        self.late.is_some() && run_started_at > expected
+    }
+}
+
+/// A schedule (along with its grace) that a system followed
+#[derive(Debug, Clone)]
+pub struct ScheduleVersion {
+    pub schedule: Schedule,
+    pub grace: Grace,
+    /// The time at which the system switched to this schedule, `None` for the
+    /// first schedule of the system
+    pub valid_from: Option<NaiveDateTime>,
+}
+
+impl ScheduleVersion {
+    fn contains(&self, timestamp: NaiveDateTime) -> bool {
+        self.valid_from
+            .is_none_or(|valid_from| timestamp >= valid_from)
+    }
+}
+
+/// The schedules a system followed over time, so that the past expected
+/// timestamps are computed against the schedule that was in effect back then
+#[derive(Debug, Clone)]
+pub struct ScheduleHistory {
+    // The newest version first, the oldest one has no start
+    versions: Vec<ScheduleVersion>,
+}
+
+impl ScheduleHistory {
+    /// A history made of the current schedule only
+    pub fn new(schedule: Schedule, grace: Grace) -> Self {
+        ScheduleHistory {
+            versions: vec![ScheduleVersion {
+                schedule,
+                grace,
+                valid_from: None,
+            }],
+        }
+    }
+
+    /// Adds a schedule that was replaced at `valid_until`, the schedules must
+    /// be added from the newest to the oldest
+    pub fn push_previous(&mut self, schedule: Schedule, grace: Grace, valid_until: NaiveDateTime) {
+        if let Some(oldest) = self.versions.last_mut() {
+            oldest.valid_from = Some(valid_until);
+        }
+
+        self.versions.push(ScheduleVersion {
+            schedule,
+            grace,
+            valid_from: None,
+        });
+    }
+
+    /// The schedule the system follows now
+    pub fn current(&self) -> &ScheduleVersion {
+        &self.versions[0]
+    }
+
+    /// The schedule that was in effect at the timestamp
+    pub fn version_at(&self, timestamp: NaiveDateTime) -> &ScheduleVersion {
+        self.versions
+            .iter()
+            .find(|version| version.contains(timestamp))
+            .unwrap_or_else(|| self.current())
+    }
+
+    /// The expected timestamps going back in time from the latest one at or
+    /// before `timestamp`, skipping the first `skip` ones and returning (at
+    /// most) the following `take` ones, each along with the schedule it
+    /// belongs to
+    pub fn expected_timestamps(
+        &self,
+        timestamp: NaiveDateTime,
+        mut skip: i64,
+        take: i64,
+    ) -> Result<Vec<(NaiveDateTime, &ScheduleVersion)>, ApproxError> {
+        let mut expected_timestamps = Vec::new();
+
+        if take <= 0 {
+            return Ok(expected_timestamps);
+        }
+
+        // The timestamp itself is included, the start of a newer version is not
+        let mut until = timestamp;
+        let mut inclusive = true;
+
+        for version in &self.versions {
+            if version
+                .valid_from
+                .is_some_and(|valid_from| valid_from > until)
+            {
+                continue;
+            }
+
+            let schedule = &version.schedule;
+            let mut expected = schedule.approx_expected_timestamp(until)?;
+
+            if !inclusive && expected == until {
+                expected = schedule.previous(expected)?;
+            }
+
+            // Jumps straight to the first returned timestamp when it is in the
+            // same version (always the case for systems that were never edited)
+            if skip > 0 {
+                let candidate = schedule.nth_previous(expected, skip)?;
+
+                if version.contains(candidate) {
+                    expected = candidate;
+                    skip = 0;
+                }
+            }
+
+            while version.contains(expected) {
+                if skip > 0 {
+                    skip -= 1;
+                } else {
+                    expected_timestamps.push((expected, version));
+
+                    if expected_timestamps.len() as i64 == take {
+                        return Ok(expected_timestamps);
+                    }
+                }
+
+                expected = schedule.previous(expected)?;
+            }
+
+            if let Some(valid_from) = version.valid_from {
+                until = valid_from;
+                inclusive = false;
+            }
+        }
+
+        Ok(expected_timestamps)
+    }
+}
+
+/// Parses a standard 5 fields cron expression (seconds can optionally be
+/// added as the first field)
+pub fn parse_cron_expression(expression: &str) -> Result<Cron, ScheduleError> {
+    croner::parser::CronParser::builder()
+        .seconds(croner::parser::Seconds::Optional)
+        .build()
+        .parse(expression)
+        .map_err(|_| ScheduleError::InvalidCronExpression)
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn datetime(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 9, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_cron_schedule() -> Result<(), ApproxError> {
+        // Weekdays at 3:00 in Rome (UTC+2 in September)
+        let schedule = Schedule::new(
+            ScheduleKind::Cron,
+            None,
+            Some("0 3 * * 1-5"),
+            Some("Europe/Rome"),
+            datetime(1, 0, 0),
+        )
+        .unwrap();
+
+        // Wednesday 11th, after the run
+        assert_eq!(
+            schedule.approx_expected_timestamp(datetime(11, 9, 30))?,
+            datetime(11, 1, 0)
+        );
+
+        // Monday 9th, the previous run was on Friday 6th
+        assert_eq!(schedule.previous(datetime(9, 1, 0))?, datetime(6, 1, 0));
+        assert_eq!(
+            schedule.nth_previous(datetime(11, 1, 0), 3)?,
+            datetime(6, 1, 0)
+        );
+
+        // Friday 6th, the next run is on Monday 9th
+        assert_eq!(schedule.next(datetime(6, 1, 0))?, datetime(9, 1, 0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_match_expected_timestamp() -> Result<(), ApproxError> {
+        let schedule = Schedule::Interval {
+            frequency: Duration::hours(1),
+            starts_at: datetime(1, 0, 0),
+        };
+
+        // Without a grace every run counts for the latest expected timestamp
+        let grace = Grace::default();
+        assert_eq!(
+            schedule.match_expected_timestamp(datetime(1, 2, 59), &grace)?,
+            Some(datetime(1, 2, 0))
+        );
+
+        let grace = Grace {
+            early: Duration::minutes(2),
+            late: Some(Duration::minutes(5)),
+        };
+
+        // A run that fires a bit early counts for the next expected timestamp
+        assert_eq!(
+            schedule.match_expected_timestamp(datetime(1, 2, 59), &grace)?,
+            Some(datetime(1, 3, 0))
+        );
+        assert_eq!(
+            schedule.match_expected_timestamp(datetime(1, 3, 0), &grace)?,
+            Some(datetime(1, 3, 0))
+        );
+        assert_eq!(
+            schedule.match_expected_timestamp(datetime(1, 3, 5), &grace)?,
+            Some(datetime(1, 3, 0))
+        );
+        assert!(grace.is_late(datetime(1, 3, 5), datetime(1, 3, 0)));
+
+        // Outside of the grace the run does not count at all
+        assert_eq!(
+            schedule.match_expected_timestamp(datetime(1, 3, 30), &grace)?,
+            None
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_schedule() {
+        assert!(matches!(
+            Schedule::new(
+                ScheduleKind::Cron,
+                None,
+                Some("0 3 * *"),
+                None,
+                datetime(1, 0, 0)
+            ),
+            Err(ScheduleError::InvalidCronExpression)
+        ));
+        assert!(matches!(
+            Schedule::new(
+                ScheduleKind::Cron,
+                None,
+                Some("0 3 * * *"),
+                Some("Mars/Olympus"),
+                datetime(1, 0, 0)
+            ),
+            Err(ScheduleError::InvalidTimezone)
+        ));
+        assert!(matches!(
+            Schedule::new(ScheduleKind::Interval, None, None, None, datetime(1, 0, 0)),
+            Err(ScheduleError::MissingFrequency)
+        ));
+
+        // February 30th parses, but never comes
+        let schedule = Schedule::new(
+            ScheduleKind::Cron,
+            None,
+            Some("0 0 30 2 *"),
+            None,
+            datetime(1, 0, 0),
+        )
+        .unwrap();
+        assert!(matches!(
+            schedule.check_fires(datetime(11, 0, 0)),
+            Err(ScheduleError::NeverFires)
+        ));
+    }
+
+    #[test]
+    fn test_nth_previous_out_of_range() {
+        let schedule = Schedule::Interval {
+            frequency: Duration::minutes(1),
+            starts_at: datetime(1, 0, 0),
+        };
+
+        assert!(matches!(
+            schedule.nth_previous(datetime(11, 0, 0), i64::MAX),
+            Err(ApproxError::OutOfRange)
+        ));
+    }
+
+    #[test]
+    fn test_schedule_history() -> Result<(), ApproxError> {
+        // Hourly until the 2nd at 12:00, every 30 minutes since
+        let mut history = ScheduleHistory::new(
+            Schedule::Interval {
+                frequency: Duration::minutes(30),
+                starts_at: datetime(1, 0, 0),
+            },
+            Grace::default(),
+        );
+        history.push_previous(
+            Schedule::Interval {
+                frequency: Duration::hours(1),
+                starts_at: datetime(1, 0, 0),
+            },
+            Grace::default(),
+            datetime(2, 12, 0),
+        );
+
+        assert_eq!(
+            history.version_at(datetime(2, 11, 59)).schedule.frequency(),
+            Some(Duration::hours(1))
+        );
+        assert_eq!(
+            history.version_at(datetime(2, 12, 0)).schedule.frequency(),
+            Some(Duration::minutes(30))
+        );
+
+        let expected: Vec<_> = history
+            .expected_timestamps(datetime(2, 13, 10), 1, 4)?
+            .into_iter()
+            .map(|(expected, _)| expected)
+            .collect();
+        assert_eq!(
+            expected,
+            vec![
+                datetime(2, 12, 30),
+                datetime(2, 12, 0),
+                datetime(2, 11, 0),
+                datetime(2, 10, 0)
+            ]
+        );
+
+        Ok(())
+    }
+}
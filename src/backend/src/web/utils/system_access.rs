@@ -0,0 +1,244 @@
+use axum::{
+    extract::{FromRequest, FromRequestParts, Request},
+    response::{IntoResponse, Response},
+};
+use axum_serde::Sonic;
+use http::StatusCode;
+use serde::de::DeserializeOwned;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    middleware::api_token::TokenScope, users::AuthSession,
+    web::protected::organizations::OrganizationRole,
+};
+
+/// A request about a single system, identified by its ID
+pub trait SystemRequest {
+    fn system_id(&self) -> Uuid;
+}
+
+/// Extracts a JSON request about a system, rejecting it unless the logged-in
+/// user can edit the system (it is theirs, or they are at least an editor of
+/// its organization) and it is not deleted, so that the handlers can safely act
+/// on the system
+#[derive(Debug)]
+pub struct EditableSystem<T>(pub T);
+
+impl<S, T> FromRequest<S> for EditableSystem<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned + SystemRequest + Send,
+{
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let (mut parts, body) = request.into_parts();
+
+        let auth_session = AuthSession::from_request_parts(&mut parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let scope = parts.extensions.get::<TokenScope>().cloned();
+
+        let Some(user) = auth_session.user else {
+            return Err(StatusCode::UNAUTHORIZED.into_response());
+        };
+
+        let Sonic(request) = Sonic::<T>::from_request(Request::from_parts(parts, body), state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        // API tokens restricted to other systems cannot tell that the system exists
+        if scope.is_some_and(|scope| !scope.allows_system(request.system_id())) {
+            return Err(StatusCode::NOT_FOUND.into_response());
+        }
+
+        ensure_system_role(
+            &auth_session.backend.db,
+            user.id,
+            request.system_id(),
+            OrganizationRole::Editor,
+        )
+        .await?;
+
+        Ok(EditableSystem(request))
+    }
+}
+
+/// The role of the user on the system (if it is not deleted): the owner of
+/// their personal systems, and their role in the organization for the systems
+/// of the organizations they are a member of. The queries reading the systems
+/// the user can see filter them with the `visible_system_ids` SQL function,
+/// which follows the same rules
+pub async fn system_role(
+    db: &PgPool,
+    user_id: i32,
+    system_id: Uuid,
+) -> Result<Option<OrganizationRole>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT CASE
+                   WHEN s.organization_id IS NULL THEN 'owner'::organization_role
+                   ELSE m.role
+               END AS "role!: OrganizationRole"
+        FROM system s
+            LEFT JOIN organization_member m
+                ON m.organization_id = s.organization_id AND m.user_id = $2
+        WHERE s.id = $1
+          AND s.deleted = FALSE
+          AND (s.organization_id IS NULL AND s.user_id = $2 OR m.user_id IS NOT NULL)
+        "#,
+        system_id,
+        user_id
+    )
+    .fetch_optional(db)
+    .await
+}
+
+/// Checks that the user has at least the role on the system, the systems the
+/// user cannot see are reported as not found to not reveal that they exist
+pub async fn ensure_system_role(
+    db: &PgPool,
+    user_id: i32,
+    system_id: Uuid,
+    minimum: OrganizationRole,
+) -> Result<OrganizationRole, Response> {
+    match system_role(db, user_id, system_id).await {
+        Ok(Some(role)) if role.at_least(minimum) => Ok(role),
+        Ok(Some(_)) => Err(StatusCode::FORBIDDEN.into_response()),
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use http::header;
+    use sonic_rs::json;
+
+    use crate::app::test_app::TestApp;
+
+    use super::*;
+
+    async fn system_state(db: &PgPool, id: Uuid) -> (String, String, bool) {
+        let system = sqlx::query!(
+            r#"
+            SELECT name, visibility::text AS "visibility!", deleted FROM system WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_one(db)
+        .await
+        .unwrap();
+
+        (system.name, system.visibility, system.deleted)
+    }
+
+    async fn mutate_system(app: &TestApp, cookie: &str, id: Uuid) -> Vec<StatusCode> {
+        let requests = [
+            app.client
+                .patch(app.url("/change_visibility"))
+                .json(&json!({ "id": id, "visibility": "private" })),
+            app.client
+                .patch(app.url("/edit_system_name"))
+                .json(&json!({ "id": id, "name": "Renamed" })),
+            app.client
+                .delete(app.url("/delete_system"))
+                .json(&json!({ "id": id })),
+        ];
+
+        let mut statuses = Vec::new();
+        for request in requests {
+            let response = request.header(header::COOKIE, cookie).send().await.unwrap();
+            statuses.push(response.status());
+        }
+
+        statuses
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a PostgreSQL database (set DATABASE_URL)"]
+    async fn test_own_system_is_mutated(db: PgPool) {
+        let app = TestApp::spawn(db).await;
+        let user_id = app.create_user("owner@example.com").await;
+        let system_id = app.create_system(user_id, "Backups").await;
+        let cookie = app.login(user_id).await;
+
+        assert_eq!(
+            mutate_system(&app, &cookie, system_id).await,
+            vec![StatusCode::OK; 3]
+        );
+        assert_eq!(
+            system_state(&app.db, system_id).await,
+            ("Renamed".to_string(), "private".to_string(), true)
+        );
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a PostgreSQL database (set DATABASE_URL)"]
+    async fn test_foreign_system_is_not_found(db: PgPool) {
+        let app = TestApp::spawn(db).await;
+        let owner_id = app.create_user("owner@example.com").await;
+        let other_id = app.create_user("other@example.com").await;
+        let system_id = app.create_system(owner_id, "Backups").await;
+        let cookie = app.login(other_id).await;
+
+        assert_eq!(
+            mutate_system(&app, &cookie, system_id).await,
+            vec![StatusCode::NOT_FOUND; 3]
+        );
+        assert_eq!(
+            system_state(&app.db, system_id).await,
+            ("Backups".to_string(), "public".to_string(), false)
+        );
+
+        // Missing systems cannot be told apart from the foreign ones
+        assert_eq!(
+            mutate_system(&app, &cookie, Uuid::new_v4()).await,
+            vec![StatusCode::NOT_FOUND; 3]
+        );
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a PostgreSQL database (set DATABASE_URL)"]
+    async fn test_deleted_system_is_not_found(db: PgPool) {
+        let app = TestApp::spawn(db).await;
+        let user_id = app.create_user("owner@example.com").await;
+        let system_id = app.create_system(user_id, "Backups").await;
+        let cookie = app.login(user_id).await;
+
+        let response = app
+            .client
+            .delete(app.url("/delete_system"))
+            .header(header::COOKIE, &cookie)
+            .json(&json!({ "id": system_id }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        assert_eq!(
+            mutate_system(&app, &cookie, system_id).await,
+            vec![StatusCode::NOT_FOUND; 3]
+        );
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a PostgreSQL database (set DATABASE_URL)"]
+    async fn test_anonymous_user_is_unauthorized(db: PgPool) {
+        let app = TestApp::spawn(db).await;
+        let user_id = app.create_user("owner@example.com").await;
+        let system_id = app.create_system(user_id, "Backups").await;
+
+        let response = app
+            .client
+            .delete(app.url("/delete_system"))
+            .json(&json!({ "id": system_id }))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(!system_state(&app.db, system_id).await.2);
+    }
+}
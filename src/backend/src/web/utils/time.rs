@@ -9,6 +9,10 @@ pub fn naive_datetime_now() -> NaiveDateTime {
 pub enum ApproxError {
     #[error("Invalid frequency")]
     InvalidFrequency,
+    #[error("No occurrence of the schedule was found")]
+    NoOccurrence,
+    #[error("The timestamp is out of range")]
+    OutOfRange,
 }
 
 /// Rounds the timestamp to the closest expected timestamp given a frequency and
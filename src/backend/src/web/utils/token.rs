@@ -0,0 +1,29 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+/// Generates a random secret token (starting with the prefix), returning it
+/// along with its hash, which is what gets stored
+pub fn generate_token(prefix: &str) -> (String, String) {
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+
+    let token = format!("{}{}", prefix, hex::encode(bytes));
+    let hash = hash_token(&token);
+
+    (token, hash)
+}
+
+/// Generates a random base64url token, for the values that are only kept in
+/// the session (such as challenges)
+pub fn generate_url_safe_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// The tokens are random, so a fast hash is enough to not store them in clear
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
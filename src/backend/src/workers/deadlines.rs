@@ -0,0 +1,152 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use sidekiq::{RedisPool, Worker, redis_rs::Script};
+use sqlx::PgPool;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::workers::{
+    GenericResult,
+    down_check_worker::{DownCheckArgs, DownCheckWorker, query_system_status},
+};
+
+/// Redis sorted set holding the deadline of every system (scored by the unix
+/// timestamp of the deadline in milliseconds), so that pending deadlines
+/// survive restarts
+const DEADLINES_KEY: &str = "down_deadlines";
+
+/// How often the sorted set is checked for deadlines that have passed
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The maximum number of deadlines handled at every check
+const POLL_BATCH_SIZE: isize = 100;
+
+/// Removes a deadline only if it was not rescheduled in the meantime (its
+/// score is unchanged), so that a deadline replaced after the down check was
+/// enqueued is not lost
+const REMOVE_DEADLINE_SCRIPT: &str = r#"
+if tonumber(redis.call('ZSCORE', KEYS[1], ARGV[1])) == tonumber(ARGV[2]) then
+    return redis.call('ZREM', KEYS[1], ARGV[1])
+end
+return 0
+"#;
+
+/// Computes the next deadline of a system (the time at which it is considered
+/// down) and stores it, replacing the previous one
+///
+/// The deadline is removed if there is none (e.g. the system was already
+/// alerted, or it never pinged)
+pub async fn schedule_deadline(
+    redis: &RedisPool,
+    db: &PgPool,
+    system_id: Uuid,
+) -> GenericResult<()> {
+    let deadline = query_system_status(db, system_id)
+        .await?
+        .map(|status| status.down_since);
+
+    let mut conn = redis.get().await?;
+
+    match deadline {
+        Some(deadline) => {
+            conn.zadd(
+                DEADLINES_KEY.to_string(),
+                system_id.to_string(),
+                deadline.timestamp_millis(),
+            )
+            .await?;
+        }
+        None => {
+            conn.zrem(DEADLINES_KEY.to_string(), system_id.to_string())
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Schedules the deadline of every system that can still be alerted, used at
+/// startup to pick up the systems that pinged before the deadlines were kept
+pub async fn schedule_all_deadlines(redis: &RedisPool, db: &PgPool) -> GenericResult<()> {
+    let system_ids = sqlx::query_scalar!(
+        r#"
+        SELECT id FROM system WHERE deleted = FALSE AND down_sent_email = FALSE
+        "#
+    )
+    .fetch_all(db)
+    .await?;
+
+    for system_id in system_ids {
+        schedule_deadline(redis, db, system_id).await?;
+    }
+
+    info!("Deadlines: Scheduled the deadlines of all systems");
+
+    Ok(())
+}
+
+/// Enqueues a down check for every system whose deadline has passed, runs
+/// forever
+pub async fn run_deadline_scheduler(redis: RedisPool) -> color_eyre::Result<()> {
+    loop {
+        if let Err(e) = enqueue_due_deadlines(&redis).await {
+            error!("Deadlines: Error enqueueing the due deadlines: {}", e);
+        }
+
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// The number of systems waiting for their deadline
+pub async fn pending_deadlines(redis: &RedisPool) -> GenericResult<u64> {
+    let mut conn = redis.get().await?;
+
+    let count = conn
+        .cmd_with_key("ZCARD", DEADLINES_KEY.to_string())
+        .query_async(conn.unnamespaced_borrow_mut())
+        .await?;
+
+    Ok(count)
+}
+
+async fn enqueue_due_deadlines(redis: &RedisPool) -> GenericResult<()> {
+    let mut conn = redis.get().await?;
+
+    let due: Vec<(String, i64)> = conn
+        .cmd_with_key("ZRANGEBYSCORE", DEADLINES_KEY.to_string())
+        .arg("-inf")
+        .arg(Utc::now().timestamp_millis())
+        .arg("WITHSCORES")
+        .arg("LIMIT")
+        .arg(0)
+        .arg(POLL_BATCH_SIZE)
+        .query_async(conn.unnamespaced_borrow_mut())
+        .await?;
+
+    let remove_deadline = Script::new(REMOVE_DEADLINE_SCRIPT);
+
+    for (member, score) in due {
+        // The check is enqueued before the deadline is removed, so that a failure
+        // in between leaves the deadline to be picked up again. A check enqueued
+        // twice is harmless, as only one of them can open the incident
+        match Uuid::parse_str(&member) {
+            Ok(system_id) => {
+                DownCheckWorker::opts()
+                    .queue("down_checks")
+                    .perform_async(redis, DownCheckArgs { system_id })
+                    .await?;
+            }
+            Err(_) => error!("Deadlines: Invalid system id {}", member),
+        }
+
+        let _: usize = remove_deadline
+            .key(DEADLINES_KEY)
+            .arg(&member)
+            .arg(score)
+            .invoke_async(conn.unnamespaced_borrow_mut())
+            .await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,258 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sidekiq::{RedisPool, Worker};
+use sqlx::PgPool;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    models::{PingKind, ScheduleKind},
+    notifiers::{EventSystem, NotificationEvent, dispatch},
+    web::utils::{schedule::Schedule, time_conversions::pg_interval_to_duration},
+    workers::{GenericError, GenericResult, deadlines::schedule_deadline},
+};
+
+#[derive(Clone)]
+pub struct DownCheckWorker {
+    db: PgPool,
+    redis: RedisPool,
+}
+
+impl DownCheckWorker {
+    pub fn new(db: PgPool, redis: RedisPool) -> Self {
+        Self { db, redis }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownCheckArgs {
+    /// The system whose deadline has passed
+    pub system_id: Uuid,
+}
+
+#[async_trait]
+impl Worker<DownCheckArgs> for DownCheckWorker {
+    async fn perform(&self, args: DownCheckArgs) -> sidekiq::Result<()> {
+        info!(
+            "Scheduled task: Checking whether system {} is down",
+            args.system_id
+        );
+
+        // The system may have pinged (or may have been deleted) since the deadline
+        // was scheduled
+        let Some(status) = query_system_status(&self.db, args.system_id).await? else {
+            return Ok(());
+        };
+
+        // The check ran before the deadline (e.g. the deadline was moved later
+        // since it was enqueued), so schedule it again rather than dropping it
+        if status.down_since > Utc::now() {
+            schedule_deadline(&self.redis, &self.db, args.system_id).await?;
+            return Ok(());
+        }
+
+        // Set the down_sent_email flag to true, so that the system is not alerted
+        // again until it pings successfully (when it is sent a recovery
+        // notification) and open an incident, only the check that sets it sends
+        // the notifications
+        let incident_id = sqlx::query_scalar!(
+            r#"
+            WITH claimed AS (
+                UPDATE system
+                SET down_sent_email = TRUE,
+                    down_since = $2
+                WHERE id = $1
+                  AND down_sent_email = FALSE
+                RETURNING id
+            )
+            INSERT INTO incident (system_id, started_at)
+            SELECT id, $2 FROM claimed
+            RETURNING id
+            "#,
+            args.system_id,
+            status.down_since.naive_utc()
+        )
+        .fetch_optional(&self.db)
+        .await
+        .map_err(|e| {
+            error!("Scheduled task: Error opening the incident: {}", e);
+            GenericError::from(e)
+        })?;
+
+        let Some(incident_id) = incident_id else {
+            return Ok(());
+        };
+
+        let event = NotificationEvent::Down {
+            system: EventSystem {
+                id: status.system_id,
+                name: status.system_name,
+            },
+            down_since: status.down_since,
+            down_after: status.down_after.num_seconds(),
+            last_ping_kind: status.last_ping_kind,
+            last_exit_code: status.last_exit_code,
+            last_output: status.last_body,
+        };
+
+        dispatch(&self.redis, &self.db, Some(incident_id), &event).await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct SystemStatus {
+    pub system_id: Uuid,
+    pub system_name: String,
+    /// The time at which the system is (or will be) considered down
+    pub down_since: DateTime<Utc>,
+    pub down_after: Duration,
+    /// The kind of the latest ping received after the last successful one, used
+    /// to tell a failed or stuck run from a run that never started
+    pub last_ping_kind: Option<PingKind>,
+    /// The exit code reported by the latest ping, if any
+    pub last_exit_code: Option<i32>,
+    /// The output sent along with the failing (or latest) ping, if any
+    pub last_body: Option<String>,
+}
+
+/// Queries the status of a system that has not been alerted yet, `down_since`
+/// is the time at which it is (or will be) considered down: `down_after` past
+/// its latest successful ping (according to its schedule), or right away if it
+/// reported a failure since then
+///
+/// Returns `None` if the system does not exist, was deleted, was already
+/// alerted or never pinged successfully
+pub(crate) async fn query_system_status(
+    db: &PgPool,
+    system_id: Uuid,
+) -> GenericResult<Option<SystemStatus>> {
+    let row = sqlx::query!(
+        r#"
+        SELECT s.id AS system_id,
+               s.name AS system_name,
+               s.starts_at AS system_starts_at,
+               s.down_after,
+               s.frequency,
+               s.schedule_kind AS "schedule_kind: ScheduleKind",
+               s.cron_expression,
+               s.cron_timezone,
+               latest_ping.timestamp AS "timestamp?",
+               latest_attempt.timestamp AS "last_ping_timestamp?",
+               latest_attempt.kind AS "last_ping_kind?: PingKind",
+               latest_attempt.exit_code AS last_exit_code,
+               COALESCE(latest_attempt.body, latest_ping.body) AS last_body
+        FROM system s
+        LEFT JOIN LATERAL (
+            SELECT p.timestamp, p.body
+            FROM ping p
+            WHERE p.system_id = s.id
+              AND p.kind = 'success'
+            ORDER BY p.timestamp DESC
+            LIMIT 1
+        ) latest_ping ON TRUE
+        LEFT JOIN LATERAL (
+            SELECT p.timestamp, p.kind, p.exit_code, p.body
+            FROM ping p
+            WHERE p.system_id = s.id
+              AND p.timestamp > COALESCE(latest_ping.timestamp, '-infinity')
+            ORDER BY p.timestamp DESC
+            LIMIT 1
+        ) latest_attempt ON TRUE
+        WHERE s.id = $1
+          AND s.deleted = FALSE
+          AND s.down_sent_email = FALSE;
+        "#,
+        system_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    let status = row.and_then(|row| {
+        let schedule = Schedule::new(
+            row.schedule_kind,
+            row.frequency,
+            row.cron_expression.as_deref(),
+            row.cron_timezone.as_deref(),
+            row.system_starts_at,
+        )
+        .map_err(|e| {
+            error!(
+                "Scheduled task: Error reading the schedule of system {}: {}",
+                row.system_id, e
+            );
+        })
+        .ok()?;
+        let down_after = pg_interval_to_duration(row.down_after);
+
+        let down_since = match (row.last_ping_kind, row.last_ping_timestamp) {
+            // The job reported a failure (with `/fail` or a nonzero exit code), so
+            // it is down since then
+            (Some(PingKind::Fail), Some(failed_at)) => failed_at.and_utc(),
+            _ => schedule
+                .down_since(row.timestamp?, down_after)
+                .map_err(|e| {
+                    error!("Scheduled task: Error calculating down timestamp: {}", e);
+                })
+                .ok()?
+                .and_utc(),
+        };
+
+        Some(SystemStatus {
+            system_id: row.system_id,
+            system_name: row.system_name,
+            down_since,
+            down_after,
+            last_ping_kind: row.last_ping_kind,
+            last_exit_code: row.last_exit_code,
+            last_body: row.last_body,
+        })
+    });
+
+    Ok(status)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::app::test_app::TestApp;
+
+    use super::*;
+
+    async fn ping(db: &PgPool, system_id: Uuid, kind: PingKind, exit_code: Option<i32>) {
+        sqlx::query!(
+            r#"
+            INSERT INTO ping (system_id, kind, exit_code) VALUES ($1, $2, $3)
+            "#,
+            system_id,
+            kind as PingKind,
+            exit_code
+        )
+        .execute(db)
+        .await
+        .unwrap();
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a PostgreSQL database (set DATABASE_URL)"]
+    async fn test_failures_are_down_right_away(db: PgPool) {
+        let app = TestApp::spawn(db.clone()).await;
+        let user_id = app.create_user("owner@example.com").await;
+
+        for exit_code in [None, Some(2)] {
+            let system_id = app.create_system(user_id, "Backups").await;
+            ping(&db, system_id, PingKind::Success, Some(0)).await;
+
+            let status = query_system_status(&db, system_id).await.unwrap().unwrap();
+            assert!(status.down_since > Utc::now());
+
+            ping(&db, system_id, PingKind::Fail, exit_code).await;
+
+            let status = query_system_status(&db, system_id).await.unwrap().unwrap();
+            assert!(status.down_since <= Utc::now());
+            assert_eq!(status.last_ping_kind, Some(PingKind::Fail));
+            assert_eq!(status.last_exit_code, exit_code);
+        }
+    }
+}
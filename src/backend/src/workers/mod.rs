@@ -1,30 +1,60 @@
 use color_eyre::Result;
-use sidekiq::{Processor, periodic};
+use sidekiq::{Processor, RedisPool};
 use sqlx::PgPool;
 use tracing::info;
 
 use crate::{
-    PRODUCTION,
-    workers::email_worker::{EmailWorker, SmtpClient},
+    notifiers::email::SmtpClient,
+    workers::{down_check_worker::DownCheckWorker, notify_worker::NotifyWorker},
 };
 
-pub(crate) mod email_worker;
+pub(crate) mod deadlines;
+pub(crate) mod down_check_worker;
+pub(crate) mod notify_worker;
+
+pub(crate) type GenericError = Box<dyn std::error::Error + Send + Sync>;
+pub(crate) type GenericResult<T> = Result<T, GenericError>;
 
 pub async fn register_workers(
     p: &mut Processor,
+    redis: RedisPool,
     db: PgPool,
     smtp_client: SmtpClient,
 ) -> Result<()> {
-    if *PRODUCTION {
-        // Add a new periodic job, every 15 minutes
-        periodic::builder("0 */15 * * * *")?
-            .name("Check and send emails for down services")
-            .queue("down_emails")
-            .register(p, EmailWorker::new(db, smtp_client))
+    // Down checks are enqueued by the deadline scheduler when the deadline of a
+    // system passes
+    p.register(DownCheckWorker::new(db.clone(), redis));
+
+    info!("Sidekiq: Registered worker for down checks");
+
+    p.register(NotifyWorker::new(db, smtp_client));
+
+    info!("Sidekiq: Registered worker for notifications");
+
+    Ok(())
+}
+
+/// The jobs waiting to be run: the enqueued ones, plus the ones scheduled to
+/// be retried or run later
+pub async fn pending_jobs(redis: &RedisPool) -> GenericResult<u64> {
+    let mut conn = redis.get().await?;
+    let mut pending = 0;
+
+    for queue in ["down_checks", "notifications"] {
+        let length: u64 = conn
+            .cmd_with_key("LLEN", format!("queue:{queue}"))
+            .query_async(conn.unnamespaced_borrow_mut())
             .await?;
+        pending += length;
+    }
 
-        info!("Sidekiq: Registered periodic job for down emails");
+    for sorted_set in ["retry", "schedule"] {
+        let length: u64 = conn
+            .cmd_with_key("ZCARD", sorted_set.to_string())
+            .query_async(conn.unnamespaced_borrow_mut())
+            .await?;
+        pending += length;
     }
 
-    Ok(())
+    Ok(pending)
 }
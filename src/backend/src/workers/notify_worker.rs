@@ -0,0 +1,172 @@
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use sidekiq::Worker;
+use sqlx::{PgPool, types::Json};
+use tracing::{error, info};
+
+use crate::{
+    ALLOW_PRIVATE_TARGETS,
+    notifiers::{
+        ChannelConfig, NotificationEvent, NotificationTarget, Notifier,
+        email::{EmailNotifier, SmtpClient},
+        message::MessageLocale,
+    },
+    workers::{GenericError, GenericResult},
+};
+
+/// Sends a single notification, failures are retried with backoff by sidekiq
+#[derive(Clone)]
+pub struct NotifyWorker {
+    db: PgPool,
+    smtp_client: SmtpClient,
+}
+
+impl NotifyWorker {
+    pub fn new(db: PgPool, smtp_client: SmtpClient) -> Self {
+        Self { db, smtp_client }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotifyArgs {
+    pub target: NotificationTarget,
+    /// The incident the notification is about, if any
+    #[serde(default)]
+    pub incident_id: Option<i32>,
+    pub event: NotificationEvent,
+}
+
+#[async_trait]
+impl Worker<NotifyArgs> for NotifyWorker {
+    async fn perform(&self, args: NotifyArgs) -> sidekiq::Result<()> {
+        info!(
+            "Scheduled task: Sending {} notification to {:?}",
+            args.event.name(),
+            args.target
+        );
+
+        // The target may have been removed (or the email address changed and not
+        // verified yet) in the meantime
+        let Some(notifier) = self.notifier(&args.target).await? else {
+            return Ok(());
+        };
+
+        notifier.notify(&args.event).await.map_err(|e| {
+            error!(
+                "Scheduled task: Error sending notification to {:?}: {}",
+                args.target, e
+            );
+            e
+        })?;
+
+        if let Some(incident_id) = args.incident_id {
+            sqlx::query!(
+                r#"
+                INSERT INTO incident_notification (incident_id, event, channel)
+                VALUES ($1, $2, $3)
+                "#,
+                incident_id,
+                args.event.name(),
+                notifier.kind()
+            )
+            .execute(&self.db)
+            .await
+            .map_err(|e| {
+                error!(
+                    "Scheduled task: Error recording the notification of the incident {}: {}",
+                    incident_id, e
+                );
+                GenericError::from(e)
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+impl NotifyWorker {
+    async fn notifier(
+        &self,
+        target: &NotificationTarget,
+    ) -> GenericResult<Option<Box<dyn Notifier>>> {
+        match *target {
+            NotificationTarget::Email { user_id } => {
+                let Some(user) = sqlx::query!(
+                    r#"
+                    SELECT email, timezone, language
+                    FROM "user"
+                    WHERE id = $1
+                      AND email_verified_at IS NOT NULL
+                    "#,
+                    user_id
+                )
+                .fetch_optional(&self.db)
+                .await?
+                else {
+                    return Ok(None);
+                };
+
+                let timezone = Tz::from_str(&user.timezone).map_err(|e| {
+                    error!(
+                        "Scheduled task: Error parsing timezone: {}, user email is {}",
+                        e, user.email
+                    );
+                    GenericError::from(e)
+                })?;
+
+                Ok(Some(Box::new(EmailNotifier {
+                    smtp_client: self.smtp_client.clone(),
+                    user_email: user.email,
+                    timezone,
+                    language: user.language,
+                })))
+            }
+            NotificationTarget::Channel { channel_id } => {
+                let Some(channel) = sqlx::query!(
+                    r#"
+                    SELECT c.config AS "config: Json<ChannelConfig>",
+                           u.timezone,
+                           u.language
+                    FROM notification_channel c
+                        JOIN "user" u ON c.user_id = u.id
+                    WHERE c.id = $1
+                    "#,
+                    channel_id
+                )
+                .fetch_optional(&self.db)
+                .await?
+                else {
+                    return Ok(None);
+                };
+
+                let timezone = Tz::from_str(&channel.timezone).map_err(|e| {
+                    error!(
+                        "Scheduled task: Error parsing timezone: {}, channel id is {}",
+                        e, channel_id
+                    );
+                    GenericError::from(e)
+                })?;
+
+                let locale = MessageLocale {
+                    language: channel.language,
+                    timezone,
+                };
+
+                // Channels added before the private addresses were rejected are
+                // skipped (retrying would not help)
+                if let Err(e) = channel.config.0.check_targets(*ALLOW_PRIVATE_TARGETS) {
+                    error!(
+                        "Scheduled task: Not sending to channel {}: {}",
+                        channel_id, e
+                    );
+                    return Ok(None);
+                }
+
+                Ok(Some(channel.config.0.into_notifier(locale)))
+            }
+        }
+    }
+}